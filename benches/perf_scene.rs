@@ -0,0 +1,48 @@
+//! Compares rendering perf_scene with a plain HittableList against a Bvh
+//!
+//! Run with `cargo bench`
+use rtweekend::{render, scenes, Bvh, Camera, HittableList, RandState, RenderParams, Vec3};
+use std::time::Instant;
+
+fn camera() -> Camera {
+    Camera::new(
+        Vec3::new(13.0, 2.0, 3.0),
+        Vec3::new(0.0, 0.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        20.0,
+        3.0 / 2.0,
+        0.0,
+        1.0,
+    )
+}
+fn params() -> RenderParams {
+    RenderParams {
+        image_width: 300,
+        image_height: 200,
+        samples_per_px: 10,
+        max_depth: 50,
+        ..Default::default()
+    }
+}
+
+fn main() {
+    let mut rand = RandState::new();
+
+    let world = scenes::perf_scene();
+    let start = Instant::now();
+    render(camera(), world, &HittableList::new(), params(), &mut rand);
+    let list_time = start.elapsed();
+
+    let world = scenes::perf_scene();
+    let start = Instant::now();
+    let bvh = Bvh::new(world);
+    let build_time = start.elapsed();
+
+    let start = Instant::now();
+    render(camera(), bvh, &HittableList::new(), params(), &mut rand);
+    let bvh_time = start.elapsed();
+
+    println!("HittableList:       {:?}", list_time);
+    println!("Bvh:                {:?}", bvh_time);
+    println!("Building the Bvh:   {:?}", build_time);
+}
@@ -0,0 +1,317 @@
+use super::{HitRecord, Hittable, HittableList, HittableType, Ray, Vec3};
+
+/// Axis aligned bounding box
+#[derive(Debug, Clone, Copy)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+impl Aabb {
+    pub const fn new(min: Vec3, max: Vec3) -> Self {
+        Self { min, max }
+    }
+    /// A box that contains nothing, so surrounding it with any other box returns that box
+    pub const fn empty() -> Self {
+        Self {
+            min: Vec3::repeat(f64::INFINITY),
+            max: Vec3::repeat(f64::NEG_INFINITY),
+        }
+    }
+    pub fn surrounding(&self, other: &Aabb) -> Aabb {
+        let (a, b) = (self, other);
+        Aabb {
+            min: Vec3::new(
+                a.min.x().min(b.min.x()),
+                a.min.y().min(b.min.y()),
+                a.min.z().min(b.min.z()),
+            ),
+            max: Vec3::new(
+                a.max.x().max(b.max.x()),
+                a.max.y().max(b.max.y()),
+                a.max.z().max(b.max.z()),
+            ),
+        }
+    }
+    /// The box grown a bit on every side. Flat shapes have a flat box, and the padding keeps the
+    /// slab test stable for them
+    pub fn padded(&self) -> Aabb {
+        let padding = Vec3::repeat(1e-4);
+        Aabb::new(self.min - padding, self.max + padding)
+    }
+    pub fn centroid(&self) -> Vec3 {
+        0.5 * (self.min + self.max)
+    }
+    pub fn surface_area(&self) -> f64 {
+        let [x, y, z] = (self.max - self.min).0;
+        if x < 0.0 || y < 0.0 || z < 0.0 {
+            return 0.0;
+        }
+        2.0 * (x * y + y * z + z * x)
+    }
+    pub fn longest_axis(&self) -> usize {
+        let [x, y, z] = (self.max - self.min).0;
+        if x > y && x > z {
+            0
+        } else if y > z {
+            1
+        } else {
+            2
+        }
+    }
+    /// Slab test. Only tells us whether the ray hits the box between t_min and t_max
+    pub fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> bool {
+        self.interval(ray, t_min, t_max).is_some()
+    }
+    /// Part of [t_min, t_max] where the ray is inside the box, if any
+    pub fn interval(&self, ray: &Ray, mut t_min: f64, mut t_max: f64) -> Option<(f64, f64)> {
+        for a in 0..3 {
+            let inv_d = 1.0 / ray.dir.0[a];
+            let mut t0 = (self.min.0[a] - ray.orig.0[a]) * inv_d;
+            let mut t1 = (self.max.0[a] - ray.orig.0[a]) * inv_d;
+            if inv_d < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            t_min = t0.max(t_min);
+            t_max = t1.min(t_max);
+            if t_max < t_min {
+                return None;
+            }
+        }
+        Some((t_min, t_max))
+    }
+}
+
+struct BvhNode {
+    bbox: Aabb,
+    /// For leaves, the index of the first object. For interior nodes, the index of the right child
+    /// (The left child is always the next node)
+    start: usize,
+    /// Number of objects in a leaf. Zero for interior nodes
+    count: usize,
+    /// Axis the node was split along, used to visit the closest child first
+    axis: usize,
+}
+
+struct BuildPrim {
+    idx: usize,
+    bbox: Aabb,
+    centroid: Vec3,
+}
+
+const SAH_BINS: usize = 16;
+const MAX_LEAF_SIZE: usize = 4;
+/// Cost of visiting a node relative to intersecting an object
+const TRAVERSAL_COST: f64 = 0.125;
+
+/// Bounding volume hierarchy, built using the surface area heuristic (With a midpoint split fallback)
+///
+/// The nodes are stored in a flat array in depth first order. Objects without a bounding box (Like
+/// planes) can't go in the tree, so they are kept apart and checked for every ray
+pub struct Bvh<T: Hittable = HittableType> {
+    nodes: Vec<BvhNode>,
+    objects: Vec<T>,
+    unbounded: Vec<T>,
+}
+impl Bvh {
+    pub fn new(list: HittableList) -> Self {
+        Self::from_objects(list.0)
+    }
+}
+impl<T: Hittable> Bvh<T> {
+    pub fn from_objects(objects: Vec<T>) -> Self {
+        let (objects, unbounded): (Vec<_>, Vec<_>) = objects
+            .into_iter()
+            .partition(|obj| obj.bounding_box(&mut Aabb::empty()));
+        let mut prims = objects
+            .iter()
+            .enumerate()
+            .map(|(idx, obj)| {
+                let mut bbox = Aabb::empty();
+                obj.bounding_box(&mut bbox);
+                BuildPrim {
+                    idx,
+                    bbox,
+                    centroid: bbox.centroid(),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let mut nodes = Vec::with_capacity(2 * objects.len());
+        if !prims.is_empty() {
+            build_recursive(&mut nodes, &mut prims, 0);
+        }
+
+        // Reorder the objects so that every leaf references a contiguous range
+        let mut objects = objects.into_iter().map(Some).collect::<Vec<_>>();
+        let objects = prims
+            .iter()
+            .map(|prim| objects[prim.idx].take().unwrap())
+            .collect();
+
+        Self {
+            nodes,
+            objects,
+            unbounded,
+        }
+    }
+
+    fn hit_node(
+        &self,
+        node_idx: usize,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        out: &mut HitRecord,
+    ) -> bool {
+        let node = &self.nodes[node_idx];
+        if !node.bbox.hit(ray, t_min, t_max) {
+            return false;
+        }
+
+        if node.count > 0 {
+            let mut hit_anything = false;
+            let mut closest_so_far = t_max;
+            let mut tmp_record = HitRecord::default();
+            for obj in &self.objects[node.start..node.start + node.count] {
+                if obj.hit(ray, t_min, closest_so_far, &mut tmp_record) {
+                    hit_anything = true;
+                    closest_so_far = tmp_record.t;
+                    *out = tmp_record.clone();
+                }
+            }
+            return hit_anything;
+        }
+
+        let (first, second) = if ray.dir.0[node.axis] < 0.0 {
+            (node.start, node_idx + 1)
+        } else {
+            (node_idx + 1, node.start)
+        };
+        let hit_first = self.hit_node(first, ray, t_min, t_max, out);
+        let t_max = if hit_first { out.t } else { t_max };
+        let hit_second = self.hit_node(second, ray, t_min, t_max, out);
+        hit_first || hit_second
+    }
+}
+impl<T: Hittable> Hittable for Bvh<T> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, out: &mut HitRecord) -> bool {
+        let mut hit_anything = false;
+        let mut closest_so_far = t_max;
+        let mut tmp_record = HitRecord::default();
+        for obj in &self.unbounded {
+            if obj.hit(ray, t_min, closest_so_far, &mut tmp_record) {
+                hit_anything = true;
+                closest_so_far = tmp_record.t;
+                *out = tmp_record.clone();
+            }
+        }
+        if self.nodes.is_empty() {
+            return hit_anything;
+        }
+        self.hit_node(0, ray, t_min, closest_so_far, out) || hit_anything
+    }
+    fn bounding_box(&self, output_box: &mut Aabb) -> bool {
+        match self.nodes.first() {
+            Some(root) if self.unbounded.is_empty() => {
+                *output_box = root.bbox;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+fn build_recursive(nodes: &mut Vec<BvhNode>, prims: &mut [BuildPrim], offset: usize) -> usize {
+    let bbox = prims
+        .iter()
+        .fold(Aabb::empty(), |acc, prim| acc.surrounding(&prim.bbox));
+    let node_idx = nodes.len();
+    nodes.push(BvhNode {
+        bbox,
+        start: offset,
+        count: prims.len(),
+        axis: 0,
+    });
+    if prims.len() == 1 {
+        return node_idx;
+    }
+
+    let centroid_bounds = prims.iter().fold(Aabb::empty(), |acc, prim| {
+        acc.surrounding(&Aabb::new(prim.centroid, prim.centroid))
+    });
+    let axis = centroid_bounds.longest_axis();
+    let axis_min = centroid_bounds.min.0[axis];
+    let extent = centroid_bounds.max.0[axis] - axis_min;
+    if extent <= 0.0 {
+        // Every centroid is in the same place, there's no sensible way to split these
+        return node_idx;
+    }
+
+    let bin_of = |prim: &BuildPrim| {
+        let bin = ((prim.centroid.0[axis] - axis_min) / extent * SAH_BINS as f64) as usize;
+        bin.min(SAH_BINS - 1)
+    };
+    let mut bin_counts = [0usize; SAH_BINS];
+    let mut bin_boxes = [Aabb::empty(); SAH_BINS];
+    for prim in prims.iter() {
+        let bin = bin_of(prim);
+        bin_counts[bin] += 1;
+        bin_boxes[bin] = bin_boxes[bin].surrounding(&prim.bbox);
+    }
+
+    // Cost of splitting after each bin, sweeping from both sides
+    let mut right_area = [0.0; SAH_BINS];
+    let mut acc = Aabb::empty();
+    for bin in (1..SAH_BINS).rev() {
+        acc = acc.surrounding(&bin_boxes[bin]);
+        right_area[bin - 1] = acc.surface_area();
+    }
+    let mut best_split = 0;
+    let mut best_cost = f64::INFINITY;
+    let mut acc = Aabb::empty();
+    let mut left_count = 0;
+    for split in 0..SAH_BINS - 1 {
+        acc = acc.surrounding(&bin_boxes[split]);
+        left_count += bin_counts[split];
+        let right_count = prims.len() - left_count;
+        let cost = left_count as f64 * acc.surface_area() + right_count as f64 * right_area[split];
+        if cost < best_cost {
+            best_cost = cost;
+            best_split = split;
+        }
+    }
+    let split_cost = TRAVERSAL_COST + best_cost / bbox.surface_area();
+    if prims.len() <= MAX_LEAF_SIZE && split_cost >= prims.len() as f64 {
+        return node_idx;
+    }
+
+    let mut mid = partition(prims, |prim| bin_of(prim) <= best_split);
+    if mid == 0 || mid == prims.len() {
+        // SAH could not separate them, fall back to splitting at the median centroid
+        mid = prims.len() / 2;
+        prims.select_nth_unstable_by(mid, |a, b| {
+            a.centroid.0[axis].partial_cmp(&b.centroid.0[axis]).unwrap()
+        });
+    }
+
+    let (left, right) = prims.split_at_mut(mid);
+    build_recursive(nodes, left, offset);
+    let right_idx = build_recursive(nodes, right, offset + mid);
+    let node = &mut nodes[node_idx];
+    node.start = right_idx;
+    node.count = 0;
+    node.axis = axis;
+    node_idx
+}
+
+/// Moves the elements for which pred is true to the front, returning how many there are
+fn partition<T>(slice: &mut [T], pred: impl Fn(&T) -> bool) -> usize {
+    let mut first_false = 0;
+    for i in 0..slice.len() {
+        if pred(&slice[i]) {
+            slice.swap(i, first_false);
+            first_false += 1;
+        }
+    }
+    first_false
+}
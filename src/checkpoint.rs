@@ -0,0 +1,180 @@
+//! Checkpoints of a `ProgressiveRender`, so a long render can go on after a crash or a reboot
+//!
+//! A checkpoint has the sum of the samples of every pixel, how many it took, and the statistics
+//! of adaptive sampling, as exact little-endian floats. The random numbers of every sample only
+//! depend on the render seed, which is saved too, so a resumed render ends with the same image
+//! as one that was never stopped. The scene isn't saved: it has to be resumed with the same
+//! world, and the camera and render parameters are checked against the ones in the checkpoint
+use super::{
+    Background, Camera, HittableList, PixelStats, PixelSum, ProgressiveRender, RenderParams, Vec3,
+    World,
+};
+use std::io::Write;
+use std::path::Path;
+
+/// Starts every checkpoint. The number goes up whenever the format changes
+const MAGIC: &[u8] = b"rtweekend checkpoint 2\n";
+
+/// Camera and render parameters that change the image, which have to match to resume. The
+/// tiles don't change it, so they can be different
+fn settings(camera: &Camera, params: &RenderParams) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut float = |x: f64| out.extend_from_slice(&x.to_le_bytes());
+    for v in [
+        camera.origin,
+        camera.lower_left_corner,
+        camera.horizontal,
+        camera.vertical,
+        camera.u,
+        camera.v,
+    ]
+    .iter()
+    {
+        v.0.iter().for_each(|&x| float(x));
+    }
+    float(camera.lens_radius);
+    float(camera.shutter_open);
+    float(camera.shutter_close);
+    float(params.noise_threshold);
+    float(params.roulette_probability);
+    match params.background {
+        Background::Gradient { bottom, top } => {
+            bottom.0.iter().chain(top.0.iter()).for_each(|&x| float(x))
+        }
+        Background::Color(color) => color.0.iter().for_each(|&x| float(x)),
+    }
+    out.push(matches!(params.background, Background::Color(_)) as u8);
+    out.extend_from_slice(&params.image_width.to_le_bytes());
+    out.extend_from_slice(&params.image_height.to_le_bytes());
+    out.extend_from_slice(&params.samples_per_px.to_le_bytes());
+    out.extend_from_slice(&params.min_samples_per_px.to_le_bytes());
+    out.extend_from_slice(&params.max_depth.to_le_bytes());
+    out.extend_from_slice(&params.roulette_depth.unwrap_or(-1).to_le_bytes());
+    out.extend_from_slice(params.sampler.name().as_bytes());
+    out
+}
+
+impl<W> ProgressiveRender<'_, W> {
+    /// Writes everything rendered so far
+    pub fn write_checkpoint(&self, out: &mut impl Write) -> std::io::Result<()> {
+        let settings = settings(&self.camera, &self.params);
+        out.write_all(MAGIC)?;
+        out.write_all(&(settings.len() as u32).to_le_bytes())?;
+        out.write_all(&settings)?;
+        out.write_all(&self.seed.to_le_bytes())?;
+        out.write_all(&self.passes.to_le_bytes())?;
+        out.write_all(&self.samples_taken.to_le_bytes())?;
+        for pixel in &self.pixels {
+            for channel in pixel.color.0.iter() {
+                out.write_all(&channel.to_le_bytes())?;
+            }
+            out.write_all(&pixel.samples.to_le_bytes())?;
+            out.write_all(&pixel.stats.count.to_le_bytes())?;
+            out.write_all(&pixel.stats.mean.to_le_bytes())?;
+            out.write_all(&pixel.stats.m2.to_le_bytes())?;
+            out.write_all(&[pixel.converged as u8])?;
+        }
+        out.flush()
+    }
+
+    /// Writes a checkpoint to `path`. It's written to a temporary file first and then renamed, so
+    /// a crash while saving doesn't lose the checkpoint before
+    pub fn save_checkpoint(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let path = path.as_ref();
+        let mut temp = path.as_os_str().to_owned();
+        temp.push(".tmp");
+        let mut out = std::io::BufWriter::new(std::fs::File::create(&temp)?);
+        self.write_checkpoint(&mut out)?;
+        out.into_inner()?.sync_all()?;
+        std::fs::rename(&temp, path)
+    }
+}
+
+/// Reads the checkpoint one value at a time
+struct Reader<'a>(&'a [u8]);
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, count: usize) -> Result<&'a [u8], String> {
+        if self.0.len() < count {
+            return Err("the checkpoint is truncated".to_string());
+        }
+        let (bytes, rest) = self.0.split_at(count);
+        self.0 = rest;
+        Ok(bytes)
+    }
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.bytes(N)?);
+        Ok(array)
+    }
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+    fn u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+    fn f64(&mut self) -> Result<f64, String> {
+        Ok(f64::from_le_bytes(self.array()?))
+    }
+}
+
+impl<'a, W: World> ProgressiveRender<'a, W> {
+    /// Picks a render up where `write_checkpoint` left it. Takes the same arguments as `new`,
+    /// except for the random state
+    pub fn from_checkpoint(
+        camera: Camera,
+        world: W,
+        lights: &'a HittableList,
+        params: RenderParams,
+        checkpoint: &[u8],
+    ) -> Result<Self, String> {
+        let reader = &mut Reader(checkpoint);
+        if !checkpoint.starts_with(MAGIC) {
+            return Err(match checkpoint.starts_with(b"rtweekend checkpoint ") {
+                true => "the checkpoint is from another version of rtweekend".to_string(),
+                false => "not a checkpoint".to_string(),
+            });
+        }
+        reader.bytes(MAGIC.len())?;
+        let length = reader.u32()? as usize;
+        if reader.bytes(length)? != settings(&camera, &params) {
+            return Err(
+                "the checkpoint is of a render with a different camera or parameters".to_string(),
+            );
+        }
+        let seed = reader.u64()?;
+        let mut progressive = Self::with_seed(camera, world, lights, params, seed);
+        progressive.passes = reader.u32()?;
+        progressive.samples_taken = reader.u32()?;
+        for pixel in &mut progressive.pixels {
+            let color = [reader.f64()?, reader.f64()?, reader.f64()?];
+            *pixel = PixelSum {
+                color: Vec3(color),
+                samples: reader.u32()?,
+                stats: PixelStats {
+                    count: reader.u32()?,
+                    mean: reader.f64()?,
+                    m2: reader.f64()?,
+                },
+                converged: reader.bytes(1)?[0] != 0,
+            };
+        }
+        if !reader.0.is_empty() {
+            return Err("the checkpoint has more pixels than the image".to_string());
+        }
+        Ok(progressive)
+    }
+
+    /// Loads a checkpoint saved by `save_checkpoint`
+    pub fn resume(
+        camera: Camera,
+        world: W,
+        lights: &'a HittableList,
+        params: RenderParams,
+        path: impl AsRef<Path>,
+    ) -> Result<Self, String> {
+        let path = path.as_ref();
+        let checkpoint = std::fs::read(path)
+            .map_err(|err| format!("could not read {}: {}", path.display(), err))?;
+        Self::from_checkpoint(camera, world, lights, params, &checkpoint)
+    }
+}
@@ -0,0 +1,608 @@
+//! Command line arguments of the rtweekend binary
+use super::{
+    sampler::SamplerKind,
+    scene_file::{self, CameraDesc},
+    scenes,
+    tiles::TileOrder,
+    Background, HittableList, RandState, RenderParams, Vec3,
+};
+use std::path::PathBuf;
+
+struct BuiltinScene {
+    name: &'static str,
+    build: fn(&mut RandState) -> HittableList,
+    /// Copies of the lights in the world, to sample them
+    lights: fn() -> HittableList,
+    camera: fn() -> CameraDesc,
+    background: Background,
+}
+
+const BUILTIN_SCENES: &[BuiltinScene] = &[
+    BuiltinScene {
+        name: "random",
+        build: scenes::random_scene,
+        lights: HittableList::new,
+        camera: CameraDesc::default,
+        background: Background::SKY,
+    },
+    BuiltinScene {
+        name: "bouncing",
+        build: scenes::bouncing_scene,
+        lights: HittableList::new,
+        camera: || CameraDesc {
+            shutter_close: 1.0,
+            ..CameraDesc::default()
+        },
+        background: Background::SKY,
+    },
+    BuiltinScene {
+        name: "pastel",
+        build: scenes::pastel_scene,
+        lights: HittableList::new,
+        camera: CameraDesc::default,
+        background: Background::PASTEL_SKY,
+    },
+    BuiltinScene {
+        name: "moon",
+        build: scenes::moon_scene,
+        lights: HittableList::new,
+        camera: CameraDesc::default,
+        background: Background::SKY,
+    },
+    BuiltinScene {
+        name: "red_blue",
+        build: |_| scenes::red_blue_scene(),
+        lights: HittableList::new,
+        camera: || CameraDesc {
+            lookfrom: Vec3::new(0.0, 0.0, 0.0),
+            lookat: Vec3::new(0.0, 0.0, -1.0),
+            vertical_fov_degrees: 90.0,
+            ..CameraDesc::default()
+        },
+        background: Background::SKY,
+    },
+    BuiltinScene {
+        name: "normal",
+        build: |_| scenes::normal_scene(),
+        lights: HittableList::new,
+        camera: || CameraDesc {
+            lookfrom: Vec3::new(-2.0, 2.0, 1.0),
+            lookat: Vec3::new(0.0, 0.0, -1.0),
+            vertical_fov_degrees: 40.0,
+            ..CameraDesc::default()
+        },
+        background: Background::SKY,
+    },
+    BuiltinScene {
+        name: "perf",
+        build: |_| scenes::perf_scene(),
+        lights: HittableList::new,
+        camera: CameraDesc::default,
+        background: Background::SKY,
+    },
+    BuiltinScene {
+        name: "triangle",
+        build: |_| scenes::triangle_scene(),
+        lights: HittableList::new,
+        camera: CameraDesc::default,
+        background: Background::SKY,
+    },
+    BuiltinScene {
+        name: "light",
+        build: |_| scenes::light_scene(),
+        lights: scenes::light_scene_lights,
+        camera: || CameraDesc {
+            lookfrom: Vec3::new(26.0, 3.0, 6.0),
+            lookat: Vec3::new(0.0, 2.0, 0.0),
+            ..CameraDesc::default()
+        },
+        background: Background::BLACK,
+    },
+    BuiltinScene {
+        name: "instances",
+        build: |_| scenes::instance_scene(),
+        lights: HittableList::new,
+        camera: || CameraDesc {
+            lookfrom: Vec3::new(0.0, 5.0, 12.0),
+            lookat: Vec3::new(0.0, 1.0, 0.0),
+            vertical_fov_degrees: 35.0,
+            ..CameraDesc::default()
+        },
+        background: Background::SKY,
+    },
+    BuiltinScene {
+        name: "cornell",
+        build: |_| scenes::cornell_box(),
+        lights: scenes::cornell_box_lights,
+        camera: || CameraDesc {
+            lookfrom: Vec3::new(278.0, 278.0, -800.0),
+            lookat: Vec3::new(278.0, 278.0, 0.0),
+            vertical_fov_degrees: 40.0,
+            aspect_ratio: 1.0,
+            ..CameraDesc::default()
+        },
+        background: Background::BLACK,
+    },
+    BuiltinScene {
+        name: "cornell_smoke",
+        build: |_| scenes::cornell_smoke(),
+        lights: scenes::cornell_smoke_lights,
+        camera: || CameraDesc {
+            lookfrom: Vec3::new(278.0, 278.0, -800.0),
+            lookat: Vec3::new(278.0, 278.0, 0.0),
+            vertical_fov_degrees: 40.0,
+            aspect_ratio: 1.0,
+            ..CameraDesc::default()
+        },
+        background: Background::BLACK,
+    },
+    BuiltinScene {
+        name: "cloud",
+        build: |_| scenes::cloud_scene(),
+        lights: HittableList::new,
+        camera: || CameraDesc {
+            lookfrom: Vec3::new(0.0, 2.5, 12.0),
+            lookat: Vec3::new(0.0, 2.0, 0.0),
+            vertical_fov_degrees: 35.0,
+            ..CameraDesc::default()
+        },
+        background: Background::SKY,
+    },
+    BuiltinScene {
+        name: "texture",
+        build: |_| scenes::texture_scene(),
+        lights: HittableList::new,
+        camera: CameraDesc::default,
+        background: Background::SKY,
+    },
+];
+
+const USAGE: &str = "\
+Usage: rtweekend [OPTIONS]
+
+Renders a scene and writes it as an image (To stdout by default)
+
+Scene:
+    --scene NAME          Built-in scene to render (Default: random). One of:
+                          {SCENES}
+    --scene-file PATH     Scene file to render instead of a built-in scene
+
+Image:
+    --width PX            Image width (Default: 1200)
+    --height PX           Image height (Default: width / aspect)
+    --aspect RATIO        Aspect ratio, used when the width or height is missing (Default: 1.5)
+    --samples N           Samples per pixel (Default: 200)
+    --depth N             Maximum number of bounces per ray (Default: 50)
+    --roulette DEPTH,P    Bounces before Russian roulette can end a ray, and the highest chance
+                          of going on after them, like 5,0.95 (Default: off)
+    --adaptive MIN,NOISE  Stop sampling a pixel after MIN samples once the standard error of its
+                          brightness over the brightness is below NOISE (Default: off)
+    --heatmap PATH        Also write an image of how many samples each pixel took (png or ppm)
+    --passes N            Render the samples in N passes, writing the image after every one, to
+                          watch it get less noisy (Needs --output)
+    --checkpoint PATH     Save the render to PATH every few minutes, and resume from it if it's
+                          already there, with the same options. Without --passes, every pass is
+                          one sample per pixel
+    --checkpoint-every S  Seconds between checkpoints, saved at the end of a pass (Default: 300)
+    --sampler NAME        Where the random numbers of the samples come from (Default:
+                          independent). One of: {SAMPLERS}
+
+Camera:
+    --lookfrom X,Y,Z      Camera position
+    --lookat X,Y,Z        Point the camera looks at
+    --up X,Y,Z            Up direction of the camera
+    --fov DEGREES         Vertical field of view
+    --aperture A          Lens aperture, 0 for no depth of field
+    --focus DIST          Distance to the focus plane
+    --shutter OPEN,CLOSE  Times when the shutter opens and closes, for motion blur
+
+Other:
+    --seed N              Seed for the scene and the render (Default: fixed)
+    --threads N           Number of threads (Needs the parallel feature)
+    --tiles SIZE,ORDER    Size of the square tiles the threads take one at a time, and the order
+                          they go in (Default: 32,scanline). Orders: {TILE_ORDERS}
+    --output PATH         File to write the image to (Default: stdout)
+    --format FORMAT       Image format (Default: from the output extension, or ppm). One of:
+                          ppm, png, or the linear hdr (Radiance), pfm and exr
+    --help                Print this message
+";
+
+pub fn usage() -> String {
+    let names = BUILTIN_SCENES
+        .iter()
+        .map(|scene| scene.name)
+        .collect::<Vec<_>>();
+    let samplers = SamplerKind::ALL
+        .iter()
+        .map(|kind| kind.name())
+        .collect::<Vec<_>>();
+    let tile_orders = TileOrder::ALL
+        .iter()
+        .map(|order| order.name())
+        .collect::<Vec<_>>();
+    USAGE
+        .replace("{SCENES}", &names.join(", "))
+        .replace("{SAMPLERS}", &samplers.join(", "))
+        .replace("{TILE_ORDERS}", &tile_orders.join(", "))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Ppm,
+    Png,
+    /// Radiance RGBE
+    Hdr,
+    Pfm,
+    Exr,
+}
+impl OutputFormat {
+    fn from_name(name: &str) -> Option<OutputFormat> {
+        match name.to_ascii_lowercase().as_str() {
+            "ppm" => Some(OutputFormat::Ppm),
+            "png" => Some(OutputFormat::Png),
+            "hdr" => Some(OutputFormat::Hdr),
+            "pfm" => Some(OutputFormat::Pfm),
+            "exr" => Some(OutputFormat::Exr),
+            _ => None,
+        }
+    }
+    /// Whether the format stores linear radiance (From `render_hdr`) instead of 8-bit colors
+    pub fn is_hdr(self) -> bool {
+        matches!(
+            self,
+            OutputFormat::Hdr | OutputFormat::Pfm | OutputFormat::Exr
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SceneSource {
+    Builtin(String),
+    File(PathBuf),
+}
+
+/// Everything given on the command line. Options that weren't given are None
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Args {
+    pub scene: Option<SceneSource>,
+    pub width: Option<i64>,
+    pub height: Option<i64>,
+    pub aspect: Option<f64>,
+    pub samples: Option<u32>,
+    pub depth: Option<i32>,
+    pub roulette: Option<(i32, f64)>,
+    pub adaptive: Option<(u32, f64)>,
+    pub sampler: Option<SamplerKind>,
+    pub passes: Option<u32>,
+    pub checkpoint: Option<PathBuf>,
+    pub checkpoint_every: Option<u64>,
+    pub lookfrom: Option<Vec3>,
+    pub lookat: Option<Vec3>,
+    pub up: Option<Vec3>,
+    pub fov: Option<f64>,
+    pub aperture: Option<f64>,
+    pub focus: Option<f64>,
+    pub shutter: Option<(f64, f64)>,
+    pub seed: Option<u64>,
+    pub threads: Option<usize>,
+    pub tiles: Option<(u32, TileOrder)>,
+    pub output: Option<PathBuf>,
+    pub format: Option<OutputFormat>,
+    pub heatmap: Option<PathBuf>,
+    pub help: bool,
+}
+
+fn parse_value<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value '{}' for {}", value, flag))
+}
+fn parse_vec3(flag: &str, value: &str) -> Result<Vec3, String> {
+    let parts = value.split(',').collect::<Vec<_>>();
+    if parts.len() != 3 {
+        return Err(format!(
+            "invalid value '{}' for {} (Expected X,Y,Z)",
+            value, flag
+        ));
+    }
+    Ok(Vec3::new(
+        parse_value(flag, parts[0])?,
+        parse_value(flag, parts[1])?,
+        parse_value(flag, parts[2])?,
+    ))
+}
+fn positive<T: PartialOrd + Default>(flag: &str, value: T) -> Result<T, String> {
+    if value > T::default() {
+        Ok(value)
+    } else {
+        Err(format!("{} must be greater than 0", flag))
+    }
+}
+
+/// Parses the arguments, without the program name
+pub fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Args, String> {
+    let mut out = Args::default();
+    let mut args = args.into_iter();
+    while let Some(flag) = args.next() {
+        if flag == "--help" || flag == "-h" {
+            out.help = true;
+            continue;
+        }
+        let value = match args.next() {
+            Some(value) => value,
+            None if flag.starts_with("--") => return Err(format!("missing value for {}", flag)),
+            None => return Err(format!("unexpected argument '{}'", flag)),
+        };
+        let f = flag.as_str();
+        let value = value.as_str();
+        match f {
+            "--scene" => {
+                if !BUILTIN_SCENES.iter().any(|scene| scene.name == value) {
+                    return Err(format!("unknown scene '{}'", value));
+                }
+                out.scene = Some(SceneSource::Builtin(value.to_string()));
+            }
+            "--scene-file" => out.scene = Some(SceneSource::File(PathBuf::from(value))),
+            "--width" => out.width = Some(positive(f, parse_value(f, value)?)?),
+            "--height" => out.height = Some(positive(f, parse_value(f, value)?)?),
+            "--aspect" => out.aspect = Some(positive(f, parse_value(f, value)?)?),
+            "--samples" => out.samples = Some(positive(f, parse_value(f, value)?)?),
+            "--depth" => out.depth = Some(positive(f, parse_value(f, value)?)?),
+            "--roulette" => {
+                let parts = value.split(',').collect::<Vec<_>>();
+                if parts.len() != 2 {
+                    return Err(format!(
+                        "invalid value '{}' for {} (Expected DEPTH,P)",
+                        value, f
+                    ));
+                }
+                let (depth, probability) = (parse_value(f, parts[0])?, parse_value(f, parts[1])?);
+                if depth < 0 {
+                    return Err("--roulette depth can't be negative".to_string());
+                }
+                if !(0.0 < probability && probability <= 1.0) {
+                    return Err("--roulette probability must be between 0 and 1".to_string());
+                }
+                out.roulette = Some((depth, probability));
+            }
+            "--adaptive" => {
+                let parts = value.split(',').collect::<Vec<_>>();
+                if parts.len() != 2 {
+                    return Err(format!(
+                        "invalid value '{}' for {} (Expected MIN,NOISE)",
+                        value, f
+                    ));
+                }
+                let min_samples = parse_value(f, parts[0])?;
+                let noise_threshold = positive(f, parse_value(f, parts[1])?)?;
+                out.adaptive = Some((min_samples, noise_threshold));
+            }
+            "--sampler" => {
+                out.sampler = Some(
+                    SamplerKind::from_name(value)
+                        .ok_or_else(|| format!("unknown sampler '{}'", value))?,
+                )
+            }
+            "--passes" => out.passes = Some(positive(f, parse_value(f, value)?)?),
+            "--lookfrom" => out.lookfrom = Some(parse_vec3(f, value)?),
+            "--lookat" => out.lookat = Some(parse_vec3(f, value)?),
+            "--up" => out.up = Some(parse_vec3(f, value)?),
+            "--fov" => {
+                let fov = parse_value(f, value)?;
+                if !(0.0 < fov && fov < 180.0) {
+                    return Err("--fov must be between 0 and 180 degrees".to_string());
+                }
+                out.fov = Some(fov);
+            }
+            "--aperture" => {
+                let aperture: f64 = parse_value(f, value)?;
+                if aperture < 0.0 {
+                    return Err("--aperture can't be negative".to_string());
+                }
+                out.aperture = Some(aperture);
+            }
+            "--focus" => out.focus = Some(positive(f, parse_value(f, value)?)?),
+            "--shutter" => {
+                let parts = value.split(',').collect::<Vec<_>>();
+                if parts.len() != 2 {
+                    return Err(format!(
+                        "invalid value '{}' for {} (Expected OPEN,CLOSE)",
+                        value, f
+                    ));
+                }
+                let (open, close) = (parse_value(f, parts[0])?, parse_value(f, parts[1])?);
+                if close < open {
+                    return Err("the shutter can't close before it opens".to_string());
+                }
+                out.shutter = Some((open, close));
+            }
+            "--seed" => out.seed = Some(parse_value(f, value)?),
+            "--threads" => out.threads = Some(positive(f, parse_value(f, value)?)?),
+            "--tiles" => {
+                let parts = value.split(',').collect::<Vec<_>>();
+                if parts.len() != 2 {
+                    return Err(format!(
+                        "invalid value '{}' for {} (Expected SIZE,ORDER)",
+                        value, f
+                    ));
+                }
+                let size = positive(f, parse_value(f, parts[0])?)?;
+                let order = TileOrder::from_name(parts[1])
+                    .ok_or_else(|| format!("unknown tile order '{}'", parts[1]))?;
+                out.tiles = Some((size, order));
+            }
+            "--output" => out.output = Some(PathBuf::from(value)),
+            "--heatmap" => out.heatmap = Some(PathBuf::from(value)),
+            "--checkpoint" => out.checkpoint = Some(PathBuf::from(value)),
+            "--checkpoint-every" => {
+                out.checkpoint_every = Some(positive(f, parse_value(f, value)?)?)
+            }
+            "--format" => {
+                out.format = Some(
+                    OutputFormat::from_name(value)
+                        .ok_or_else(|| format!("unknown format '{}'", value))?,
+                )
+            }
+            _ => return Err(format!("unknown option '{}'", flag)),
+        }
+    }
+    Ok(out)
+}
+
+/// Everything needed to render and save the image
+pub struct Job {
+    pub camera: CameraDesc,
+    pub params: RenderParams,
+    pub world: HittableList,
+    /// Lights to sample, which are also in the world
+    pub lights: HittableList,
+    pub rand: RandState,
+    pub output: Option<PathBuf>,
+    pub format: OutputFormat,
+    /// Where to write the samples every pixel took, with `sample_heatmap`
+    pub heatmap: Option<(PathBuf, OutputFormat)>,
+    /// Passes to render the samples in
+    pub passes: u32,
+    /// Whether to write the output after every pass, and not only at the end
+    pub previews: bool,
+    /// Where to save the render, and resume it from, with the time between saves
+    pub checkpoint: Option<(PathBuf, std::time::Duration)>,
+}
+
+impl Args {
+    /// Builds the scene, with the arguments overriding what the scene (Or the defaults) say
+    pub fn into_job(self) -> Result<Job, String> {
+        let mut rand = match self.seed {
+            Some(seed) => RandState::from_seed(seed),
+            None => RandState::new(),
+        };
+
+        let scene = self
+            .scene
+            .unwrap_or_else(|| SceneSource::Builtin("random".to_string()));
+        let (mut camera, mut params, world, lights) = match scene {
+            SceneSource::Builtin(name) => {
+                let builtin = BUILTIN_SCENES
+                    .iter()
+                    .find(|scene| scene.name == name)
+                    .ok_or_else(|| format!("unknown scene '{}'", name))?;
+                let camera = (builtin.camera)();
+                let params = RenderParams {
+                    image_width: 1200,
+                    image_height: (1200.0 / camera.aspect_ratio) as i64,
+                    samples_per_px: 200,
+                    max_depth: 50,
+                    background: builtin.background,
+                    ..RenderParams::default()
+                };
+                let world = (builtin.build)(&mut rand);
+                (camera, params, world, (builtin.lights)())
+            }
+            SceneSource::File(path) => {
+                let scene = scene_file::load_scene(path).map_err(|err| err.to_string())?;
+                (scene.camera, scene.params, scene.world, scene.lights)
+            }
+        };
+
+        let aspect = self.aspect.unwrap_or(camera.aspect_ratio);
+        let (width, height) = match (self.width, self.height) {
+            (Some(width), Some(height)) => (width, height),
+            (Some(width), None) => (width, (width as f64 / aspect) as i64),
+            (None, Some(height)) => ((height as f64 * aspect) as i64, height),
+            (None, None) if self.aspect.is_some() => (
+                params.image_width,
+                (params.image_width as f64 / aspect) as i64,
+            ),
+            (None, None) => (params.image_width, params.image_height),
+        };
+        if width < 2 || height < 2 {
+            return Err(format!("the image is too small ({}x{})", width, height));
+        }
+        params.image_width = width;
+        params.image_height = height;
+        camera.aspect_ratio = width as f64 / height as f64;
+        params.samples_per_px = self.samples.unwrap_or(params.samples_per_px);
+        params.max_depth = self.depth.unwrap_or(params.max_depth);
+        if let Some((depth, probability)) = self.roulette {
+            params.roulette_depth = Some(depth);
+            params.roulette_probability = probability;
+        }
+        if let Some((min_samples, noise_threshold)) = self.adaptive {
+            params.min_samples_per_px = min_samples;
+            params.noise_threshold = noise_threshold;
+        }
+        params.sampler = self.sampler.unwrap_or(params.sampler);
+        if let Some((size, order)) = self.tiles {
+            params.tile_size = size;
+            params.tile_order = order;
+        }
+
+        camera.lookfrom = self.lookfrom.unwrap_or(camera.lookfrom);
+        camera.lookat = self.lookat.unwrap_or(camera.lookat);
+        camera.view_up = self.up.unwrap_or(camera.view_up);
+        camera.vertical_fov_degrees = self.fov.unwrap_or(camera.vertical_fov_degrees);
+        camera.aperture = self.aperture.unwrap_or(camera.aperture);
+        camera.focus_dist = self.focus.unwrap_or(camera.focus_dist);
+        if let Some((open, close)) = self.shutter {
+            camera.shutter_open = open;
+            camera.shutter_close = close;
+        }
+        if (camera.lookfrom - camera.lookat).near_zero() {
+            return Err("the camera can't look at its own position".to_string());
+        }
+        if (camera.lookfrom - camera.lookat)
+            .cross(camera.view_up)
+            .near_zero()
+        {
+            return Err("the up direction can't be parallel to the view direction".to_string());
+        }
+
+        let format = match (self.format, &self.output) {
+            (Some(format), _) => format,
+            (None, Some(path)) => path
+                .extension()
+                .and_then(|ext| OutputFormat::from_name(&ext.to_string_lossy()))
+                .unwrap_or(OutputFormat::Ppm),
+            (None, None) => OutputFormat::Ppm,
+        };
+        let heatmap = match self.heatmap {
+            Some(path) => {
+                let format = path
+                    .extension()
+                    .and_then(|ext| OutputFormat::from_name(&ext.to_string_lossy()))
+                    .unwrap_or(OutputFormat::Ppm);
+                if format.is_hdr() {
+                    return Err("the heatmap can only be written as png or ppm".to_string());
+                }
+                Some((path, format))
+            }
+            None => None,
+        };
+        if self.passes.unwrap_or(1) > 1 && self.output.is_none() {
+            return Err("--passes needs --output".to_string());
+        }
+        let checkpoint_every = std::time::Duration::from_secs(self.checkpoint_every.unwrap_or(300));
+        let checkpoint = self.checkpoint.map(|path| (path, checkpoint_every));
+        // Checkpoints are saved between passes, so there have to be a few of them
+        let passes = match (self.passes, &checkpoint) {
+            (Some(1), Some(_)) => return Err("--checkpoint needs more than one pass".to_string()),
+            (Some(passes), _) => passes,
+            (None, Some(_)) => params.samples_per_px,
+            (None, None) => 1,
+        };
+
+        Ok(Job {
+            camera,
+            params,
+            world,
+            lights,
+            rand,
+            output: self.output,
+            format,
+            heatmap,
+            passes,
+            previews: self.passes.is_some(),
+            checkpoint,
+        })
+    }
+}
@@ -0,0 +1,148 @@
+//! Writers for the linear radiance returned by `render_hdr`
+//!
+//! Every writer takes RGB pixels with the top row first, like `render_hdr` returns them
+use std::io::Write;
+
+fn check_size(width: u32, height: u32, pixels: &[f32]) {
+    assert_eq!(
+        pixels.len(),
+        width as usize * height as usize * 3,
+        "the pixels don't match the size of the image"
+    );
+}
+
+/// Shared exponent encoding of one pixel, as used by Radiance
+fn rgbe(pixel: &[f32]) -> [u8; 4] {
+    let [r, g, b] = [pixel[0].max(0.0), pixel[1].max(0.0), pixel[2].max(0.0)];
+    let max = r.max(g).max(b) as f64;
+    if max <= 1e-32 {
+        return [0; 4];
+    }
+    // max = mantissa * 2^exponent, with the mantissa in [0.5, 1)
+    let exponent = max.log2().floor() as i32 + 1;
+    let scale = 256.0 / 2f64.powi(exponent);
+    let channel = |c: f32| ((c as f64 * scale) as u32).min(255) as u8;
+    [
+        channel(r),
+        channel(g),
+        channel(b),
+        (exponent + 128).clamp(0, 255) as u8,
+    ]
+}
+
+/// Writes a Radiance .hdr (RGBE) file. Scanlines are written flat, without run length encoding
+pub fn write_rgbe(
+    out: &mut impl Write,
+    width: u32,
+    height: u32,
+    pixels: &[f32],
+) -> std::io::Result<()> {
+    check_size(width, height, pixels);
+    write!(
+        out,
+        "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+        height, width
+    )?;
+    for pixel in pixels.chunks(3) {
+        out.write_all(&rgbe(pixel))?;
+    }
+    out.flush()
+}
+
+/// Writes a little endian color PFM file. PFM stores the bottom row first
+pub fn write_pfm(
+    out: &mut impl Write,
+    width: u32,
+    height: u32,
+    pixels: &[f32],
+) -> std::io::Result<()> {
+    check_size(width, height, pixels);
+    // A negative scale means little endian
+    write!(out, "PF\n{} {}\n-1.0\n", width, height)?;
+    for row in pixels.chunks(3 * width as usize).rev() {
+        for value in row {
+            out.write_all(&value.to_le_bytes())?;
+        }
+    }
+    out.flush()
+}
+
+fn exr_attribute(header: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    header.extend_from_slice(name.as_bytes());
+    header.push(0);
+    header.extend_from_slice(kind.as_bytes());
+    header.push(0);
+    header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    header.extend_from_slice(value);
+}
+
+/// Writes an uncompressed scanline OpenEXR file with 32-bit float R, G and B channels
+pub fn write_exr(
+    out: &mut impl Write,
+    width: u32,
+    height: u32,
+    pixels: &[f32],
+) -> std::io::Result<()> {
+    check_size(width, height, pixels);
+    const FLOAT: i32 = 2;
+    // Channels have to be sorted by name
+    const CHANNELS: [(&str, usize); 3] = [("B", 2), ("G", 1), ("R", 0)];
+
+    // Magic number, then version 2 with no flags (Single part scanline image)
+    let mut header = vec![0x76, 0x2f, 0x31, 0x01, 2, 0, 0, 0];
+
+    let mut channels = vec![];
+    for (name, _) in CHANNELS.iter() {
+        channels.extend_from_slice(name.as_bytes());
+        channels.push(0);
+        channels.extend_from_slice(&FLOAT.to_le_bytes());
+        // pLinear and 3 reserved bytes, then the x and y sampling
+        channels.extend_from_slice(&[0; 4]);
+        channels.extend_from_slice(&1i32.to_le_bytes());
+        channels.extend_from_slice(&1i32.to_le_bytes());
+    }
+    channels.push(0);
+    exr_attribute(&mut header, "channels", "chlist", &channels);
+    exr_attribute(&mut header, "compression", "compression", &[0]);
+    let mut window = vec![];
+    for coord in [0, 0, width as i32 - 1, height as i32 - 1].iter() {
+        window.extend_from_slice(&coord.to_le_bytes());
+    }
+    exr_attribute(&mut header, "dataWindow", "box2i", &window);
+    exr_attribute(&mut header, "displayWindow", "box2i", &window);
+    // Increasing y, which means the top row first
+    exr_attribute(&mut header, "lineOrder", "lineOrder", &[0]);
+    exr_attribute(
+        &mut header,
+        "pixelAspectRatio",
+        "float",
+        &1f32.to_le_bytes(),
+    );
+    exr_attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8]);
+    exr_attribute(
+        &mut header,
+        "screenWindowWidth",
+        "float",
+        &1f32.to_le_bytes(),
+    );
+    header.push(0);
+
+    // Without compression every chunk is a single scanline: its y coordinate, the size of its
+    // data, and then every channel one after the other
+    let chunk_size = 4 + 4 + 4 * 3 * width as u64;
+    let first_chunk = header.len() as u64 + 8 * height as u64;
+    out.write_all(&header)?;
+    for y in 0..height as u64 {
+        out.write_all(&(first_chunk + y * chunk_size).to_le_bytes())?;
+    }
+    for (y, row) in pixels.chunks(3 * width as usize).enumerate() {
+        out.write_all(&(y as i32).to_le_bytes())?;
+        out.write_all(&(4 * 3 * width as i32).to_le_bytes())?;
+        for (_, channel) in CHANNELS.iter() {
+            for pixel in row.chunks(3) {
+                out.write_all(&pixel[*channel].to_le_bytes())?;
+            }
+        }
+    }
+    out.flush()
+}
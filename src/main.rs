@@ -1,27 +1,51 @@
+pub mod bvh;
+pub mod checkpoint;
+pub mod cli;
+pub mod hdr;
 pub mod material;
+pub mod obj;
+pub mod pdf;
+pub mod planar;
+pub mod png;
+pub mod sampler;
+pub mod scene_file;
 pub mod scenes;
+pub mod texture;
+pub mod tiles;
+pub mod transform;
+pub mod triangle;
 mod vec3;
+pub mod volume;
 
-#[cfg(not(all(feature = "wincrypt_rand", target_os = "windows")))]
-mod libc_rand;
 #[cfg(all(feature = "wincrypt_rand", target_os = "windows"))]
 mod win32_rand;
-
 #[cfg(not(all(feature = "wincrypt_rand", target_os = "windows")))]
-pub use libc_rand::RandState;
-#[cfg(all(feature = "wincrypt_rand", target_os = "windows"))]
-pub use win32_rand::RandState;
+mod xoshiro_rand;
 
-use material::{Dielectric, LambertianDiffuse, MaterialType, Metal};
+pub use sampler::RandState;
+
+use bvh::Aabb;
+pub use bvh::Bvh;
+use material::{Dielectric, DiffuseLight, Isotropic, LambertianDiffuse, MaterialType, Metal};
+use pdf::{HittablePdf, Pdf};
+use planar::{Disk, Plane, Quad};
+use sampler::{EnumSampler, SamplerKind};
+use scene_file::ObjectDesc;
+use tiles::{Tile, TileOrder};
+use transform::Transform;
+use triangle::{Triangle, TriangleMesh};
 pub use vec3::Vec3;
+use volume::{ConstantMedium, GridMedium};
 
 pub struct Ray {
     pub orig: Vec3,
     pub dir: Vec3,
+    /// When the ray was cast, somewhere in the shutter interval of the camera
+    pub time: f64,
 }
 impl Ray {
-    fn new(orig: Vec3, dir: Vec3) -> Self {
-        Self { orig, dir }
+    fn new(orig: Vec3, dir: Vec3, time: f64) -> Self {
+        Self { orig, dir, time }
     }
     fn at(&self, t: f64) -> Vec3 {
         self.orig + t * self.dir
@@ -34,11 +58,50 @@ const CYAN: Vec3 = Vec3::new(0.5, 0.7, 1.0);
 fn lerp(t: f64, a: Vec3, b: Vec3) -> Vec3 {
     (1.0 - t) * a + t * b
 }
+
+/// What rays that don't hit anything see
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Background {
+    /// Vertical gradient depending on the direction of the ray
+    Gradient {
+        bottom: Vec3,
+        top: Vec3,
+    },
+    Color(Vec3),
+}
+impl Background {
+    /// The blue sky from the book
+    pub const SKY: Background = Background::Gradient {
+        bottom: WHITE,
+        top: CYAN,
+    };
+    /// No light from the background, so only emissive materials light the scene
+    pub const BLACK: Background = Background::Color(Vec3::zero());
+    /// A lighter sky, for the pastel scene
+    pub const PASTEL_SKY: Background = Background::Gradient {
+        bottom: WHITE,
+        top: Vec3::new(0.86, 0.92, 1.0),
+    };
+
+    fn color(&self, r: &Ray) -> Vec3 {
+        match *self {
+            Background::Gradient { bottom, top } => {
+                let unit_dir = r.dir.unit_vector();
+                let t = 0.5 * (unit_dir.y() + 1.0);
+                lerp(t, bottom, top)
+            }
+            Background::Color(color) => color,
+        }
+    }
+}
 #[derive(Clone)]
 pub struct HitRecord {
     p: Vec3,
     normal: Vec3,
     t: f64,
+    /// Surface coordinates of the hit point
+    u: f64,
+    v: f64,
     front_face: bool,
     material: MaterialType,
 }
@@ -51,6 +114,26 @@ impl HitRecord {
             -outward_normal
         };
     }
+    /// Ray parameter of the hit point
+    pub fn t(&self) -> f64 {
+        self.t
+    }
+    /// Unit normal at the hit point, on the side the ray came from
+    pub fn normal(&self) -> Vec3 {
+        self.normal
+    }
+    /// Whether the ray hit the outside of the surface
+    pub fn front_face(&self) -> bool {
+        self.front_face
+    }
+    /// Surface coordinates of the hit point
+    pub fn uv(&self) -> (f64, f64) {
+        (self.u, self.v)
+    }
+    /// Material of the surface that was hit
+    pub fn material(&self) -> &MaterialType {
+        &self.material
+    }
 }
 impl Default for HitRecord {
     fn default() -> Self {
@@ -58,61 +141,398 @@ impl Default for HitRecord {
             p: Vec3::zero(),
             normal: Vec3::zero(),
             t: -1.0,
+            u: 0.0,
+            v: 0.0,
             front_face: false,
             material: LambertianDiffuse::new(Vec3::repeat(0.5)),
         }
     }
 }
-trait Hittable {
+pub trait Hittable {
     fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, out: &mut HitRecord) -> bool;
+    /// Returns false if the object has no bounding box
+    fn bounding_box(&self, output_box: &mut Aabb) -> bool;
+    /// Parameters used to write the object to a scene file, if it can be written
+    fn describe(&self) -> Option<ObjectDesc> {
+        None
+    }
+    /// Density per unit of solid angle of the directions `random` picks from `origin`. It's 0 for
+    /// objects that can't be sampled, and for directions that miss the object
+    fn pdf_value(&self, _origin: Vec3, _direction: Vec3) -> f64 {
+        0.0
+    }
+    /// Random direction from `origin` towards a point of the object
+    fn random(&self, _rand: &mut RandState, _origin: Vec3) -> Vec3 {
+        Vec3::new(1.0, 0.0, 0.0)
+    }
+}
+impl<T: Hittable + ?Sized> Hittable for std::rc::Rc<T> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, out: &mut HitRecord) -> bool {
+        (**self).hit(ray, t_min, t_max, out)
+    }
+    fn bounding_box(&self, output_box: &mut Aabb) -> bool {
+        (**self).bounding_box(output_box)
+    }
+    fn describe(&self) -> Option<ObjectDesc> {
+        (**self).describe()
+    }
+    fn pdf_value(&self, origin: Vec3, direction: Vec3) -> f64 {
+        (**self).pdf_value(origin, direction)
+    }
+    fn random(&self, rand: &mut RandState, origin: Vec3) -> Vec3 {
+        (**self).random(rand, origin)
+    }
+}
+/// Spherical coordinates of a point on the unit sphere, scaled to [0, 1]. u goes around the y axis
+/// starting from -x, and v goes from the bottom (-y) to the top
+fn sphere_uv(p: Vec3) -> (f64, f64) {
+    let theta = (-p.y()).clamp(-1.0, 1.0).acos();
+    let phi = (-p.z()).atan2(p.x()) + std::f64::consts::PI;
+    (
+        phi / (2.0 * std::f64::consts::PI),
+        theta / std::f64::consts::PI,
+    )
+}
+/// Shared by Sphere and MovingSphere, which only differ in where the center is
+fn hit_sphere(
+    center: Vec3,
+    radius: f64,
+    material: &MaterialType,
+    ray: &Ray,
+    t_min: f64,
+    t_max: f64,
+    out: &mut HitRecord,
+) -> bool {
+    let ray_to_sphere = ray.orig - center;
+    let a = ray.dir.length_squared();
+    let half_b = ray.dir.dot(ray_to_sphere);
+    let c = ray_to_sphere.length_squared() - radius.powi(2);
+    let discriminant = half_b.powi(2) - a * c;
+    if discriminant < 0.0 {
+        return false;
+    }
+    let sqrtd = discriminant.sqrt();
+
+    let mut root = (-half_b - sqrtd) / a;
+    if root < t_min || t_max < root {
+        root = (-half_b + sqrtd) / a;
+        if root < t_min || t_max < root {
+            return false;
+        }
+    }
+    out.t = root;
+    out.p = ray.at(out.t);
+    let outward_normal = (out.p - center) / radius;
+    out.set_face_normal(ray, outward_normal);
+    (out.u, out.v) = sphere_uv(outward_normal);
+    out.material = material.clone();
+    true
+}
+/// Cosine of the angle between the direction from `origin` to the center of a sphere and its edge.
+/// None if `origin` is inside, where the sphere is seen in every direction
+fn sphere_cone(center: Vec3, radius: f64, origin: Vec3) -> Option<f64> {
+    let ratio = radius.powi(2) / (center - origin).length_squared();
+    if ratio >= 1.0 {
+        return None;
+    }
+    Some((1.0 - ratio).sqrt())
 }
-struct Sphere {
+fn sphere_bbox(center: Vec3, radius: f64) -> Aabb {
+    let radius = Vec3::repeat(radius.abs());
+    Aabb::new(center - radius, center + radius)
+}
+pub struct Sphere {
     center: Vec3,
     radius: f64,
     material: MaterialType,
 }
+impl Sphere {
+    pub fn new(center: Vec3, radius: f64, material: MaterialType) -> Self {
+        Self {
+            center,
+            radius,
+            material,
+        }
+    }
+}
 impl Hittable for Sphere {
     fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, out: &mut HitRecord) -> bool {
-        let ray_to_sphere = ray.orig - self.center;
-        let a = ray.dir.length_squared();
-        let half_b = ray.dir.dot(ray_to_sphere);
-        let c = ray_to_sphere.length_squared() - self.radius.powi(2);
-        let discriminant = half_b.powi(2) - a * c;
-        if discriminant < 0.0 {
-            return false;
+        hit_sphere(
+            self.center,
+            self.radius,
+            &self.material,
+            ray,
+            t_min,
+            t_max,
+            out,
+        )
+    }
+    fn bounding_box(&self, output_box: &mut Aabb) -> bool {
+        *output_box = sphere_bbox(self.center, self.radius);
+        true
+    }
+    fn describe(&self) -> Option<ObjectDesc> {
+        Some(ObjectDesc::Sphere {
+            center: self.center,
+            radius: self.radius,
+            material: self.material.describe()?,
+        })
+    }
+    fn pdf_value(&self, origin: Vec3, direction: Vec3) -> f64 {
+        let ray = Ray::new(origin, direction, 0.0);
+        if !self.hit(&ray, 0.001, f64::INFINITY, &mut HitRecord::default()) {
+            return 0.0;
         }
-        let sqrtd = discriminant.sqrt();
-
-        let mut root = (-half_b - sqrtd) / a;
-        if root < t_min || t_max < root {
-            root = (-half_b + sqrtd) / a;
-            if root < t_min || t_max < root {
-                return false;
-            }
+        match sphere_cone(self.center, self.radius, origin) {
+            // Uniform over the cone of directions that see the sphere
+            Some(cos_theta_max) => 1.0 / (2.0 * std::f64::consts::PI * (1.0 - cos_theta_max)),
+            None => 1.0 / (4.0 * std::f64::consts::PI),
         }
-        out.t = root;
-        out.p = ray.at(out.t);
-        let outward_normal = (out.p - self.center) / self.radius;
-        out.set_face_normal(ray, outward_normal);
-        out.material = self.material.clone();
+    }
+    fn random(&self, rand: &mut RandState, origin: Vec3) -> Vec3 {
+        let cos_theta_max = match sphere_cone(self.center, self.radius, origin) {
+            Some(cos_theta_max) => cos_theta_max,
+            None => return Vec3::random_unit_vector(rand),
+        };
+        let axis = (self.center - origin).unit_vector();
+        let (tangent, bitangent) = planar::tangents(axis);
+        let z = 1.0 + rand.random_double() * (cos_theta_max - 1.0);
+        let phi = 2.0 * std::f64::consts::PI * rand.random_double();
+        let r = (1.0 - z * z).max(0.0).sqrt();
+        r * phi.cos() * tangent + r * phi.sin() * bitangent + z * axis
+    }
+}
+/// A sphere that moves in a straight line from `center0` at `time0` to `center1` at `time1`. It
+/// stays at the closest end outside of that interval, so it never leaves its bounding box
+pub struct MovingSphere {
+    center0: Vec3,
+    center1: Vec3,
+    time0: f64,
+    time1: f64,
+    radius: f64,
+    material: MaterialType,
+}
+impl MovingSphere {
+    pub fn new(
+        (center0, time0): (Vec3, f64),
+        (center1, time1): (Vec3, f64),
+        radius: f64,
+        material: MaterialType,
+    ) -> Self {
+        Self {
+            center0,
+            center1,
+            time0,
+            time1,
+            radius,
+            material,
+        }
+    }
+    pub fn center(&self, time: f64) -> Vec3 {
+        if self.time1 <= self.time0 {
+            return self.center0;
+        }
+        let t = ((time - self.time0) / (self.time1 - self.time0)).clamp(0.0, 1.0);
+        lerp(t, self.center0, self.center1)
+    }
+}
+impl Hittable for MovingSphere {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, out: &mut HitRecord) -> bool {
+        hit_sphere(
+            self.center(ray.time),
+            self.radius,
+            &self.material,
+            ray,
+            t_min,
+            t_max,
+            out,
+        )
+    }
+    fn bounding_box(&self, output_box: &mut Aabb) -> bool {
+        *output_box = sphere_bbox(self.center0, self.radius)
+            .surrounding(&sphere_bbox(self.center1, self.radius));
         true
     }
+    fn describe(&self) -> Option<ObjectDesc> {
+        Some(ObjectDesc::MovingSphere {
+            centers: [self.center0, self.center1],
+            times: [self.time0, self.time1],
+            radius: self.radius,
+            material: self.material.describe()?,
+        })
+    }
 }
 #[cfg(feature = "dyn_hit")]
-pub struct HittableList(Vec<std::rc::Rc<dyn Hittable>>);
+pub type HittableType = std::rc::Rc<dyn Hittable>;
 #[cfg(not(feature = "dyn_hit"))]
-pub struct HittableList(Vec<Sphere>);
+pub type HittableType = EnumHit;
+
+pub enum EnumHit {
+    Sphere(Sphere),
+    MovingSphere(MovingSphere),
+    Triangle(Triangle),
+    Mesh(TriangleMesh),
+    Quad(Quad),
+    Disk(Disk),
+    Plane(Plane),
+    // Boxed, because it holds three 4x4 matrices
+    Transform(Box<Transform>),
+    Medium(ConstantMedium),
+    GridMedium(GridMedium),
+    List(HittableList),
+}
+impl Hittable for EnumHit {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, out: &mut HitRecord) -> bool {
+        match self {
+            EnumHit::Sphere(obj) => obj.hit(ray, t_min, t_max, out),
+            EnumHit::MovingSphere(obj) => obj.hit(ray, t_min, t_max, out),
+            EnumHit::Triangle(obj) => obj.hit(ray, t_min, t_max, out),
+            EnumHit::Mesh(obj) => obj.hit(ray, t_min, t_max, out),
+            EnumHit::Quad(obj) => obj.hit(ray, t_min, t_max, out),
+            EnumHit::Disk(obj) => obj.hit(ray, t_min, t_max, out),
+            EnumHit::Plane(obj) => obj.hit(ray, t_min, t_max, out),
+            EnumHit::Transform(obj) => obj.hit(ray, t_min, t_max, out),
+            EnumHit::Medium(obj) => obj.hit(ray, t_min, t_max, out),
+            EnumHit::GridMedium(obj) => obj.hit(ray, t_min, t_max, out),
+            EnumHit::List(obj) => obj.hit(ray, t_min, t_max, out),
+        }
+    }
+    fn bounding_box(&self, output_box: &mut Aabb) -> bool {
+        match self {
+            EnumHit::Sphere(obj) => obj.bounding_box(output_box),
+            EnumHit::MovingSphere(obj) => obj.bounding_box(output_box),
+            EnumHit::Triangle(obj) => obj.bounding_box(output_box),
+            EnumHit::Mesh(obj) => obj.bounding_box(output_box),
+            EnumHit::Quad(obj) => obj.bounding_box(output_box),
+            EnumHit::Disk(obj) => obj.bounding_box(output_box),
+            EnumHit::Plane(obj) => obj.bounding_box(output_box),
+            EnumHit::Transform(obj) => obj.bounding_box(output_box),
+            EnumHit::Medium(obj) => obj.bounding_box(output_box),
+            EnumHit::GridMedium(obj) => obj.bounding_box(output_box),
+            EnumHit::List(obj) => obj.bounding_box(output_box),
+        }
+    }
+    fn describe(&self) -> Option<ObjectDesc> {
+        match self {
+            EnumHit::Sphere(obj) => obj.describe(),
+            EnumHit::MovingSphere(obj) => obj.describe(),
+            EnumHit::Triangle(obj) => obj.describe(),
+            EnumHit::Mesh(obj) => obj.describe(),
+            EnumHit::Quad(obj) => obj.describe(),
+            EnumHit::Disk(obj) => obj.describe(),
+            EnumHit::Plane(obj) => obj.describe(),
+            EnumHit::Transform(obj) => obj.describe(),
+            EnumHit::Medium(obj) => obj.describe(),
+            EnumHit::GridMedium(obj) => obj.describe(),
+            EnumHit::List(obj) => obj.describe(),
+        }
+    }
+    fn pdf_value(&self, origin: Vec3, direction: Vec3) -> f64 {
+        match self {
+            EnumHit::Sphere(obj) => obj.pdf_value(origin, direction),
+            EnumHit::MovingSphere(obj) => obj.pdf_value(origin, direction),
+            EnumHit::Triangle(obj) => obj.pdf_value(origin, direction),
+            EnumHit::Mesh(obj) => obj.pdf_value(origin, direction),
+            EnumHit::Quad(obj) => obj.pdf_value(origin, direction),
+            EnumHit::Disk(obj) => obj.pdf_value(origin, direction),
+            EnumHit::Plane(obj) => obj.pdf_value(origin, direction),
+            EnumHit::Transform(obj) => obj.pdf_value(origin, direction),
+            EnumHit::Medium(obj) => obj.pdf_value(origin, direction),
+            EnumHit::GridMedium(obj) => obj.pdf_value(origin, direction),
+            EnumHit::List(obj) => obj.pdf_value(origin, direction),
+        }
+    }
+    fn random(&self, rand: &mut RandState, origin: Vec3) -> Vec3 {
+        match self {
+            EnumHit::Sphere(obj) => obj.random(rand, origin),
+            EnumHit::MovingSphere(obj) => obj.random(rand, origin),
+            EnumHit::Triangle(obj) => obj.random(rand, origin),
+            EnumHit::Mesh(obj) => obj.random(rand, origin),
+            EnumHit::Quad(obj) => obj.random(rand, origin),
+            EnumHit::Disk(obj) => obj.random(rand, origin),
+            EnumHit::Plane(obj) => obj.random(rand, origin),
+            EnumHit::Transform(obj) => obj.random(rand, origin),
+            EnumHit::Medium(obj) => obj.random(rand, origin),
+            EnumHit::GridMedium(obj) => obj.random(rand, origin),
+            EnumHit::List(obj) => obj.random(rand, origin),
+        }
+    }
+}
+impl From<Sphere> for EnumHit {
+    fn from(obj: Sphere) -> Self {
+        EnumHit::Sphere(obj)
+    }
+}
+impl From<MovingSphere> for EnumHit {
+    fn from(obj: MovingSphere) -> Self {
+        EnumHit::MovingSphere(obj)
+    }
+}
+impl From<Triangle> for EnumHit {
+    fn from(obj: Triangle) -> Self {
+        EnumHit::Triangle(obj)
+    }
+}
+impl From<TriangleMesh> for EnumHit {
+    fn from(obj: TriangleMesh) -> Self {
+        EnumHit::Mesh(obj)
+    }
+}
+impl From<Quad> for EnumHit {
+    fn from(obj: Quad) -> Self {
+        EnumHit::Quad(obj)
+    }
+}
+impl From<Disk> for EnumHit {
+    fn from(obj: Disk) -> Self {
+        EnumHit::Disk(obj)
+    }
+}
+impl From<Plane> for EnumHit {
+    fn from(obj: Plane) -> Self {
+        EnumHit::Plane(obj)
+    }
+}
+impl From<Transform> for EnumHit {
+    fn from(obj: Transform) -> Self {
+        EnumHit::Transform(Box::new(obj))
+    }
+}
+impl From<ConstantMedium> for EnumHit {
+    fn from(obj: ConstantMedium) -> Self {
+        EnumHit::Medium(obj)
+    }
+}
+impl From<GridMedium> for EnumHit {
+    fn from(obj: GridMedium) -> Self {
+        EnumHit::GridMedium(obj)
+    }
+}
+impl From<HittableList> for EnumHit {
+    fn from(obj: HittableList) -> Self {
+        EnumHit::List(obj)
+    }
+}
+
+#[derive(Default)]
+pub struct HittableList(Vec<HittableType>);
 impl HittableList {
+    pub fn new() -> Self {
+        Self(vec![])
+    }
     pub fn clear(&mut self) {
         self.0.clear();
     }
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
     #[cfg(feature = "dyn_hit")]
-    fn add<T: Hittable + 'static>(&mut self, object: T) {
+    pub fn add<T: Hittable + 'static>(&mut self, object: T) {
         self.0.push(std::rc::Rc::new(object));
     }
     #[cfg(not(feature = "dyn_hit"))]
-    fn add(&mut self, object: Sphere) {
-        self.0.push(object);
+    pub fn add<T: Into<EnumHit>>(&mut self, object: T) {
+        self.0.push(object.into());
     }
 }
 impl Hittable for HittableList {
@@ -129,44 +549,189 @@ impl Hittable for HittableList {
         }
         hit_anything
     }
+    fn bounding_box(&self, output_box: &mut Aabb) -> bool {
+        let mut tmp_box = Aabb::empty();
+        let mut result = Aabb::empty();
+        for obj in &self.0 {
+            if !obj.bounding_box(&mut tmp_box) {
+                return false;
+            }
+            result = result.surrounding(&tmp_box);
+        }
+        *output_box = result;
+        !self.0.is_empty()
+    }
+    fn describe(&self) -> Option<ObjectDesc> {
+        let objects = self.0.iter().map(|obj| obj.describe());
+        Some(ObjectDesc::Group(objects.collect::<Option<_>>()?))
+    }
+    /// Every object is picked as often, whatever its size
+    fn pdf_value(&self, origin: Vec3, direction: Vec3) -> f64 {
+        let sum: f64 = self
+            .0
+            .iter()
+            .map(|obj| obj.pdf_value(origin, direction))
+            .sum();
+        sum / self.0.len().max(1) as f64
+    }
+    fn random(&self, rand: &mut RandState, origin: Vec3) -> Vec3 {
+        if self.0.is_empty() {
+            return Vec3::new(1.0, 0.0, 0.0);
+        }
+        let idx = ((rand.random_double() * self.0.len() as f64) as usize).min(self.0.len() - 1);
+        self.0[idx].random(rand, origin)
+    }
 }
-#[cfg(feature = "dyn_hit")]
-type HitWorld<'a> = &'a dyn Hittable;
-#[cfg(not(feature = "dyn_hit"))]
-type HitWorld<'a> = &'a HittableList;
-fn ray_color<'a>(rand: &mut RandState, world: HitWorld<'a>, r: &Ray, depth: i32) -> Vec3 {
-    if depth <= 0 {
-        return Vec3::zero();
-    }
-
-    let mut hit_record = HitRecord::default();
-    if world.hit(r, 0.001, f64::INFINITY, &mut hit_record) {
-        //return hit_record.normal * 0.5 + Vec3::repeat(0.5);
-
-        let mat = hit_record.material.clone();
-        let mut attenuation = Vec3::zero();
-        let mut scatter_ray = Ray::new(Vec3::zero(), Vec3::zero());
-        let scatter = mat.scatter(rand, r, &hit_record, &mut attenuation, &mut scatter_ray);
-        return if scatter {
-            attenuation * ray_color(rand, world, &scatter_ray, depth - 1)
-        } else {
-            Vec3::zero()
-        };
+
+/// Anything that can be rendered. With the parallel feature it also needs to be shared between threads
+#[cfg(feature = "parallel")]
+pub trait World: Hittable + Sync {}
+#[cfg(feature = "parallel")]
+impl<T: Hittable + Sync> World for T {}
+#[cfg(not(feature = "parallel"))]
+pub trait World: Hittable {}
+#[cfg(not(feature = "parallel"))]
+impl<T: Hittable> World for T {}
+
+/// Power heuristic weight of a sample taken with density `pdf`, which another strategy could have
+/// taken with density `other_pdf`
+fn power_heuristic(pdf: f64, other_pdf: f64) -> f64 {
+    let (a, b) = (pdf * pdf, other_pdf * other_pdf);
+    if a + b > 0.0 {
+        a / (a + b)
+    } else {
+        1.0
     }
+}
+
+/// Dimensions of a sample used by the camera: where in the pixel, where on the lens and when
+const CAMERA_DIMENSIONS: u32 = 6;
+/// Dimensions used by each bounce: scattering, sampling a light and Russian roulette
+const BOUNCE_DIMENSIONS: u32 = 8;
 
-    let unit_dir = r.dir.unit_vector();
-    let t = 0.5 * (unit_dir.y() + 1.0);
-    lerp(t, WHITE, CYAN)
-    // For pastel background:
-    //lerp(t, Vec3::new(1.0, 1.0, 1.0), Vec3::new(0.86, 0.92, 1.0))
+/// What rays bounce around in and how, which stays the same for a whole render
+struct Tracer<'a, W: ?Sized> {
+    world: &'a W,
+    lights: &'a HittableList,
+    params: &'a RenderParams,
 }
+impl<W: Hittable + ?Sized> Tracer<'_, W> {
+    /// Light reaching the hit point straight from a random point on the lights, weighted against
+    /// finding it by scattering with `material_pdf`. Shadow rays stop at whatever they hit first,
+    /// so a light that's blocked adds nothing
+    fn sample_light(
+        &self,
+        rand: &mut RandState,
+        r: &Ray,
+        hit_record: &HitRecord,
+        material_pdf: &impl Pdf,
+    ) -> Vec3 {
+        let light_pdf = HittablePdf::new(self.lights, hit_record.p);
+        let direction = light_pdf.generate(rand);
+        let light_pdf = light_pdf.value(direction);
+        if light_pdf <= 0.0 {
+            return Vec3::zero();
+        }
+        let bsdf = hit_record.material.eval(r, hit_record, direction);
+        if bsdf == Vec3::zero() {
+            // Behind the surface
+            return Vec3::zero();
+        }
+        let shadow_ray = Ray::new(hit_record.p, direction, r.time);
+        let mut light_record = HitRecord::default();
+        if !self
+            .world
+            .hit(&shadow_ray, 0.001, f64::INFINITY, &mut light_record)
+        {
+            return Vec3::zero();
+        }
+        let emitted = light_record.material.emitted(&light_record);
+        let weight = power_heuristic(light_pdf, material_pdf.value(direction));
+        bsdf * emitted * (weight / light_pdf)
+    }
+
+    /// Radiance coming back along `r`
+    ///
+    /// At diffuse bounces the lights (Which should also be in the world) are sampled directly too,
+    /// and both ways of reaching a light are combined with multiple importance sampling.
+    /// `scatter_pdf` is the density the material that scattered `r` picked it with, when it was
+    /// combined like that. `throughput` is how much of the radiance reaches the camera, and makes
+    /// Russian roulette end dark paths sooner
+    fn ray_color(
+        &self,
+        rand: &mut RandState,
+        r: &Ray,
+        scatter_pdf: Option<f64>,
+        throughput: Vec3,
+        depth: i32,
+    ) -> Vec3 {
+        if depth <= 0 {
+            return Vec3::zero();
+        }
 
-fn output_color(output_px: &mut [u8], pixel: Vec3, samples_per_px: u32) {
-    let scale = 1.0 / (samples_per_px as f64); // Divide by samples_per_px using a multiplication
+        let mut hit_record = HitRecord::default();
+        if self.world.hit(r, 0.001, f64::INFINITY, &mut hit_record) {
+            //return hit_record.normal * 0.5 + Vec3::repeat(0.5);
+            let bounces = self.params.max_depth - depth;
+            rand.start_dimensions(
+                CAMERA_DIMENSIONS + BOUNCE_DIMENSIONS * bounces as u32,
+                BOUNCE_DIMENSIONS,
+            );
 
-    let [r, g, b] = pixel.0;
+            let mat = hit_record.material.clone();
+            let mut emitted = mat.emitted(&hit_record);
+            if let Some(scatter_pdf) = scatter_pdf {
+                if emitted != Vec3::zero() {
+                    let light_pdf = self.lights.pdf_value(r.orig, r.dir);
+                    emitted = emitted * power_heuristic(scatter_pdf, light_pdf);
+                }
+            }
+            let mut attenuation = Vec3::zero();
+            let mut scatter_ray = Ray::new(Vec3::zero(), Vec3::zero(), r.time);
+            if !mat.scatter(rand, r, &hit_record, &mut attenuation, &mut scatter_ray) {
+                return emitted;
+            }
 
-    let [r, g, b] = [r * scale, g * scale, b * scale];
+            // Without lights, or for mirrors and glass, only the scattered ray can find the light.
+            // Materials with a density sample it in `scatter`, where the BSDF over the density is
+            // the attenuation
+            let material_pdf = mat.pdf(&hit_record).filter(|_| !self.lights.is_empty());
+            let (direct, scatter_pdf) = match &material_pdf {
+                Some(pdf) => (
+                    self.sample_light(rand, r, &hit_record, pdf),
+                    Some(pdf.value(scatter_ray.dir)),
+                ),
+                None => (Vec3::zero(), None),
+            };
+
+            // Paths that carry little light are more likely to end, and the ones that go on carry
+            // more to make up for it
+            if matches!(self.params.roulette_depth, Some(start) if bounces >= start) {
+                let brightest = (throughput * attenuation)
+                    .0
+                    .iter()
+                    .fold(0.0, |a, &b| b.max(a));
+                let survival = brightest.min(self.params.roulette_probability);
+                if survival < 1.0 {
+                    if rand.random_double() >= survival {
+                        return emitted + direct;
+                    }
+                    attenuation = attenuation / survival;
+                }
+            }
+
+            let throughput = throughput * attenuation;
+            let incoming = self.ray_color(rand, &scatter_ray, scatter_pdf, throughput, depth - 1);
+            return emitted + direct + attenuation * incoming;
+        }
+
+        self.params.background.color(r)
+    }
+}
+
+/// Gamma corrects and quantizes a pixel, which has already been divided by the number of samples
+fn output_color(output_px: &mut [u8], pixel: Vec3) {
+    let [r, g, b] = pixel.0;
 
     // gamma correction
     let [r, g, b] = [r.sqrt(), g.sqrt(), b.sqrt()];
@@ -180,6 +745,12 @@ fn output_color(output_px: &mut [u8], pixel: Vec3, samples_per_px: u32) {
     output_px[2] = b;
 }
 
+/// Stores the linear radiance of a pixel
+fn output_hdr(output_px: &mut [f32], pixel: Vec3) {
+    let [r, g, b] = pixel.0;
+    output_px.copy_from_slice(&[r as f32, g as f32, b as f32]);
+}
+
 pub struct Camera {
     origin: Vec3,
     lower_left_corner: Vec3,
@@ -189,6 +760,9 @@ pub struct Camera {
     u: Vec3,
     v: Vec3,
     _w: Vec3,
+    /// Rays are cast at random times between these
+    shutter_open: f64,
+    shutter_close: f64,
 }
 impl Camera {
     pub fn new(
@@ -221,145 +795,588 @@ impl Camera {
             u,
             v,
             _w: w,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
         }
     }
+    /// Keeps the shutter open from `open` to `close`, so anything moving in that time gets blurred
+    pub fn with_shutter(mut self, open: f64, close: f64) -> Self {
+        self.shutter_open = open;
+        self.shutter_close = close;
+        self
+    }
 
     fn get_ray(&self, rand: &mut RandState, s: f64, t: f64) -> Ray {
         let rd = self.lens_radius * Vec3::random_in_unit_disk(rand);
         let offset = self.u * rd.x() + self.v * rd.y();
+        // Without an interval no random number is used, so still images stay the same
+        let time = if self.shutter_close > self.shutter_open {
+            rand.random_double_range(self.shutter_open, self.shutter_close)
+        } else {
+            self.shutter_open
+        };
         Ray {
             orig: self.origin + offset,
             dir: self.lower_left_corner + s * self.horizontal + t * self.vertical
                 - self.origin
                 - offset,
+            time,
         }
     }
 }
 
+#[derive(Debug, Clone)]
 pub struct RenderParams {
     pub image_width: i64,
     pub image_height: i64,
+    /// Samples per pixel, or the most a pixel can take with adaptive sampling
     pub samples_per_px: u32,
+    /// Samples every pixel takes before adaptive sampling can stop
+    pub min_samples_per_px: u32,
+    /// Adaptive sampling stops sampling a pixel once the standard error of its brightness over
+    /// the brightness is below this. 0 turns it off
+    pub noise_threshold: f64,
+    /// Where the random numbers of every sample come from
+    pub sampler: SamplerKind,
+    /// Width and height of the tiles the image is split into, which threads take one at a time
+    pub tile_size: u32,
+    pub tile_order: TileOrder,
     pub max_depth: i32,
+    /// Bounces before Russian roulette can end a path, or None to turn it off. Paths never go past
+    /// `max_depth` either way
+    pub roulette_depth: Option<i32>,
+    /// Highest chance of a path going on after `roulette_depth`, even when it's still bright
+    pub roulette_probability: f64,
+    pub background: Background,
 }
-pub fn render(
+impl Default for RenderParams {
+    fn default() -> Self {
+        Self {
+            image_width: 400,
+            image_height: 225,
+            samples_per_px: 100,
+            min_samples_per_px: 16,
+            noise_threshold: 0.0,
+            sampler: SamplerKind::Independent,
+            tile_size: 32,
+            tile_order: TileOrder::Scanline,
+            max_depth: 50,
+            roulette_depth: None,
+            roulette_probability: 0.95,
+            background: Background::SKY,
+        }
+    }
+}
+/// Seed for the random numbers used by one sample of one pixel
+///
+/// Every sample gets its own stream derived from the render seed, so the image doesn't depend on
+/// which thread rendered which pixel, or in which order
+fn sample_seed(seed: u64, pixel_idx: u64, sample: u32) -> u64 {
+    use sampler::mix;
+    mix(mix(seed ^ pixel_idx).wrapping_add(sample as u64))
+}
+
+/// Running mean and variance of the brightness of a pixel's samples (Welford's algorithm)
+#[derive(Default, Clone, Copy)]
+struct PixelStats {
+    count: u32,
+    mean: f64,
+    m2: f64,
+}
+impl PixelStats {
+    fn add(&mut self, sample: Vec3) {
+        let [r, g, b] = sample.0;
+        let brightness = (r + g + b) / 3.0;
+        self.count += 1;
+        let delta = brightness - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (brightness - self.mean);
+    }
+    /// Standard error of the mean brightness over the mean, which doesn't get huge for dark pixels
+    fn relative_error(&self) -> f64 {
+        let count = self.count as f64;
+        let variance = self.m2 / (count - 1.0);
+        (variance / count).sqrt() / self.mean.max(0.01)
+    }
+}
+
+/// Everything a pixel has taken so far
+#[derive(Clone, Copy)]
+struct PixelSum {
+    color: Vec3,
+    samples: u32,
+    stats: PixelStats,
+    /// Whether adaptive sampling stopped it
+    converged: bool,
+}
+
+/// A render that gets better one pass at a time
+///
+/// Every pass adds more samples to every pixel, and the image (The average of all the samples so
+/// far) can be looked at between passes, to show previews, stop early, or keep going when it's
+/// still noisy. A pixel's samples are the same however they're split into passes, so one pass of
+/// `samples_per_px` gives the same image as `render`
+pub struct ProgressiveRender<'a, W> {
     camera: Camera,
-    world: HittableList,
+    world: W,
+    lights: &'a HittableList,
     params: RenderParams,
-    rand: &mut RandState,
-) -> Vec<u8> {
-    let RenderParams {
-        image_width,
-        image_height,
-        samples_per_px,
-        max_depth,
-    } = params;
-
-    let mut output = vec![0u8; 3 * (image_width * image_height) as usize];
-
-    #[cfg(not(feature = "parallel"))]
-    let stderr = &mut std::io::stderr();
-
-    let scanline_iter = (0..image_height)
-        .rev()
-        .zip(output.chunks_mut(3 * image_width as usize));
-    #[cfg(feature = "parallel")]
-    use rayon::prelude::*;
-    #[cfg(feature = "parallel")]
-    let scanline_iter = scanline_iter.collect::<Vec<_>>().into_par_iter();
-    scanline_iter.for_each(|(i, output_scanline)| {
-        #[cfg(not(feature = "parallel"))]
-        {
-            use std::io::Write;
-            write!(stderr, "\rScanlines remaining: {:04}", i).unwrap();
+    seed: u64,
+    sampler: Option<EnumSampler>,
+    pixels: Vec<PixelSum>,
+    passes: u32,
+    /// Samples every pixel took so far, unless adaptive sampling stopped it
+    samples_taken: u32,
+}
+
+impl<'a, W: World> ProgressiveRender<'a, W> {
+    /// Starts a render with no samples yet. Takes the same arguments as `render`
+    pub fn new(
+        camera: Camera,
+        world: W,
+        lights: &'a HittableList,
+        params: RenderParams,
+        rand: &mut RandState,
+    ) -> Self {
+        Self::with_seed(camera, world, lights, params, rand.next_u64())
+    }
+
+    fn with_seed(
+        camera: Camera,
+        world: W,
+        lights: &'a HittableList,
+        params: RenderParams,
+        seed: u64,
+    ) -> Self {
+        // Independent numbers come straight from the generator, which is faster
+        let sampler = match params.sampler {
+            SamplerKind::Independent => None,
+            sampler => Some(sampler.build(seed, params.samples_per_px)),
+        };
+        let pixels = (0..params.image_width * params.image_height)
+            .map(|_| PixelSum {
+                color: Vec3::zero(),
+                samples: 0,
+                stats: PixelStats::default(),
+                converged: false,
+            })
+            .collect();
+        Self {
+            camera,
+            world,
+            lights,
+            params,
+            seed,
+            sampler,
+            pixels,
+            passes: 0,
+            samples_taken: 0,
         }
+    }
+
+    /// Adds `samples` samples to every pixel, or fewer to those that adaptive sampling stops.
+    /// Passes can go past `samples_per_px`
+    pub fn render_pass(&mut self, samples: u32) {
+        let RenderParams {
+            image_width,
+            image_height,
+            min_samples_per_px,
+            noise_threshold,
+            max_depth,
+            ..
+        } = self.params;
+        let adaptive = noise_threshold > 0.0;
+        // The variance needs 2 samples
+        let min_samples_per_px = min_samples_per_px.max(2).min(self.params.samples_per_px);
+        let tracer = Tracer {
+            world: &self.world,
+            lights: self.lights,
+            params: &self.params,
+        };
+        let (camera, seed, sampler) = (&self.camera, self.seed, self.sampler);
 
-        let mut work = |rand: &mut RandState| {
-            for (j, output_px) in (0..image_width).zip(output_scanline.chunks_mut(3)) {
-                let mut color = Vec3::zero();
-                for _ in 0..samples_per_px {
+        let render_tile = |tile: Tile, pixels: &mut [PixelSum]| {
+            let rows = (tile.y..tile.y + tile.height).map(|row| image_height - 1 - row as i64);
+            let positions = rows.flat_map(|i| (tile.x..tile.x + tile.width).map(move |j| (i, j)));
+            for ((i, j), pixel) in positions.zip(pixels) {
+                if pixel.converged {
+                    continue;
+                }
+                let pixel_idx = (i * image_width + j as i64) as u64;
+                let first_sample = pixel.samples;
+                for sample in first_sample..first_sample + samples {
+                    let rand = &mut RandState::from_seed(sample_seed(seed, pixel_idx, sample));
+                    if let Some(sampler) = sampler {
+                        rand.use_sampler(sampler, [j, i as u32], sample);
+                        rand.start_dimensions(0, CAMERA_DIMENSIONS);
+                    }
                     let (u, v) = (
                         (j as f64 + rand.random_double()) / (image_width as f64 - 1.0),
                         (i as f64 + rand.random_double()) / (image_height as f64 - 1.0),
                     );
                     //let r = Ray::new(origin, lower_left_corner + u * horizontal + v * vertical);
                     let r = camera.get_ray(rand, u, v);
-                    color += ray_color(rand, &world, &r, max_depth);
-                }
+                    let sample_color =
+                        tracer.ray_color(rand, &r, None, Vec3::repeat(1.0), max_depth);
+                    pixel.color += sample_color;
+                    pixel.samples += 1;
 
-                output_color(output_px, color, samples_per_px);
+                    if adaptive {
+                        pixel.stats.add(sample_color);
+                        if pixel.stats.count >= min_samples_per_px
+                            && pixel.stats.relative_error() < noise_threshold
+                        {
+                            pixel.converged = true;
+                            break;
+                        }
+                    }
+                }
             }
         };
 
+        // Every tile works on a copy of its pixels, which goes back into the image at the end
+        let tiles = tiles::tiles(
+            image_width as u32,
+            image_height as u32,
+            self.params.tile_size,
+            self.params.tile_order,
+        );
+        let pixel_indices = |tile: Tile| {
+            (tile.y..tile.y + tile.height).flat_map(move |row| {
+                let start = row as usize * image_width as usize + tile.x as usize;
+                start..start + tile.width as usize
+            })
+        };
+        let mut tiles = tiles
+            .into_iter()
+            .map(|tile| {
+                let pixels = pixel_indices(tile).map(|idx| self.pixels[idx]);
+                (tile, pixels.collect::<Vec<_>>())
+            })
+            .collect::<Vec<_>>();
+
+        #[cfg(not(feature = "parallel"))]
+        {
+            use std::io::Write;
+            let stderr = &mut std::io::stderr();
+            let count = tiles.len();
+            for (done, (tile, pixels)) in tiles.iter_mut().enumerate() {
+                write!(stderr, "\rTiles remaining: {:04}", count - done).unwrap();
+                render_tile(*tile, pixels);
+            }
+            write!(stderr, "\rTiles remaining: {:04}", 0).unwrap();
+        }
+        // Every thread takes the next tile in order until there are none left, so the tiles are
+        // started in order, and a slow one doesn't hold up the others
         #[cfg(feature = "parallel")]
         {
-            thread_local! {
-                static RAND: std::cell::RefCell<RandState> =
-                    std::cell::RefCell::new(RandState::new())
+            use rayon::prelude::*;
+            use std::sync::{
+                atomic::{AtomicUsize, Ordering},
+                Mutex,
             };
-            RAND.with(|rand| {
-                let mut rand = rand.borrow_mut();
-                work(&mut *rand);
-            });
+            let queue = tiles.iter_mut().map(Mutex::new).collect::<Vec<_>>();
+            let next = AtomicUsize::new(0);
+            (0..rayon::current_num_threads())
+                .into_par_iter()
+                .for_each(|_| {
+                    while let Some(tile) = queue.get(next.fetch_add(1, Ordering::Relaxed)) {
+                        let (tile, pixels) = &mut **tile.lock().unwrap();
+                        render_tile(*tile, pixels);
+                    }
+                });
         }
 
-        #[cfg(not(feature = "parallel"))]
-        work(rand);
-    });
+        for (tile, pixels) in tiles {
+            for (idx, pixel) in pixel_indices(tile).zip(pixels) {
+                self.pixels[idx] = pixel;
+            }
+        }
+        self.passes += 1;
+        self.samples_taken += samples;
+    }
 
-    output
+    /// Renders passes until there are `passes`, calling `on_pass` after every one, and stops
+    /// early when `on_pass` returns false. The passes split `samples_per_px` evenly, including
+    /// the ones before, so a render resumed from a checkpoint ends up with the same samples
+    pub fn render_passes(&mut self, passes: u32, mut on_pass: impl FnMut(&Self) -> bool) {
+        let (total, passes) = (self.params.samples_per_px, passes.max(1));
+        while self.passes < passes {
+            let end = (total as u64 * (self.passes as u64 + 1) / passes as u64) as u32;
+            self.render_pass(end.saturating_sub(self.samples_taken));
+            if !on_pass(self) {
+                break;
+            }
+        }
+    }
 }
+
+impl<W> ProgressiveRender<'_, W> {
+    /// Passes rendered so far
+    pub fn passes(&self) -> u32 {
+        self.passes
+    }
+
+    /// Calls `store` with the average of the samples of every pixel so far and its 3 output
+    /// channels
+    fn output_with<T: Copy + Default>(&self, store: impl Fn(&mut [T], Vec3)) -> Vec<T> {
+        let mut output = vec![T::default(); 3 * self.pixels.len()];
+        for (output_px, pixel) in output.chunks_mut(3).zip(&self.pixels) {
+            // Divide by the number of samples using a multiplication
+            store(output_px, pixel.color * (1.0 / pixel.samples.max(1) as f64));
+        }
+        output
+    }
+
+    /// The image so far, like `render` returns it
+    pub fn image(&self) -> Vec<u8> {
+        self.output_with(output_color)
+    }
+
+    /// The image so far, like `render_hdr` returns it
+    pub fn image_hdr(&self) -> Vec<f32> {
+        self.output_with(output_hdr)
+    }
+
+    /// How many samples every pixel has taken so far, top row first
+    pub fn samples(&self) -> Vec<u32> {
+        self.pixels.iter().map(|pixel| pixel.samples).collect()
+    }
+}
+
+/// Renders `samples_per_px` samples per pixel in `passes` passes, calling `on_pass` after every
+/// one. Stops early when `on_pass` returns false, and returns the render so it can be refined
+/// further with `render_pass`
+pub fn render_progressive<'a, W: World>(
+    camera: Camera,
+    world: W,
+    lights: &'a HittableList,
+    params: RenderParams,
+    rand: &mut RandState,
+    passes: u32,
+    on_pass: impl FnMut(&ProgressiveRender<'a, W>) -> bool,
+) -> ProgressiveRender<'a, W> {
+    let mut progressive = ProgressiveRender::new(camera, world, lights, params, rand);
+    progressive.render_passes(passes, on_pass);
+    progressive
+}
+
+/// Renders the world, calling `store` with the average of the samples of every pixel and its 3
+/// output channels. Also returns how many samples every pixel took
+fn render_with<W: World, T: Copy + Default>(
+    camera: Camera,
+    world: W,
+    lights: &HittableList,
+    params: RenderParams,
+    rand: &mut RandState,
+    store: impl Fn(&mut [T], Vec3),
+) -> (Vec<T>, Vec<u32>) {
+    let samples_per_px = params.samples_per_px;
+    let mut progressive = ProgressiveRender::new(camera, world, lights, params, rand);
+    progressive.render_pass(samples_per_px);
+    (progressive.output_with(store), progressive.samples())
+}
+
+/// Renders the world. The seed for the image is taken from `rand`, so the output only depends on
+/// the state of `rand` (Not on the parallel feature or the number of threads)
+///
+/// `lights` are sampled directly from diffuse surfaces, which finds small lights much sooner. They
+/// have to be in the world too, and can be empty
+///
+/// Returns 8-bit gamma corrected RGB, top row first
+pub fn render<W: World>(
+    camera: Camera,
+    world: W,
+    lights: &HittableList,
+    params: RenderParams,
+    rand: &mut RandState,
+) -> Vec<u8> {
+    render_with(camera, world, lights, params, rand, output_color).0
+}
+
+/// Like `render`, but also returns how many samples every pixel took (Top row first), which
+/// `sample_heatmap` can show
+pub fn render_counting_samples<W: World>(
+    camera: Camera,
+    world: W,
+    lights: &HittableList,
+    params: RenderParams,
+    rand: &mut RandState,
+) -> (Vec<u8>, Vec<u32>) {
+    render_with(camera, world, lights, params, rand, output_color)
+}
+
+/// Like `render`, but returns the linear radiance of every pixel (RGB, top row first) without any
+/// gamma correction or clamping. Uses the same samples as `render`, given the same `rand` state
+pub fn render_hdr<W: World>(
+    camera: Camera,
+    world: W,
+    lights: &HittableList,
+    params: RenderParams,
+    rand: &mut RandState,
+) -> Vec<f32> {
+    render_with(camera, world, lights, params, rand, output_hdr).0
+}
+
+/// Colors the number of samples of every pixel, from black for none through red and yellow to
+/// white for `max_samples`. Returns 8-bit RGB like `render`
+pub fn sample_heatmap(samples: &[u32], max_samples: u32) -> Vec<u8> {
+    samples
+        .iter()
+        .flat_map(|&samples| {
+            let t = 3.0 * samples as f64 / max_samples.max(1) as f64;
+            let channel = |offset: f64| ((t - offset).clamp(0.0, 1.0) * 255.0).round() as u8;
+            [channel(0.0), channel(1.0), channel(2.0)]
+        })
+        .collect()
+}
+
+fn write_ppm(
+    out: &mut impl std::io::Write,
+    width: i64,
+    height: i64,
+    pixels: &[u8],
+) -> std::io::Result<()> {
+    writeln!(out, "P3\n{} {}\n255", width, height)?;
+    for x in pixels.chunks(3) {
+        writeln!(out, "{} {} {}", x[0], x[1], x[2])?;
+    }
+    out.flush()
+}
+
 #[allow(dead_code)]
 fn main() {
-    let mut rand = RandState::new();
-
-    // Image
-    let aspect_ratio = 3.0 / 2.0;
-    let image_width = 1200i64;
-    let image_height = (image_width as f64 / aspect_ratio) as i64;
-    let samples_per_px = 200;
-    let max_depth = 50;
-
-    let render_params = RenderParams {
-        image_width,
-        image_height,
-        samples_per_px,
-        max_depth,
+    let exit_with_error = |message: String| -> ! {
+        eprintln!("error: {}", message);
+        eprintln!("Try 'rtweekend --help' for more information");
+        std::process::exit(2);
+    };
+
+    let args = cli::parse_args(std::env::args().skip(1)).unwrap_or_else(|err| exit_with_error(err));
+    if args.help {
+        print!("{}", cli::usage());
+        return;
+    }
+    let threads = args.threads;
+    let cli::Job {
+        camera,
+        params,
+        world,
+        lights,
+        mut rand,
+        output,
+        format,
+        heatmap,
+        passes,
+        previews,
+        checkpoint,
+    } = args.into_job().unwrap_or_else(|err| exit_with_error(err));
+
+    if let Some(threads) = threads {
+        #[cfg(feature = "parallel")]
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build_global()
+            .unwrap_or_else(|err| exit_with_error(err.to_string()));
+        #[cfg(not(feature = "parallel"))]
+        if threads > 1 {
+            exit_with_error("--threads needs the parallel feature".to_string());
+        }
+    }
+
+    // Open the outputs before rendering, so a bad path doesn't waste a whole render
+    let output_name = match &output {
+        Some(path) => path.display().to_string(),
+        None => "stdout".to_string(),
+    };
+    let create = |path: &std::path::Path| match std::fs::File::create(path) {
+        Ok(file) => std::io::BufWriter::new(file),
+        Err(err) => exit_with_error(format!("could not create {}: {}", path.display(), err)),
+    };
+    let mut out: Box<dyn std::io::Write> = match &output {
+        Some(path) => Box::new(create(path)),
+        None => Box::new(std::io::BufWriter::new(std::io::stdout().lock())),
+    };
+    let heatmap = heatmap.map(|(path, format)| (create(&path), path, format));
+
+    let (image_width, image_height) = (params.image_width, params.image_height);
+    let (width, height) = (image_width as u32, image_height as u32);
+    let max_samples = params.samples_per_px;
+    let camera = camera.build();
+    let world = Bvh::new(world);
+    let mut progressive = match &checkpoint {
+        Some((path, _)) if path.exists() => {
+            let progressive = ProgressiveRender::resume(camera, world, &lights, params, path)
+                .unwrap_or_else(|err| {
+                    eprintln!("error: could not resume from {}: {}", path.display(), err);
+                    std::process::exit(1);
+                });
+            eprintln!(
+                "Resuming from {} after {} passes",
+                path.display(),
+                progressive.passes()
+            );
+            progressive
+        }
+        _ => ProgressiveRender::new(camera, world, &lights, params, &mut rand),
     };
+    let resumed_passes = progressive.passes();
+    let mut write_output = |progressive: &ProgressiveRender<Bvh>| {
+        // Every pass replaces the image of the one before
+        if let (true, Some(path)) = (progressive.passes() > resumed_passes + 1, &output) {
+            out = Box::new(create(path));
+        }
+        let result = if format.is_hdr() {
+            let output_pixels = progressive.image_hdr();
+            match format {
+                cli::OutputFormat::Hdr => hdr::write_rgbe(&mut out, width, height, &output_pixels),
+                cli::OutputFormat::Pfm => hdr::write_pfm(&mut out, width, height, &output_pixels),
+                _ => hdr::write_exr(&mut out, width, height, &output_pixels),
+            }
+        } else {
+            let output_pixels = progressive.image();
+            match format {
+                cli::OutputFormat::Png => png::write_png(&mut out, width, height, &output_pixels),
+                _ => write_ppm(&mut out, image_width, image_height, &output_pixels),
+            }
+        };
+        if let Err(err) = result.and_then(|()| out.flush()) {
+            eprintln!("error: could not write {}: {}", output_name, err);
+            std::process::exit(1);
+        }
+    };
+    let mut last_checkpoint = std::time::Instant::now();
+    progressive.render_passes(passes, |progressive| {
+        let last_pass = progressive.passes() >= passes;
+        if previews || last_pass {
+            write_output(progressive);
+        }
+        if let Some((path, every)) = &checkpoint {
+            if last_pass || last_checkpoint.elapsed() >= *every {
+                if let Err(err) = progressive.save_checkpoint(path) {
+                    eprintln!("error: could not write {}: {}", path.display(), err);
+                    std::process::exit(1);
+                }
+                last_checkpoint = std::time::Instant::now();
+            }
+        }
+        true
+    });
+    // A finished checkpoint has nothing left to render
+    if progressive.passes() == resumed_passes {
+        write_output(&progressive);
+    }
+    let samples = progressive.samples();
 
-    // World
-    //let world = scenes::normal_scene(&mut rand);
-    //let world = scenes::red_blue_scene(&mut rand);
-    //let world = scenes::moon_scene(&mut rand);
-    let world = scenes::random_scene(&mut rand);
-    //let world = scenes::pastel_scene(&mut rand);
-    //let world = scenes::perf_scene();
-
-    // Camera
-
-    let lookfrom = Vec3::new(13.0, 2.0, 3.0);
-    let lookat = Vec3::new(0.0, 0.0, 0.0);
-    let dist_to_focus = 1.0;
-    let camera = Camera::new(
-        lookfrom,
-        lookat,
-        Vec3::new(0.0, 1.0, 0.0),
-        20.0,
-        aspect_ratio,
-        0.0,
-        dist_to_focus,
-    );
-
-    // Render
-    println!("P3\n{} {}\n255", image_width, image_height);
-
-    let output = render(camera, world, render_params, &mut rand);
-
-    let stdout = std::io::stdout();
-    let lock = stdout.lock();
-    let mut buf = std::io::BufWriter::new(lock);
-    for x in output.chunks(3) {
-        use std::io::Write;
-        write!(&mut buf, "{} {} {}\n", x[0], x[1], x[2]).unwrap();
+    if let Some((mut out, path, format)) = heatmap {
+        let pixels = sample_heatmap(&samples, max_samples);
+        let result = match format {
+            cli::OutputFormat::Png => png::write_png(&mut out, width, height, &pixels),
+            _ => write_ppm(&mut out, image_width, image_height, &pixels),
+        };
+        if let Err(err) = result {
+            eprintln!("error: could not write {}: {}", path.display(), err);
+            std::process::exit(1);
+        }
     }
 }
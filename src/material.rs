@@ -1,4 +1,12 @@
-use super::{HitRecord, RandState, Ray, Vec3};
+// Constructors return the MaterialType (Rc or enum) instead of Self
+#![allow(clippy::new_ret_no_self)]
+
+use super::{
+    pdf::{CosinePdf, MaterialPdf, Pdf, SpherePdf},
+    scene_file::MaterialDesc,
+    texture::{SolidColor, TextureType},
+    HitRecord, RandState, Ray, Vec3,
+};
 
 #[cfg(feature = "dyn_mat")]
 use std::rc::Rc;
@@ -13,6 +21,8 @@ pub enum EnumMat {
     Lamb(LambertianDiffuse),
     Diele(Dielectric),
     Met(Metal),
+    Light(DiffuseLight),
+    Iso(Isotropic),
 }
 impl EnumMat {
     pub fn scatter(
@@ -33,6 +43,48 @@ impl EnumMat {
             EnumMat::Met(mat) => {
                 mat.scatter(rand, incoming_ray, hit_record, attenuation, scatter_ray)
             }
+            EnumMat::Light(mat) => {
+                mat.scatter(rand, incoming_ray, hit_record, attenuation, scatter_ray)
+            }
+            EnumMat::Iso(mat) => {
+                mat.scatter(rand, incoming_ray, hit_record, attenuation, scatter_ray)
+            }
+        }
+    }
+    pub fn emitted(&self, hit_record: &HitRecord) -> Vec3 {
+        match self {
+            EnumMat::Lamb(mat) => mat.emitted(hit_record),
+            EnumMat::Diele(mat) => mat.emitted(hit_record),
+            EnumMat::Met(mat) => mat.emitted(hit_record),
+            EnumMat::Light(mat) => mat.emitted(hit_record),
+            EnumMat::Iso(mat) => mat.emitted(hit_record),
+        }
+    }
+    pub fn pdf(&self, hit_record: &HitRecord) -> Option<MaterialPdf> {
+        match self {
+            EnumMat::Lamb(mat) => mat.pdf(hit_record),
+            EnumMat::Diele(mat) => mat.pdf(hit_record),
+            EnumMat::Met(mat) => mat.pdf(hit_record),
+            EnumMat::Light(mat) => mat.pdf(hit_record),
+            EnumMat::Iso(mat) => mat.pdf(hit_record),
+        }
+    }
+    pub fn eval(&self, incoming_ray: &Ray, hit_record: &HitRecord, direction: Vec3) -> Vec3 {
+        match self {
+            EnumMat::Lamb(mat) => mat.eval(incoming_ray, hit_record, direction),
+            EnumMat::Diele(mat) => mat.eval(incoming_ray, hit_record, direction),
+            EnumMat::Met(mat) => mat.eval(incoming_ray, hit_record, direction),
+            EnumMat::Light(mat) => mat.eval(incoming_ray, hit_record, direction),
+            EnumMat::Iso(mat) => mat.eval(incoming_ray, hit_record, direction),
+        }
+    }
+    pub fn describe(&self) -> Option<MaterialDesc> {
+        match self {
+            EnumMat::Lamb(mat) => mat.describe(),
+            EnumMat::Diele(mat) => mat.describe(),
+            EnumMat::Met(mat) => mat.describe(),
+            EnumMat::Light(mat) => mat.describe(),
+            EnumMat::Iso(mat) => mat.describe(),
         }
     }
 }
@@ -45,18 +97,40 @@ pub trait Material {
         attenuation: &mut Vec3,
         scatter_ray: &mut Ray,
     ) -> bool;
+    /// Light given off by the surface at the hit point. Most materials don't emit any
+    fn emitted(&self, _hit_record: &HitRecord) -> Vec3 {
+        Vec3::zero()
+    }
+    /// The density `scatter` picks directions from, for materials that scatter light all around.
+    /// Mirrors and glass scatter in a single direction, which no density can pick, so they don't
+    /// have one
+    fn pdf(&self, _hit_record: &HitRecord) -> Option<MaterialPdf> {
+        None
+    }
+    /// How much of the light coming from `direction` leaves towards the incoming ray: the BSDF times
+    /// the cosine with the normal. Only materials with a `pdf` can be evaluated
+    fn eval(&self, _incoming_ray: &Ray, _hit_record: &HitRecord, _direction: Vec3) -> Vec3 {
+        Vec3::zero()
+    }
+    /// Parameters used to write the material to a scene file, if it can be written
+    fn describe(&self) -> Option<MaterialDesc> {
+        None
+    }
 }
 #[derive(Clone)]
 pub struct LambertianDiffuse {
-    albedo: Vec3,
+    albedo: TextureType,
 }
 impl LambertianDiffuse {
-    #[cfg(feature = "dyn_mat")]
     pub fn new(albedo: Vec3) -> MaterialType {
+        Self::textured(SolidColor::new(albedo))
+    }
+    #[cfg(feature = "dyn_mat")]
+    pub fn textured(albedo: TextureType) -> MaterialType {
         Rc::new(Self { albedo })
     }
     #[cfg(not(feature = "dyn_mat"))]
-    pub fn new(albedo: Vec3) -> MaterialType {
+    pub fn textured(albedo: TextureType) -> MaterialType {
         EnumMat::Lamb(Self { albedo })
     }
 }
@@ -64,41 +138,52 @@ impl Material for LambertianDiffuse {
     fn scatter(
         &self,
         rand: &mut RandState,
-        _incoming_ray: &Ray,
+        incoming_ray: &Ray,
         hit_record: &HitRecord,
         attenuation: &mut Vec3,
         scatter_ray: &mut Ray,
     ) -> bool {
-        let mut scatter_direction = hit_record.normal + Vec3::random_unit_vector(rand);
-        if scatter_direction.near_zero() {
-            scatter_direction = hit_record.normal;
-        }
-
         *scatter_ray = Ray {
             orig: hit_record.p,
-            dir: scatter_direction,
+            dir: CosinePdf::new(hit_record.normal).generate(rand),
+            time: incoming_ray.time,
         };
-        *attenuation = self.albedo;
+        *attenuation = self.albedo.value(hit_record.u, hit_record.v, hit_record.p);
         true
     }
+    fn pdf(&self, hit_record: &HitRecord) -> Option<MaterialPdf> {
+        Some(MaterialPdf::Cosine(CosinePdf::new(hit_record.normal)))
+    }
+    fn eval(&self, _incoming_ray: &Ray, hit_record: &HitRecord, direction: Vec3) -> Vec3 {
+        let albedo = self.albedo.value(hit_record.u, hit_record.v, hit_record.p);
+        albedo * CosinePdf::new(hit_record.normal).value(direction)
+    }
+    fn describe(&self) -> Option<MaterialDesc> {
+        Some(MaterialDesc::Lambertian {
+            albedo: self.albedo.describe()?,
+        })
+    }
 }
 #[derive(Clone)]
 pub struct Metal {
-    albedo: Vec3,
+    albedo: TextureType,
     fuzzyness: f64,
 }
 impl Metal {
-    #[cfg(feature = "dyn_mat")]
     pub fn new(r: f64, g: f64, b: f64, fuzzyness: f64) -> MaterialType {
+        Self::textured(SolidColor::new(Vec3::new(r, g, b)), fuzzyness)
+    }
+    #[cfg(feature = "dyn_mat")]
+    pub fn textured(albedo: TextureType, fuzzyness: f64) -> MaterialType {
         Rc::new(Self {
-            albedo: Vec3::new(r, g, b),
+            albedo,
             fuzzyness: fuzzyness.min(1.0),
         })
     }
     #[cfg(not(feature = "dyn_mat"))]
-    pub fn new(r: f64, g: f64, b: f64, fuzzyness: f64) -> MaterialType {
+    pub fn textured(albedo: TextureType, fuzzyness: f64) -> MaterialType {
         EnumMat::Met(Self {
-            albedo: Vec3::new(r, g, b),
+            albedo,
             fuzzyness: fuzzyness.min(1.0),
         })
     }
@@ -117,11 +202,18 @@ impl Material for Metal {
         *scatter_ray = Ray {
             orig: hit_record.p,
             dir: reflected + self.fuzzyness * Vec3::random_in_unit_sphere(rand),
+            time: incoming_ray.time,
         };
-        *attenuation = self.albedo;
+        *attenuation = self.albedo.value(hit_record.u, hit_record.v, hit_record.p);
 
         scatter_ray.dir.dot(hit_record.normal) > 0.0
     }
+    fn describe(&self) -> Option<MaterialDesc> {
+        Some(MaterialDesc::Metal {
+            albedo: self.albedo.describe()?,
+            fuzzyness: self.fuzzyness,
+        })
+    }
 }
 #[derive(Clone)]
 pub struct Dielectric {
@@ -173,6 +265,7 @@ impl Material for Dielectric {
         *scatter_ray = Ray {
             orig: hit_record.p,
             dir,
+            time: incoming_ray.time,
         };
         //*attenuation = Vec3::repeat(cannot_refract as u8 as f64);
         //*attenuation = Vec3::repeat(hit_record.front_face as u8 as f64);
@@ -180,4 +273,90 @@ impl Material for Dielectric {
 
         true
     }
+    fn describe(&self) -> Option<MaterialDesc> {
+        Some(MaterialDesc::Dielectric {
+            refraction_idx: self.refraction_idx,
+        })
+    }
+}
+#[derive(Clone)]
+pub struct DiffuseLight {
+    emit: Vec3,
+}
+impl DiffuseLight {
+    /// `emit` is the emitted radiance, which can be above 1 to make brighter lights
+    #[cfg(feature = "dyn_mat")]
+    pub fn new(emit: Vec3) -> MaterialType {
+        Rc::new(Self { emit })
+    }
+    #[cfg(not(feature = "dyn_mat"))]
+    pub fn new(emit: Vec3) -> MaterialType {
+        EnumMat::Light(Self { emit })
+    }
+}
+impl Material for DiffuseLight {
+    fn scatter(
+        &self,
+        _rand: &mut RandState,
+        _incoming_ray: &Ray,
+        _hit_record: &HitRecord,
+        _attenuation: &mut Vec3,
+        _scatter_ray: &mut Ray,
+    ) -> bool {
+        false
+    }
+    fn emitted(&self, _hit_record: &HitRecord) -> Vec3 {
+        self.emit
+    }
+    fn describe(&self) -> Option<MaterialDesc> {
+        Some(MaterialDesc::DiffuseLight { emit: self.emit })
+    }
+}
+/// Phase function of a `ConstantMedium`, which scatters light the same way in every direction
+#[derive(Clone)]
+pub struct Isotropic {
+    albedo: TextureType,
+}
+impl Isotropic {
+    pub fn new(albedo: Vec3) -> MaterialType {
+        Self::textured(SolidColor::new(albedo))
+    }
+    #[cfg(feature = "dyn_mat")]
+    pub fn textured(albedo: TextureType) -> MaterialType {
+        Rc::new(Self { albedo })
+    }
+    #[cfg(not(feature = "dyn_mat"))]
+    pub fn textured(albedo: TextureType) -> MaterialType {
+        EnumMat::Iso(Self { albedo })
+    }
+}
+impl Material for Isotropic {
+    fn scatter(
+        &self,
+        rand: &mut RandState,
+        incoming_ray: &Ray,
+        hit_record: &HitRecord,
+        attenuation: &mut Vec3,
+        scatter_ray: &mut Ray,
+    ) -> bool {
+        *scatter_ray = Ray {
+            orig: hit_record.p,
+            dir: SpherePdf.generate(rand),
+            time: incoming_ray.time,
+        };
+        *attenuation = self.albedo.value(hit_record.u, hit_record.v, hit_record.p);
+        true
+    }
+    fn pdf(&self, _hit_record: &HitRecord) -> Option<MaterialPdf> {
+        Some(MaterialPdf::Sphere(SpherePdf))
+    }
+    fn eval(&self, _incoming_ray: &Ray, hit_record: &HitRecord, direction: Vec3) -> Vec3 {
+        let albedo = self.albedo.value(hit_record.u, hit_record.v, hit_record.p);
+        albedo * SpherePdf.value(direction)
+    }
+    fn describe(&self) -> Option<MaterialDesc> {
+        Some(MaterialDesc::Isotropic {
+            albedo: self.albedo.describe()?,
+        })
+    }
 }
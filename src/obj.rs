@@ -0,0 +1,372 @@
+//! Wavefront .obj and .mtl loading
+//!
+//! Faces are triangulated as fans and grouped into one TriangleMesh per material. Statements that
+//! don't affect the geometry (Groups, smoothing groups, lines, etc) are ignored.
+//!
+//! Materials are mapped onto the ones we have:
+//! - Materials with a non black emission (`Ke`) become a DiffuseLight
+//! - Transparent materials (`d` < 1, `Tr` > 0, or `illum` 4, 6, 7 or 9) become a Dielectric using `Ni`
+//! - Mirror-like materials (`illum` 3, or a black `Kd` with a non black `Ks`) become a Metal of color
+//!   `Ks`, with a fuzzyness that goes down as `Ns` goes from 0 to 1000
+//! - Everything else becomes a LambertianDiffuse of color `Kd`
+use super::{
+    material::{Dielectric, DiffuseLight, LambertianDiffuse, MaterialType, Metal},
+    triangle::TriangleMesh,
+    Vec3,
+};
+use std::collections::HashMap;
+use std::path::Path;
+
+#[derive(Debug)]
+pub struct ObjError {
+    pub file: String,
+    /// 1-based line number. 0 if the error is not about a specific line (Like a missing file)
+    pub line: usize,
+    pub message: String,
+}
+impl std::fmt::Display for ObjError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.line == 0 {
+            write!(f, "{}: {}", self.file, self.message)
+        } else {
+            write!(f, "{}:{}: {}", self.file, self.line, self.message)
+        }
+    }
+}
+impl std::error::Error for ObjError {}
+
+/// Loads an .obj file, and any .mtl files it references (Relative to the .obj's directory)
+pub fn load_obj(path: impl AsRef<Path>) -> Result<Vec<TriangleMesh>, ObjError> {
+    let path = path.as_ref();
+    let file = path.display().to_string();
+    let source = std::fs::read_to_string(path).map_err(|err| ObjError {
+        file: file.clone(),
+        line: 0,
+        message: err.to_string(),
+    })?;
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    parse_obj(&file, &source, |name| {
+        std::fs::read_to_string(dir.join(name)).map_err(|err| err.to_string())
+    })
+}
+
+/// Parses the contents of an .obj file. `file` is only used for error messages, and `read_mtl` is
+/// called with the name of every `mtllib` to get its contents
+pub fn parse_obj(
+    file: &str,
+    source: &str,
+    mut read_mtl: impl FnMut(&str) -> Result<String, String>,
+) -> Result<Vec<TriangleMesh>, ObjError> {
+    let mut positions = vec![];
+    let mut normals = vec![];
+    let mut uvs = vec![];
+    let mut materials: HashMap<String, MaterialType> = HashMap::new();
+    let mut groups = vec![MeshGroup::new(default_material())];
+    let mut group_by_material = HashMap::new();
+    let mut current_group = 0;
+
+    for (line_idx, line) in source.lines().enumerate() {
+        let err = |message: String| ObjError {
+            file: file.to_string(),
+            line: line_idx + 1,
+            message,
+        };
+        let mut words = statement_words(line);
+        let keyword = match words.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+        let args = words.collect::<Vec<_>>();
+        match keyword {
+            "v" => positions.push(parse_vec3(&args).map_err(err)?),
+            "vn" => normals.push(parse_vec3(&args).map_err(err)?),
+            "vt" => {
+                if args.is_empty() || args.len() > 3 {
+                    return Err(err(format!("expected 1 to 3 numbers, got {}", args.len())));
+                }
+                let u = parse_f64(args[0]).map_err(err)?;
+                let v = match args.get(1) {
+                    Some(v) => parse_f64(v).map_err(err)?,
+                    None => 0.0,
+                };
+                uvs.push([u, v]);
+            }
+            "f" => {
+                if args.len() < 3 {
+                    return Err(err(format!(
+                        "a face needs at least 3 vertices, got {}",
+                        args.len()
+                    )));
+                }
+                let counts = [positions.len(), uvs.len(), normals.len()];
+                let vertices = args
+                    .iter()
+                    .map(|vertex| parse_face_vertex(vertex, counts))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(err)?;
+                let group = &mut groups[current_group];
+                let local = vertices
+                    .into_iter()
+                    .map(|vertex| group.vertex(vertex))
+                    .collect::<Vec<_>>();
+                for i in 1..local.len() - 1 {
+                    group.indices.push([local[0], local[i], local[i + 1]]);
+                }
+            }
+            "usemtl" => {
+                let name = args.join(" ");
+                let material = match materials.get(&name) {
+                    Some(material) => material,
+                    None => return Err(err(format!("unknown material '{}'", name))),
+                };
+                current_group = *group_by_material.entry(name).or_insert_with(|| {
+                    groups.push(MeshGroup::new(material.clone()));
+                    groups.len() - 1
+                });
+            }
+            "mtllib" => {
+                for name in args {
+                    let mtl_source = read_mtl(name).map_err(|message| {
+                        err(format!("could not read '{}': {}", name, message))
+                    })?;
+                    materials.extend(parse_mtl(name, &mtl_source)?);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    groups
+        .into_iter()
+        .filter(|group| !group.indices.is_empty())
+        .map(|group| {
+            group
+                .build(&positions, &uvs, &normals)
+                .map_err(|message| ObjError {
+                    file: file.to_string(),
+                    line: 0,
+                    message,
+                })
+        })
+        .collect()
+}
+
+/// Parses the contents of an .mtl file into materials by name
+pub fn parse_mtl(file: &str, source: &str) -> Result<HashMap<String, MaterialType>, ObjError> {
+    let mut materials = HashMap::new();
+    let mut current: Option<(String, MtlParams)> = None;
+
+    for (line_idx, line) in source.lines().enumerate() {
+        let err = |message: String| ObjError {
+            file: file.to_string(),
+            line: line_idx + 1,
+            message,
+        };
+        let mut words = statement_words(line);
+        let keyword = match words.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+        let args = words.collect::<Vec<_>>();
+        if keyword == "newmtl" {
+            if args.is_empty() {
+                return Err(err("newmtl needs a name".to_string()));
+            }
+            if let Some((name, params)) = current.take() {
+                materials.insert(name, params.material());
+            }
+            current = Some((args.join(" "), MtlParams::default()));
+            continue;
+        }
+        let params = match (&mut current, keyword) {
+            (Some((_, params)), _) => params,
+            (None, "Kd" | "Ks" | "Ke" | "Ni" | "Ns" | "d" | "Tr" | "illum") => {
+                return Err(err(format!("'{}' before any newmtl", keyword)))
+            }
+            (None, _) => continue,
+        };
+        match keyword {
+            "Kd" => params.kd = parse_vec3(&args).map_err(err)?,
+            "Ks" => params.ks = parse_vec3(&args).map_err(err)?,
+            "Ke" => params.ke = parse_vec3(&args).map_err(err)?,
+            "Ni" => params.ni = parse_single(&args).map_err(err)?,
+            "Ns" => params.ns = parse_single(&args).map_err(err)?,
+            "d" => params.dissolve = parse_single(&args).map_err(err)?,
+            "Tr" => params.dissolve = 1.0 - parse_single(&args).map_err(err)?,
+            "illum" => {
+                let illum = parse_single(&args).map_err(err)?;
+                params.illum = illum as u32;
+            }
+            _ => {}
+        }
+    }
+    if let Some((name, params)) = current {
+        materials.insert(name, params.material());
+    }
+
+    Ok(materials)
+}
+
+fn default_material() -> MaterialType {
+    LambertianDiffuse::new(Vec3::repeat(0.5))
+}
+
+/// Splits a line into words, ignoring comments
+fn statement_words(line: &str) -> std::str::SplitWhitespace<'_> {
+    let line = match line.find('#') {
+        Some(comment_start) => &line[..comment_start],
+        None => line,
+    };
+    line.split_whitespace()
+}
+
+fn parse_f64(word: &str) -> Result<f64, String> {
+    word.parse()
+        .map_err(|_| format!("expected a number, got '{}'", word))
+}
+fn parse_single(args: &[&str]) -> Result<f64, String> {
+    match args {
+        [x] => parse_f64(x),
+        _ => Err(format!("expected 1 number, got {}", args.len())),
+    }
+}
+fn parse_vec3(args: &[&str]) -> Result<Vec3, String> {
+    // Positions can have an optional w component, which we ignore
+    if args.len() != 3 && args.len() != 4 {
+        return Err(format!("expected 3 numbers, got {}", args.len()));
+    }
+    Ok(Vec3::new(
+        parse_f64(args[0])?,
+        parse_f64(args[1])?,
+        parse_f64(args[2])?,
+    ))
+}
+
+/// Indices into the position, uv and normal arrays of one face vertex
+type FaceVertex = (usize, Option<usize>, Option<usize>);
+
+/// Parses `v`, `v/vt`, `v//vn` or `v/vt/vn`, resolving negative (Relative) indices
+fn parse_face_vertex(vertex: &str, counts: [usize; 3]) -> Result<FaceVertex, String> {
+    let parts = vertex.split('/').collect::<Vec<_>>();
+    if parts.len() > 3 || parts[0].is_empty() {
+        return Err(format!("invalid face vertex '{}'", vertex));
+    }
+    let mut indices = [None; 3];
+    for (i, part) in parts.iter().enumerate() {
+        if part.is_empty() {
+            continue;
+        }
+        let parsed: i64 = part
+            .parse()
+            .map_err(|_| format!("invalid index '{}' in face vertex '{}'", part, vertex))?;
+        let count = counts[i] as i64;
+        let resolved = if parsed < 0 {
+            count + parsed
+        } else {
+            parsed - 1
+        };
+        if parsed == 0 || resolved < 0 || resolved >= count {
+            let kind = ["position", "texture coordinate", "normal"][i];
+            return Err(format!(
+                "{} index {} is out of range (There are {})",
+                kind, parsed, count
+            ));
+        }
+        indices[i] = Some(resolved as usize);
+    }
+    Ok((indices[0].unwrap(), indices[1], indices[2]))
+}
+
+/// The faces using one material
+struct MeshGroup {
+    material: MaterialType,
+    vertices: Vec<FaceVertex>,
+    vertex_map: HashMap<FaceVertex, usize>,
+    indices: Vec<[usize; 3]>,
+}
+impl MeshGroup {
+    fn new(material: MaterialType) -> Self {
+        Self {
+            material,
+            vertices: vec![],
+            vertex_map: HashMap::new(),
+            indices: vec![],
+        }
+    }
+    /// Index of the vertex in the mesh, since obj uses separate indices for every attribute
+    fn vertex(&mut self, vertex: FaceVertex) -> usize {
+        let vertices = &mut self.vertices;
+        *self.vertex_map.entry(vertex).or_insert_with(|| {
+            vertices.push(vertex);
+            vertices.len() - 1
+        })
+    }
+    fn build(
+        self,
+        positions: &[Vec3],
+        uvs: &[[f64; 2]],
+        normals: &[Vec3],
+    ) -> Result<TriangleMesh, String> {
+        let mesh_positions = self.vertices.iter().map(|v| positions[v.0]).collect();
+        // Attributes are only used if every vertex has them
+        let mesh_uvs = self
+            .vertices
+            .iter()
+            .map(|v| v.1.map(|idx| uvs[idx]))
+            .collect::<Option<Vec<_>>>()
+            .unwrap_or_default();
+        let mesh_normals = self
+            .vertices
+            .iter()
+            .map(|v| v.2.map(|idx| normals[idx]))
+            .collect::<Option<Vec<_>>>()
+            .unwrap_or_default();
+        TriangleMesh::new(
+            mesh_positions,
+            mesh_normals,
+            mesh_uvs,
+            self.indices,
+            self.material,
+        )
+    }
+}
+
+struct MtlParams {
+    kd: Vec3,
+    ks: Vec3,
+    ke: Vec3,
+    ni: f64,
+    ns: f64,
+    dissolve: f64,
+    illum: u32,
+}
+impl Default for MtlParams {
+    fn default() -> Self {
+        Self {
+            kd: Vec3::repeat(0.5),
+            ks: Vec3::zero(),
+            ke: Vec3::zero(),
+            ni: 1.5,
+            ns: 0.0,
+            dissolve: 1.0,
+            illum: 2,
+        }
+    }
+}
+impl MtlParams {
+    fn material(&self) -> MaterialType {
+        let max = |c: Vec3| c.x().max(c.y()).max(c.z());
+        let transparent = self.dissolve < 1.0 || matches!(self.illum, 4 | 6 | 7 | 9);
+        let mirror = self.illum == 3 || (max(self.kd) <= 0.0 && max(self.ks) > 0.0);
+        if max(self.ke) > 0.0 {
+            DiffuseLight::new(self.ke)
+        } else if transparent {
+            Dielectric::new(self.ni)
+        } else if mirror {
+            let fuzzyness = (1.0 - self.ns / 1000.0).clamp(0.0, 1.0);
+            Metal::new(self.ks.x(), self.ks.y(), self.ks.z(), fuzzyness)
+        } else {
+            LambertianDiffuse::new(self.kd)
+        }
+    }
+}
@@ -0,0 +1,88 @@
+//! Probability densities over directions, used to choose where scattered rays go
+use super::{Hittable, RandState, Vec3};
+use std::f64::consts::PI;
+
+pub trait Pdf {
+    /// Density of `direction` per unit of solid angle. The direction doesn't need to be a unit
+    /// vector
+    fn value(&self, direction: Vec3) -> f64;
+    /// Random direction following the density, not necessarily a unit vector
+    fn generate(&self, rand: &mut RandState) -> Vec3;
+}
+
+/// Cosine of the angle with the normal over pi, which is how a Lambertian surface scatters light
+pub struct CosinePdf {
+    normal: Vec3,
+}
+impl CosinePdf {
+    /// `normal` has to be a unit vector
+    pub fn new(normal: Vec3) -> Self {
+        Self { normal }
+    }
+}
+impl Pdf for CosinePdf {
+    fn value(&self, direction: Vec3) -> f64 {
+        let cosine = direction.unit_vector().dot(self.normal);
+        cosine.max(0.0) / PI
+    }
+    fn generate(&self, rand: &mut RandState) -> Vec3 {
+        // A point on the unit sphere touching the surface is cosine distributed around the normal
+        let direction = self.normal + Vec3::random_unit_vector(rand);
+        if direction.near_zero() {
+            self.normal
+        } else {
+            direction
+        }
+    }
+}
+
+/// The same density in every direction
+pub struct SpherePdf;
+impl Pdf for SpherePdf {
+    fn value(&self, _direction: Vec3) -> f64 {
+        1.0 / (4.0 * PI)
+    }
+    fn generate(&self, rand: &mut RandState) -> Vec3 {
+        Vec3::random_unit_vector(rand)
+    }
+}
+
+/// Directions from `origin` towards the points of an object, with the density given by
+/// `Hittable::pdf_value`
+pub struct HittablePdf<'a, H: Hittable + ?Sized> {
+    object: &'a H,
+    origin: Vec3,
+}
+impl<'a, H: Hittable + ?Sized> HittablePdf<'a, H> {
+    pub fn new(object: &'a H, origin: Vec3) -> Self {
+        Self { object, origin }
+    }
+}
+impl<H: Hittable + ?Sized> Pdf for HittablePdf<'_, H> {
+    fn value(&self, direction: Vec3) -> f64 {
+        self.object.pdf_value(self.origin, direction)
+    }
+    fn generate(&self, rand: &mut RandState) -> Vec3 {
+        self.object.random(rand, self.origin)
+    }
+}
+
+/// The densities materials scatter light with, so they don't need to be boxed
+pub enum MaterialPdf {
+    Cosine(CosinePdf),
+    Sphere(SpherePdf),
+}
+impl Pdf for MaterialPdf {
+    fn value(&self, direction: Vec3) -> f64 {
+        match self {
+            MaterialPdf::Cosine(pdf) => pdf.value(direction),
+            MaterialPdf::Sphere(pdf) => pdf.value(direction),
+        }
+    }
+    fn generate(&self, rand: &mut RandState) -> Vec3 {
+        match self {
+            MaterialPdf::Cosine(pdf) => pdf.generate(rand),
+            MaterialPdf::Sphere(pdf) => pdf.generate(rand),
+        }
+    }
+}
@@ -0,0 +1,280 @@
+//! Flat shapes: planes, quads, disks and boxes made of quads
+use super::{
+    scene_file::ObjectDesc, Aabb, HitRecord, Hittable, HittableList, MaterialType, RandState, Ray,
+    Vec3,
+};
+
+/// Intersection with the plane through `point` with the unit `normal`. Returns the ray parameter
+fn hit_plane(ray: &Ray, point: Vec3, normal: Vec3, t_min: f64, t_max: f64) -> Option<f64> {
+    let denom = normal.dot(ray.dir);
+    if denom.abs() < 1e-12 {
+        // The ray is parallel to the plane
+        return None;
+    }
+    let t = normal.dot(point - ray.orig) / denom;
+    if t < t_min || t_max < t {
+        return None;
+    }
+    Some(t)
+}
+
+/// Density per unit of solid angle of picking a point on a flat shape uniformly, seen from `origin`
+/// along `direction`. 0 if the direction misses it
+fn flat_pdf_value(shape: &impl Hittable, area: f64, origin: Vec3, direction: Vec3) -> f64 {
+    let ray = Ray::new(origin, direction, 0.0);
+    let mut rec = HitRecord::default();
+    if !shape.hit(&ray, 0.001, f64::INFINITY, &mut rec) {
+        return 0.0;
+    }
+    let distance_squared = rec.t.powi(2) * direction.length_squared();
+    let cosine = direction.dot(rec.normal).abs() / direction.length();
+    distance_squared / (cosine * area)
+}
+
+/// Parallelogram with a corner at `q` and sides `u` and `v`. It faces the `u` x `v` side, and its uv
+/// coordinates go from 0 to 1 along each side
+pub struct Quad {
+    q: Vec3,
+    u: Vec3,
+    v: Vec3,
+    normal: Vec3,
+    /// Turns a point in the plane (relative to q) into uv coordinates
+    w: Vec3,
+    material: MaterialType,
+}
+impl Quad {
+    pub fn new(q: Vec3, u: Vec3, v: Vec3, material: MaterialType) -> Self {
+        let n = u.cross(v);
+        Self {
+            q,
+            u,
+            v,
+            normal: n.unit_vector(),
+            w: n / n.dot(n),
+            material,
+        }
+    }
+    /// Rectangle in the plane z = k, facing +z
+    pub fn xy_rect(x0: f64, x1: f64, y0: f64, y1: f64, k: f64, material: MaterialType) -> Self {
+        Self::new(
+            Vec3::new(x0, y0, k),
+            Vec3::new(x1 - x0, 0.0, 0.0),
+            Vec3::new(0.0, y1 - y0, 0.0),
+            material,
+        )
+    }
+    /// Rectangle in the plane y = k, facing +y
+    pub fn xz_rect(x0: f64, x1: f64, z0: f64, z1: f64, k: f64, material: MaterialType) -> Self {
+        Self::new(
+            Vec3::new(x0, k, z0),
+            Vec3::new(0.0, 0.0, z1 - z0),
+            Vec3::new(x1 - x0, 0.0, 0.0),
+            material,
+        )
+    }
+    /// Rectangle in the plane x = k, facing +x
+    pub fn yz_rect(y0: f64, y1: f64, z0: f64, z1: f64, k: f64, material: MaterialType) -> Self {
+        Self::new(
+            Vec3::new(k, y0, z0),
+            Vec3::new(0.0, y1 - y0, 0.0),
+            Vec3::new(0.0, 0.0, z1 - z0),
+            material,
+        )
+    }
+}
+impl Hittable for Quad {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, out: &mut HitRecord) -> bool {
+        let t = match hit_plane(ray, self.q, self.normal, t_min, t_max) {
+            Some(t) => t,
+            None => return false,
+        };
+        let p = ray.at(t);
+        let planar = p - self.q;
+        let alpha = self.w.dot(planar.cross(self.v));
+        let beta = self.w.dot(self.u.cross(planar));
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return false;
+        }
+        out.t = t;
+        out.p = p;
+        out.u = alpha;
+        out.v = beta;
+        out.set_face_normal(ray, self.normal);
+        out.material = self.material.clone();
+        true
+    }
+    fn bounding_box(&self, output_box: &mut Aabb) -> bool {
+        let corners = [self.q + self.u, self.q + self.v, self.q + self.u + self.v];
+        let bbox = corners.iter().fold(Aabb::new(self.q, self.q), |bbox, &p| {
+            bbox.surrounding(&Aabb::new(p, p))
+        });
+        *output_box = bbox.padded();
+        true
+    }
+    fn describe(&self) -> Option<ObjectDesc> {
+        Some(ObjectDesc::Quad {
+            q: self.q,
+            u: self.u,
+            v: self.v,
+            material: self.material.describe()?,
+        })
+    }
+    fn pdf_value(&self, origin: Vec3, direction: Vec3) -> f64 {
+        flat_pdf_value(self, self.u.cross(self.v).length(), origin, direction)
+    }
+    fn random(&self, rand: &mut RandState, origin: Vec3) -> Vec3 {
+        let p = self.q + rand.random_double() * self.u + rand.random_double() * self.v;
+        p - origin
+    }
+}
+
+/// Two unit vectors perpendicular to `normal` and to each other
+pub(crate) fn tangents(normal: Vec3) -> (Vec3, Vec3) {
+    let other = if normal.x().abs() > 0.9 {
+        Vec3::new(0.0, 1.0, 0.0)
+    } else {
+        Vec3::new(1.0, 0.0, 0.0)
+    };
+    let tangent = normal.cross(other).unit_vector();
+    (tangent, normal.cross(tangent))
+}
+
+/// Flat circle. Its uv coordinates map the square around it to [0, 1]
+pub struct Disk {
+    center: Vec3,
+    normal: Vec3,
+    radius: f64,
+    tangent: Vec3,
+    bitangent: Vec3,
+    material: MaterialType,
+}
+impl Disk {
+    pub fn new(center: Vec3, normal: Vec3, radius: f64, material: MaterialType) -> Self {
+        let normal = normal.unit_vector();
+        let (tangent, bitangent) = tangents(normal);
+        Self {
+            center,
+            normal,
+            radius,
+            tangent,
+            bitangent,
+            material,
+        }
+    }
+}
+impl Hittable for Disk {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, out: &mut HitRecord) -> bool {
+        let t = match hit_plane(ray, self.center, self.normal, t_min, t_max) {
+            Some(t) => t,
+            None => return false,
+        };
+        let p = ray.at(t);
+        let a = (p - self.center).dot(self.tangent) / self.radius;
+        let b = (p - self.center).dot(self.bitangent) / self.radius;
+        if a * a + b * b > 1.0 {
+            return false;
+        }
+        out.t = t;
+        out.p = p;
+        out.u = 0.5 * (a + 1.0);
+        out.v = 0.5 * (b + 1.0);
+        out.set_face_normal(ray, self.normal);
+        out.material = self.material.clone();
+        true
+    }
+    fn bounding_box(&self, output_box: &mut Aabb) -> bool {
+        // How far the edge reaches along each axis
+        let extent = |n: f64| self.radius * (1.0 - n * n).max(0.0).sqrt();
+        let [x, y, z] = self.normal.0;
+        let extent = Vec3::new(extent(x), extent(y), extent(z));
+        *output_box = Aabb::new(self.center - extent, self.center + extent).padded();
+        true
+    }
+    fn describe(&self) -> Option<ObjectDesc> {
+        Some(ObjectDesc::Disk {
+            center: self.center,
+            normal: self.normal,
+            radius: self.radius,
+            material: self.material.describe()?,
+        })
+    }
+    fn pdf_value(&self, origin: Vec3, direction: Vec3) -> f64 {
+        let area = std::f64::consts::PI * self.radius.powi(2);
+        flat_pdf_value(self, area, origin, direction)
+    }
+    fn random(&self, rand: &mut RandState, origin: Vec3) -> Vec3 {
+        let offset = self.radius * Vec3::random_in_unit_disk(rand);
+        self.center + offset.x() * self.tangent + offset.y() * self.bitangent - origin
+    }
+}
+
+/// Infinite plane through `point`, facing `normal`. Its uv coordinates are the distances from
+/// `point` along two directions in the plane, so they aren't limited to [0, 1] (A `UvChecker` tiles
+/// them)
+///
+/// It has no bounding box, so a `Bvh` checks it for every ray
+pub struct Plane {
+    point: Vec3,
+    normal: Vec3,
+    tangent: Vec3,
+    bitangent: Vec3,
+    material: MaterialType,
+}
+impl Plane {
+    pub fn new(point: Vec3, normal: Vec3, material: MaterialType) -> Self {
+        let normal = normal.unit_vector();
+        let (tangent, bitangent) = tangents(normal);
+        Self {
+            point,
+            normal,
+            tangent,
+            bitangent,
+            material,
+        }
+    }
+}
+impl Hittable for Plane {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, out: &mut HitRecord) -> bool {
+        let t = match hit_plane(ray, self.point, self.normal, t_min, t_max) {
+            Some(t) => t,
+            None => return false,
+        };
+        out.t = t;
+        out.p = ray.at(t);
+        out.u = (out.p - self.point).dot(self.tangent);
+        out.v = (out.p - self.point).dot(self.bitangent);
+        out.set_face_normal(ray, self.normal);
+        out.material = self.material.clone();
+        true
+    }
+    fn bounding_box(&self, _output_box: &mut Aabb) -> bool {
+        false
+    }
+    fn describe(&self) -> Option<ObjectDesc> {
+        Some(ObjectDesc::Plane {
+            point: self.point,
+            normal: self.normal,
+            material: self.material.describe()?,
+        })
+    }
+}
+
+/// Box between two opposite corners, made of six quads facing outwards
+pub fn cuboid(a: Vec3, b: Vec3, material: MaterialType) -> HittableList {
+    let min = Vec3::new(a.x().min(b.x()), a.y().min(b.y()), a.z().min(b.z()));
+    let max = Vec3::new(a.x().max(b.x()), a.y().max(b.y()), a.z().max(b.z()));
+    let dx = Vec3::new(max.x() - min.x(), 0.0, 0.0);
+    let dy = Vec3::new(0.0, max.y() - min.y(), 0.0);
+    let dz = Vec3::new(0.0, 0.0, max.z() - min.z());
+
+    let mut sides = HittableList::new();
+    let mut side = |q, u, v| sides.add(Quad::new(q, u, v, material.clone()));
+    // Front, right, back, left, top and bottom
+    side(Vec3::new(min.x(), min.y(), max.z()), dx, dy);
+    side(Vec3::new(max.x(), min.y(), max.z()), -dz, dy);
+    side(Vec3::new(max.x(), min.y(), min.z()), -dx, dy);
+    side(min, dz, dy);
+    side(Vec3::new(min.x(), max.y(), max.z()), dx, -dz);
+    side(min, dx, dz);
+    sides
+}
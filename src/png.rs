@@ -0,0 +1,692 @@
+//! Dependency-free PNG writer for 8-bit RGB images, and a reader for image textures
+//!
+//! The image data is compressed with our own deflate implementation, which only uses the fixed
+//! huffman codes and greedy LZ77 matching. That's a lot worse than zlib's best, but a lot better
+//! than no compression at all, and much smaller than the ASCII PPM files. The reader handles any
+//! deflate stream, so it can load PNGs written by other programs
+use std::io::Write;
+
+pub(crate) const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+const BYTES_PER_PX: usize = 3;
+
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut n = 0;
+    while n < 256 {
+        let mut c = n as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 {
+                0xEDB8_8320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+            k += 1;
+        }
+        table[n] = c;
+        n += 1;
+    }
+    table
+};
+
+fn crc32(chunks: &[&[u8]]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for byte in chunks.iter().flat_map(|chunk| chunk.iter()) {
+        crc = CRC_TABLE[((crc ^ *byte as u32) & 0xFF) as usize] ^ (crc >> 8);
+    }
+    crc ^ 0xFFFF_FFFF
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    const MOD: u32 = 65521;
+    let (mut a, mut b) = (1u32, 0u32);
+    // 5552 is the most bytes that can be added before b can overflow
+    for block in data.chunks(5552) {
+        for byte in block {
+            a += *byte as u32;
+            b += a;
+        }
+        a %= MOD;
+        b %= MOD;
+    }
+    (b << 16) | a
+}
+
+/// Encodes `pixels` (RGB, top row first, like `render` returns them) as a PNG file
+pub fn encode_png(width: u32, height: u32, pixels: &[u8]) -> Vec<u8> {
+    assert_eq!(
+        pixels.len(),
+        width as usize * height as usize * BYTES_PER_PX,
+        "the pixels don't match the size of the image"
+    );
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend_from_slice(&width.to_be_bytes());
+    ihdr.extend_from_slice(&height.to_be_bytes());
+    // Bit depth 8, color type 2 (RGB), default compression, filtering and no interlacing
+    ihdr.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+    let filtered = filter_scanlines(width as usize * BYTES_PER_PX, pixels);
+    let idat = zlib_compress(&filtered);
+
+    let mut out = Vec::with_capacity(idat.len() + 64);
+    out.extend_from_slice(&SIGNATURE);
+    write_chunk(&mut out, b"IHDR", &ihdr);
+    write_chunk(&mut out, b"IDAT", &idat);
+    write_chunk(&mut out, b"IEND", &[]);
+    out
+}
+
+pub fn write_png(
+    out: &mut impl Write,
+    width: u32,
+    height: u32,
+    pixels: &[u8],
+) -> std::io::Result<()> {
+    out.write_all(&encode_png(width, height, pixels))?;
+    out.flush()
+}
+
+fn write_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    out.extend_from_slice(&crc32(&[kind, data]).to_be_bytes());
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = (
+        (p - a as i16).abs(),
+        (p - b as i16).abs(),
+        (p - c as i16).abs(),
+    );
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+/// Filters every scanline with the filter type that gives the smallest sum of absolute
+/// differences, the heuristic suggested by the PNG spec
+fn filter_scanlines(stride: usize, pixels: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(pixels.len() + pixels.len() / stride.max(1));
+    let zero_row = vec![0u8; stride];
+    let mut candidates = vec![vec![0u8; stride]; 5];
+    for (row_idx, row) in pixels.chunks(stride).enumerate() {
+        let prev = if row_idx == 0 {
+            &zero_row[..]
+        } else {
+            &pixels[(row_idx - 1) * stride..row_idx * stride]
+        };
+        for i in 0..stride {
+            let a = if i >= BYTES_PER_PX {
+                row[i - BYTES_PER_PX]
+            } else {
+                0
+            };
+            let b = prev[i];
+            let c = if i >= BYTES_PER_PX {
+                prev[i - BYTES_PER_PX]
+            } else {
+                0
+            };
+            let x = row[i];
+            candidates[0][i] = x;
+            candidates[1][i] = x.wrapping_sub(a);
+            candidates[2][i] = x.wrapping_sub(b);
+            candidates[3][i] = x.wrapping_sub(((a as u16 + b as u16) / 2) as u8);
+            candidates[4][i] = x.wrapping_sub(paeth(a, b, c));
+        }
+        let cost = |filtered: &[u8]| -> u64 {
+            filtered
+                .iter()
+                .map(|&byte| (byte as i8).unsigned_abs() as u64)
+                .sum()
+        };
+        let (filter_type, best) = candidates
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, filtered)| cost(filtered))
+            .unwrap();
+        out.push(filter_type as u8);
+        out.extend_from_slice(best);
+    }
+    out
+}
+
+/// Writes bits starting from the least significant one, like deflate expects
+struct BitWriter {
+    out: Vec<u8>,
+    bits: u64,
+    bit_count: u32,
+}
+impl BitWriter {
+    fn new(out: Vec<u8>) -> Self {
+        Self {
+            out,
+            bits: 0,
+            bit_count: 0,
+        }
+    }
+    fn write(&mut self, value: u32, count: u32) {
+        self.bits |= (value as u64) << self.bit_count;
+        self.bit_count += count;
+        while self.bit_count >= 8 {
+            self.out.push(self.bits as u8);
+            self.bits >>= 8;
+            self.bit_count -= 8;
+        }
+    }
+    /// Huffman codes are stored starting from their most significant bit
+    fn write_code(&mut self, code: u32, len: u32) {
+        self.write(code.reverse_bits() >> (32 - len), len);
+    }
+    fn finish(mut self) -> Vec<u8> {
+        if self.bit_count > 0 {
+            self.out.push(self.bits as u8);
+        }
+        self.out
+    }
+}
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+/// Writes a literal/length symbol with the fixed huffman code
+fn write_fixed_symbol(bits: &mut BitWriter, symbol: u32) {
+    match symbol {
+        0..=143 => bits.write_code(0b0011_0000 + symbol, 8),
+        144..=255 => bits.write_code(0b1_1001_0000 + symbol - 144, 9),
+        256..=279 => bits.write_code(symbol - 256, 7),
+        _ => bits.write_code(0b1100_0000 + symbol - 280, 8),
+    }
+}
+
+fn write_match(bits: &mut BitWriter, length: usize, dist: usize) {
+    let code = LENGTH_BASE
+        .iter()
+        .rposition(|&base| base as usize <= length)
+        .unwrap();
+    write_fixed_symbol(bits, 257 + code as u32);
+    bits.write(
+        (length - LENGTH_BASE[code] as usize) as u32,
+        LENGTH_EXTRA[code] as u32,
+    );
+    let code = DIST_BASE
+        .iter()
+        .rposition(|&base| base as usize <= dist)
+        .unwrap();
+    bits.write_code(code as u32, 5);
+    bits.write(
+        (dist - DIST_BASE[code] as usize) as u32,
+        DIST_EXTRA[code] as u32,
+    );
+}
+
+const WINDOW_SIZE: usize = 1 << 15;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const HASH_BITS: u32 = 15;
+/// How many previous positions with the same hash are checked when looking for a match
+const MAX_CHAIN: usize = 64;
+
+fn hash3(data: &[u8]) -> usize {
+    let x = (data[0] as u32) << 16 | (data[1] as u32) << 8 | data[2] as u32;
+    (x.wrapping_mul(0x9E37_79B1) >> (32 - HASH_BITS)) as usize
+}
+
+/// Compresses `data` into a zlib stream made of a single fixed huffman deflate block
+fn zlib_compress(data: &[u8]) -> Vec<u8> {
+    // CMF: deflate with a 32K window. FLG: no dictionary, and a check value that makes the
+    // header a multiple of 31
+    let mut bits = BitWriter::new(vec![0x78, 0x01]);
+    // BFINAL, and BTYPE 01 (Fixed huffman codes)
+    bits.write(1, 1);
+    bits.write(1, 2);
+
+    const NONE: usize = usize::MAX;
+    let mut head = vec![NONE; 1 << HASH_BITS];
+    let mut prev = vec![NONE; WINDOW_SIZE];
+    let insert = |pos: usize, head: &mut [usize], prev: &mut [usize]| {
+        if pos + MIN_MATCH <= data.len() {
+            let hash = hash3(&data[pos..]);
+            prev[pos % WINDOW_SIZE] = head[hash];
+            head[hash] = pos;
+        }
+    };
+
+    let mut pos = 0;
+    while pos < data.len() {
+        let mut best_len = 0;
+        let mut best_dist = 0;
+        if pos + MIN_MATCH <= data.len() {
+            let max_len = MAX_MATCH.min(data.len() - pos);
+            let mut candidate = head[hash3(&data[pos..])];
+            let mut chain = 0;
+            while candidate != NONE && pos - candidate <= WINDOW_SIZE && chain < MAX_CHAIN {
+                let len = data[candidate..]
+                    .iter()
+                    .zip(&data[pos..pos + max_len])
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                if len > best_len {
+                    best_len = len;
+                    best_dist = pos - candidate;
+                    if len == max_len {
+                        break;
+                    }
+                }
+                let next = prev[candidate % WINDOW_SIZE];
+                // Entries older than the window were overwritten by newer positions
+                if next == NONE || next >= candidate {
+                    break;
+                }
+                candidate = next;
+                chain += 1;
+            }
+        }
+
+        if best_len >= MIN_MATCH {
+            write_match(&mut bits, best_len, best_dist);
+            for p in pos..pos + best_len {
+                insert(p, &mut head, &mut prev);
+            }
+            pos += best_len;
+        } else {
+            write_fixed_symbol(&mut bits, data[pos] as u32);
+            insert(pos, &mut head, &mut prev);
+            pos += 1;
+        }
+    }
+    write_fixed_symbol(&mut bits, 256);
+
+    let mut out = bits.finish();
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+/// Reads bits starting from the least significant one
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    bits: u64,
+    bit_count: u32,
+}
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            pos: 0,
+            bits: 0,
+            bit_count: 0,
+        }
+    }
+    fn read(&mut self, count: u32) -> Result<u32, String> {
+        while self.bit_count < count {
+            let byte = *self
+                .data
+                .get(self.pos)
+                .ok_or_else(|| "the compressed data is truncated".to_string())?;
+            self.bits |= (byte as u64) << self.bit_count;
+            self.pos += 1;
+            self.bit_count += 8;
+        }
+        let value = (self.bits & ((1u64 << count) - 1)) as u32;
+        self.bits >>= count;
+        self.bit_count -= count;
+        Ok(value)
+    }
+    /// Skips to the next byte boundary, for stored blocks
+    fn align(&mut self) {
+        self.bits >>= self.bit_count % 8;
+        self.bit_count -= self.bit_count % 8;
+    }
+}
+
+/// Canonical huffman code, decoded one bit at a time
+struct Huffman {
+    /// Number of codes of every length
+    counts: [u16; 16],
+    /// Symbols sorted by code
+    symbols: Vec<u16>,
+}
+impl Huffman {
+    fn new(lengths: &[u8]) -> Self {
+        let mut counts = [0u16; 16];
+        for &len in lengths {
+            counts[len as usize] += 1;
+        }
+        counts[0] = 0;
+        let mut offsets = [0u16; 16];
+        for len in 1..16 {
+            offsets[len] = offsets[len - 1] + counts[len - 1];
+        }
+        let mut symbols = vec![0; lengths.iter().filter(|&&len| len != 0).count()];
+        for (symbol, &len) in lengths.iter().enumerate() {
+            if len != 0 {
+                symbols[offsets[len as usize] as usize] = symbol as u16;
+                offsets[len as usize] += 1;
+            }
+        }
+        Self { counts, symbols }
+    }
+    fn decode(&self, bits: &mut BitReader) -> Result<u16, String> {
+        // First code of the current length, and index of its symbol
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for len in 1..16 {
+            code |= bits.read(1)? as i32;
+            let count = self.counts[len] as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err("invalid huffman code".to_string())
+    }
+}
+
+fn fixed_huffman() -> (Huffman, Huffman) {
+    let mut lengths = [0u8; 288];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+    (Huffman::new(&lengths), Huffman::new(&[5; 30]))
+}
+
+/// Reads the code lengths of a dynamic huffman block
+fn dynamic_huffman(bits: &mut BitReader) -> Result<(Huffman, Huffman), String> {
+    let literal_count = bits.read(5)? as usize + 257;
+    let dist_count = bits.read(5)? as usize + 1;
+    let code_length_count = bits.read(4)? as usize + 4;
+    const ORDER: [usize; 19] = [
+        16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+    ];
+    let mut code_lengths = [0u8; 19];
+    for &idx in &ORDER[..code_length_count] {
+        code_lengths[idx] = bits.read(3)? as u8;
+    }
+    let code_length_huffman = Huffman::new(&code_lengths);
+
+    let mut lengths = vec![];
+    while lengths.len() < literal_count + dist_count {
+        let (value, repeat) = match code_length_huffman.decode(bits)? {
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 => {
+                let prev = *lengths
+                    .last()
+                    .ok_or_else(|| "repeat without a previous length".to_string())?;
+                (prev, 3 + bits.read(2)?)
+            }
+            17 => (0, 3 + bits.read(3)?),
+            _ => (0, 11 + bits.read(7)?),
+        };
+        lengths.extend(std::iter::repeat_n(value, repeat as usize));
+    }
+    if lengths.len() > literal_count + dist_count {
+        return Err("too many code lengths".to_string());
+    }
+    Ok((
+        Huffman::new(&lengths[..literal_count]),
+        Huffman::new(&lengths[literal_count..]),
+    ))
+}
+
+/// Decompresses a zlib stream. The adler32 checksum is checked
+fn zlib_decompress(stream: &[u8]) -> Result<Vec<u8>, String> {
+    if stream.len() < 6
+        || stream[0] & 0x0F != 8
+        || !(stream[0] as u32 * 256 + stream[1] as u32).is_multiple_of(31)
+    {
+        return Err("invalid zlib header".to_string());
+    }
+    if stream[1] & 0x20 != 0 {
+        return Err("zlib preset dictionaries are not supported".to_string());
+    }
+    let mut bits = BitReader::new(&stream[2..]);
+    let mut out: Vec<u8> = vec![];
+    loop {
+        let last = bits.read(1)?;
+        match bits.read(2)? {
+            0 => {
+                bits.align();
+                let len = bits.read(16)?;
+                let nlen = bits.read(16)?;
+                if len != !nlen & 0xFFFF {
+                    return Err("invalid stored block length".to_string());
+                }
+                for _ in 0..len {
+                    out.push(bits.read(8)? as u8);
+                }
+            }
+            kind @ 1..=2 => {
+                let (literals, dists) = if kind == 1 {
+                    fixed_huffman()
+                } else {
+                    dynamic_huffman(&mut bits)?
+                };
+                loop {
+                    let symbol = literals.decode(&mut bits)? as usize;
+                    if symbol < 256 {
+                        out.push(symbol as u8);
+                        continue;
+                    }
+                    if symbol == 256 {
+                        break;
+                    }
+                    let code = symbol - 257;
+                    if code >= LENGTH_BASE.len() {
+                        return Err("invalid length code".to_string());
+                    }
+                    let length =
+                        LENGTH_BASE[code] as usize + bits.read(LENGTH_EXTRA[code] as u32)? as usize;
+                    let code = dists.decode(&mut bits)? as usize;
+                    if code >= DIST_BASE.len() {
+                        return Err("invalid distance code".to_string());
+                    }
+                    let dist =
+                        DIST_BASE[code] as usize + bits.read(DIST_EXTRA[code] as u32)? as usize;
+                    if dist > out.len() {
+                        return Err("distance goes back too far".to_string());
+                    }
+                    for _ in 0..length {
+                        out.push(out[out.len() - dist]);
+                    }
+                }
+            }
+            _ => return Err("invalid deflate block type".to_string()),
+        }
+        if last == 1 {
+            break;
+        }
+    }
+
+    bits.align();
+    let mut adler = 0;
+    for _ in 0..4 {
+        adler = adler << 8 | bits.read(8)?;
+    }
+    if adler != adler32(&out) {
+        return Err("zlib checksum mismatch".to_string());
+    }
+    Ok(out)
+}
+
+/// Decodes a PNG into 8-bit RGB pixels, top row first. Returns (width, height, pixels)
+///
+/// Every color type and bit depth is supported, but not interlacing. Transparency is ignored
+pub fn decode_png(file: &[u8]) -> Result<(usize, usize, Vec<u8>), String> {
+    if !file.starts_with(&SIGNATURE) {
+        return Err("not a PNG file".to_string());
+    }
+    let mut rest = &file[SIGNATURE.len()..];
+    let mut header = None;
+    let mut palette = vec![];
+    let mut idat = vec![];
+    loop {
+        if rest.len() < 12 {
+            return Err("the PNG file is truncated".to_string());
+        }
+        let len = u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+        if rest.len() < 12 + len {
+            return Err("the PNG file is truncated".to_string());
+        }
+        let kind = &rest[4..8];
+        let data = &rest[8..8 + len];
+        let crc =
+            u32::from_be_bytes([rest[8 + len], rest[9 + len], rest[10 + len], rest[11 + len]]);
+        if crc != crc32(&[kind, data]) {
+            return Err(format!(
+                "bad checksum in the {} chunk",
+                String::from_utf8_lossy(kind)
+            ));
+        }
+        match kind {
+            b"IHDR" if len == 13 => {
+                let width = u32::from_be_bytes([data[0], data[1], data[2], data[3]]) as usize;
+                let height = u32::from_be_bytes([data[4], data[5], data[6], data[7]]) as usize;
+                let (bit_depth, color_type, interlace) = (data[8], data[9], data[12]);
+                if interlace != 0 {
+                    return Err("interlaced PNGs are not supported".to_string());
+                }
+                let channels = match (color_type, bit_depth) {
+                    (0, 1 | 2 | 4 | 8 | 16) => 1,
+                    (3, 1 | 2 | 4 | 8) => 1,
+                    (2, 8 | 16) => 3,
+                    (4, 8 | 16) => 2,
+                    (6, 8 | 16) => 4,
+                    _ => {
+                        return Err(format!(
+                            "invalid color type {} with bit depth {}",
+                            color_type, bit_depth
+                        ))
+                    }
+                };
+                if width == 0 || height == 0 {
+                    return Err("the image is empty".to_string());
+                }
+                header = Some((width, height, bit_depth as usize, color_type, channels));
+            }
+            b"PLTE" => palette = data.to_vec(),
+            b"IDAT" => idat.extend_from_slice(data),
+            b"IEND" => break,
+            _ => {}
+        }
+        rest = &rest[12 + len..];
+    }
+    let (width, height, bit_depth, color_type, channels) =
+        header.ok_or_else(|| "missing IHDR chunk".to_string())?;
+
+    let raw = zlib_decompress(&idat)?;
+    let bits_per_px = bit_depth * channels;
+    let stride = width
+        .checked_mul(bits_per_px)
+        .ok_or("the image is too large")?
+        .div_ceil(8);
+    let data_len = (stride + 1)
+        .checked_mul(height)
+        .ok_or("the image is too large")?;
+    let pixel_len = width
+        .checked_mul(height)
+        .and_then(|pixels| pixels.checked_mul(3))
+        .ok_or("the image is too large")?;
+    // Filters work on bytes, and on whole pixels for the byte to the left
+    let filter_bpp = bits_per_px.div_ceil(8);
+    if raw.len() < data_len {
+        return Err("the image data is truncated".to_string());
+    }
+    let mut scanlines = vec![0u8; height * stride];
+    for row in 0..height {
+        let filter = raw[row * (stride + 1)];
+        let line = &raw[row * (stride + 1) + 1..][..stride];
+        let (done, current) = scanlines.split_at_mut(row * stride);
+        let prev = if row > 0 {
+            &done[(row - 1) * stride..]
+        } else {
+            &[][..]
+        };
+        let current = &mut current[..stride];
+        for i in 0..stride {
+            let a = if i >= filter_bpp {
+                current[i - filter_bpp]
+            } else {
+                0
+            };
+            let b = prev.get(i).copied().unwrap_or(0);
+            let c = if i >= filter_bpp {
+                prev.get(i - filter_bpp).copied().unwrap_or(0)
+            } else {
+                0
+            };
+            let predictor = match filter {
+                0 => 0,
+                1 => a,
+                2 => b,
+                3 => ((a as u16 + b as u16) / 2) as u8,
+                4 => paeth(a, b, c),
+                _ => return Err(format!("invalid filter type {}", filter)),
+            };
+            current[i] = line[i].wrapping_add(predictor);
+        }
+    }
+
+    // Samples scaled to 8 bits
+    let sample = |line: &[u8], idx: usize| -> u8 {
+        match bit_depth {
+            8 => line[idx],
+            16 => line[2 * idx],
+            _ => {
+                let bit = idx * bit_depth;
+                let value = (line[bit / 8] >> (8 - bit_depth - bit % 8)) & ((1 << bit_depth) - 1);
+                // Palette indices are not scaled
+                if color_type == 3 {
+                    value
+                } else {
+                    (value as usize * 255 / ((1 << bit_depth) - 1)) as u8
+                }
+            }
+        }
+    };
+    let mut pixels = Vec::with_capacity(pixel_len);
+    for line in scanlines.chunks(stride) {
+        for x in 0..width {
+            match color_type {
+                0 | 4 => {
+                    let gray = sample(line, x * channels);
+                    pixels.extend_from_slice(&[gray, gray, gray]);
+                }
+                3 => {
+                    let idx = sample(line, x) as usize;
+                    let color = palette
+                        .get(3 * idx..3 * idx + 3)
+                        .ok_or_else(|| format!("palette index {} is out of range", idx))?;
+                    pixels.extend_from_slice(color);
+                }
+                _ => {
+                    for c in 0..3 {
+                        pixels.push(sample(line, x * channels + c));
+                    }
+                }
+            }
+        }
+    }
+    Ok((width, height, pixels))
+}
@@ -0,0 +1,506 @@
+//! Where the random numbers of each sample come from. Besides independent numbers, samplers can
+//! spread the samples of a pixel evenly (Stratified and low-discrepancy sequences), which makes
+//! images less noisy with the same number of samples
+#[cfg(all(feature = "wincrypt_rand", target_os = "windows"))]
+use super::win32_rand::RandState as Generator;
+#[cfg(not(all(feature = "wincrypt_rand", target_os = "windows")))]
+use super::xoshiro_rand::RandState as Generator;
+use std::sync::OnceLock;
+
+/// The numbers of every sample of every pixel, one dimension at a time
+///
+/// Each dimension is one decision of a path (Where in the pixel it starts, which way the first
+/// bounce goes...), and has the same meaning for every sample of a pixel, so the samples can be
+/// spread out over it
+pub trait Sampler {
+    /// Dimension `dim` of sample `index` of `pixel`, uniform in [0, 1)
+    fn sample(&self, pixel: [u32; 2], index: u32, dim: u32) -> f64;
+}
+
+/// The splitmix64 finalizer, which scrambles all the bits of `z`
+pub(crate) fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+fn hash(seed: u64, pixel: [u32; 2], a: u32, b: u32) -> u64 {
+    let pixel = ((pixel[0] as u64) << 32) | pixel[1] as u64;
+    mix(mix(mix(seed ^ pixel) ^ a as u64) ^ b as u64)
+}
+fn unit_u64(bits: u64) -> f64 {
+    (bits >> 11) as f64 * (1.0 / (1u64 << 53) as f64)
+}
+fn unit_u32(bits: u32) -> f64 {
+    bits as f64 * (1.0 / (1u64 << 32) as f64)
+}
+
+/// Independent uniform numbers for every dimension, like the random generator. `render` uses the
+/// generator directly for these, which is faster and keeps the images it made before samplers
+#[derive(Debug, Clone, Copy)]
+pub struct Independent {
+    seed: u64,
+}
+impl Independent {
+    pub fn new(seed: u64) -> Self {
+        Self { seed }
+    }
+}
+impl Sampler for Independent {
+    fn sample(&self, pixel: [u32; 2], index: u32, dim: u32) -> f64 {
+        unit_u64(hash(self.seed, pixel, dim, index))
+    }
+}
+
+/// Splits every dimension into one stratum per sample, and puts each sample in a random place of
+/// a different stratum. The order of the strata is shuffled for every dimension, so dimensions
+/// aren't correlated with each other
+#[derive(Debug, Clone, Copy)]
+pub struct Stratified {
+    seed: u64,
+    samples: u32,
+}
+impl Stratified {
+    /// `samples` is the number of samples of every pixel
+    pub fn new(seed: u64, samples: u32) -> Self {
+        Self {
+            seed,
+            samples: samples.max(1),
+        }
+    }
+}
+impl Sampler for Stratified {
+    fn sample(&self, pixel: [u32; 2], index: u32, dim: u32) -> f64 {
+        let order = hash(self.seed, pixel, dim, u32::MAX) as u32;
+        let stratum = permute(index % self.samples, self.samples, order);
+        let jitter = unit_u64(hash(self.seed, pixel, dim, index));
+        (stratum as f64 + jitter) / self.samples as f64
+    }
+}
+
+/// Element `i` of a random permutation of `0..len` picked by `seed`, without building it
+/// (Kensler, Correlated Multi-Jittered Sampling)
+fn permute(mut i: u32, len: u32, seed: u32) -> u32 {
+    let mut w = len - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+    loop {
+        i ^= seed;
+        i = i.wrapping_mul(0xe170_893d);
+        i ^= seed >> 16;
+        i ^= (i & w) >> 4;
+        i ^= seed >> 8;
+        i = i.wrapping_mul(0x0929_eb3f);
+        i ^= seed >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | seed >> 27);
+        i = i.wrapping_mul(0x6935_fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dc_b303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e50_1cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860_a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < len {
+            return (i.wrapping_add(seed)) % len;
+        }
+    }
+}
+
+const PRIMES: [u32; 64] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131, 137, 139, 149, 151, 157, 163, 167, 173, 179, 181, 191, 193,
+    197, 199, 211, 223, 227, 229, 233, 239, 241, 251, 257, 263, 269, 271, 277, 281, 283, 293, 307,
+    311,
+];
+
+/// Digits of `i` in `base`, mirrored around the decimal point, with every digit changed by a
+/// random permutation for its position. The digits past those of the largest index are replaced by
+/// a random number
+fn scrambled_radical_inverse(base: u32, mut i: u32, seed: u64) -> f64 {
+    let inv_base = 1.0 / base as f64;
+    let (mut result, mut factor) = (0.0, inv_base);
+    // Leading zeros are permuted too, up to as many digits as any index can have, so all indices
+    // share the same permutation of every digit
+    let (mut position, mut indices) = (0, 1u64);
+    while indices <= u32::MAX as u64 {
+        let digit = permute(i % base, base, mix(seed ^ position) as u32);
+        result += digit as f64 * factor;
+        i /= base;
+        factor *= inv_base;
+        position += 1;
+        indices *= base as u64;
+    }
+    let tail = unit_u64(mix(seed ^ position)) * factor * base as f64;
+    (result + tail).min(1.0 - f64::EPSILON / 2.0)
+}
+
+/// The Halton sequence, with a different prime as the base of every dimension, and the digits
+/// scrambled for every pixel (Which also keeps dimensions with large bases from being
+/// correlated). Dimensions past the 64th prime are independent
+#[derive(Debug, Clone, Copy)]
+pub struct Halton {
+    seed: u64,
+}
+impl Halton {
+    pub fn new(seed: u64) -> Self {
+        Self { seed }
+    }
+}
+impl Sampler for Halton {
+    fn sample(&self, pixel: [u32; 2], index: u32, dim: u32) -> f64 {
+        match PRIMES.get(dim as usize) {
+            Some(&base) => {
+                let seed = hash(self.seed, pixel, dim, u32::MAX);
+                scrambled_radical_inverse(base, index, seed)
+            }
+            None => unit_u64(hash(self.seed, pixel, dim, index)),
+        }
+    }
+}
+
+/// Direction numbers of the first 4 dimensions of the Sobol sequence. The first dimension is the
+/// van der Corput sequence, and the rest use the primitive polynomials and initial numbers of Joe
+/// and Kuo
+static SOBOL_DIRECTIONS: [[u32; 32]; 4] = sobol_directions();
+const fn sobol_directions() -> [[u32; 32]; 4] {
+    // Degree, coefficients and initial numbers
+    let polynomials: [(usize, u32, [u32; 3]); 3] =
+        [(1, 0, [1, 0, 0]), (2, 1, [1, 3, 0]), (3, 1, [1, 3, 1])];
+    let mut directions = [[0; 32]; 4];
+    let mut i = 0;
+    while i < 32 {
+        directions[0][i] = 1 << (31 - i);
+        i += 1;
+    }
+    let mut dim = 1;
+    while dim < 4 {
+        let (degree, coefficients, initial) = polynomials[dim - 1];
+        let v = &mut directions[dim];
+        let mut i = 0;
+        while i < 32 {
+            v[i] = if i < degree {
+                initial[i] << (31 - i)
+            } else {
+                let mut x = v[i - degree] ^ (v[i - degree] >> degree);
+                let mut k = 1;
+                while k < degree {
+                    x ^= ((coefficients >> (degree - 1 - k)) & 1) * v[i - k];
+                    k += 1;
+                }
+                x
+            };
+            i += 1;
+        }
+        dim += 1;
+    }
+    directions
+}
+fn sobol(index: u32, dim: usize) -> u32 {
+    // Scrambled indices use all of the bits, so this doesn't branch on them
+    let mut x = 0;
+    for (bit, direction) in SOBOL_DIRECTIONS[dim].iter().enumerate() {
+        x ^= direction & 0u32.wrapping_sub((index >> bit) & 1);
+    }
+    x
+}
+
+/// Owen scrambling of the bits of `x` as a fraction: every bit is flipped or not depending on the
+/// ones before it. Uses the hash of Laine and Karras on the reversed bits (Burley, Practical
+/// Hash-based Owen Scrambling)
+fn owen_scramble(x: u32, seed: u32) -> u32 {
+    let mut x = x.reverse_bits();
+    x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50_b47c);
+    x ^= x.wrapping_mul(0xb82f_1e52);
+    x ^= x.wrapping_mul(0xc7af_e638);
+    x ^= x.wrapping_mul(0x8d22_f6e6);
+    x.reverse_bits()
+}
+
+/// Dimension `dim` of point `index` of a scrambled Sobol sequence. Dimensions come in groups of
+/// 4, each with its own scrambling and order of the points, so there's no limit on them
+fn owen_sobol(seed: u64, index: u32, dim: u32) -> u32 {
+    let group_seed = mix(seed ^ (dim / 4) as u64);
+    let index = owen_scramble(index, group_seed as u32);
+    let dim = (dim % 4) as usize;
+    owen_scramble(sobol(index, dim), mix(group_seed ^ (dim as u64 + 1)) as u32)
+}
+
+/// The Sobol sequence with Owen scrambling, which is different for every pixel. The first 2^n
+/// samples of a pixel are spread evenly over every pair of dimensions of a group of 4
+#[derive(Debug, Clone, Copy)]
+pub struct Sobol {
+    seed: u64,
+}
+impl Sobol {
+    pub fn new(seed: u64) -> Self {
+        Self { seed }
+    }
+}
+impl Sampler for Sobol {
+    fn sample(&self, pixel: [u32; 2], index: u32, dim: u32) -> f64 {
+        unit_u32(owen_sobol(hash(self.seed, pixel, 0, 0), index, dim))
+    }
+}
+
+/// Width and height of the blue noise tile
+pub const BLUE_NOISE_SIZE: usize = 32;
+
+/// Ranks (`0..BLUE_NOISE_SIZE²`, row by row) of a tile of blue noise made with void and cluster
+/// (Ulichney), which tiles without seams. Pixels close to each other have very different ranks
+pub fn blue_noise() -> &'static [u16] {
+    static TILE: OnceLock<Vec<u16>> = OnceLock::new();
+    TILE.get_or_init(void_and_cluster)
+}
+fn void_and_cluster() -> Vec<u16> {
+    const SIZE: usize = BLUE_NOISE_SIZE;
+    const N: usize = SIZE * SIZE;
+    let sigma = 1.5;
+    // Energy a point adds at each offset from it, wrapping around the tile
+    let kernel = (0..N)
+        .map(|i| {
+            let (x, y) = (i % SIZE, i / SIZE);
+            let (dx, dy) = (x.min(SIZE - x) as f64, y.min(SIZE - y) as f64);
+            (-(dx * dx + dy * dy) / (2.0 * sigma * sigma)).exp()
+        })
+        .collect::<Vec<_>>();
+    let set = |points: &mut [bool], energy: &mut [f64], p: usize, on: bool| {
+        points[p] = on;
+        let sign = if on { 1.0 } else { -1.0 };
+        for (i, energy) in energy.iter_mut().enumerate() {
+            let dx = (i % SIZE + SIZE - p % SIZE) % SIZE;
+            let dy = (i / SIZE + SIZE - p / SIZE) % SIZE;
+            *energy += sign * kernel[dy * SIZE + dx];
+        }
+    };
+    // The point with the most points around it, and the empty pixel with the fewest
+    let tightest_cluster = |points: &[bool], energy: &[f64]| {
+        (0..N)
+            .filter(|&i| points[i])
+            .max_by(|&a, &b| energy[a].total_cmp(&energy[b]))
+            .unwrap()
+    };
+    let largest_void = |points: &[bool], energy: &[f64]| {
+        (0..N)
+            .filter(|&i| !points[i])
+            .min_by(|&a, &b| energy[a].total_cmp(&energy[b]))
+            .unwrap()
+    };
+
+    // Start with a tenth of the pixels at random, and move points from clusters into voids until
+    // that changes nothing
+    let (mut points, mut energy) = (vec![false; N], vec![0.0; N]);
+    let initial = N / 10;
+    let mut state = 0x5EED;
+    let mut count = 0;
+    while count < initial {
+        state = mix(state);
+        let p = (state % N as u64) as usize;
+        if !points[p] {
+            set(&mut points, &mut energy, p, true);
+            count += 1;
+        }
+    }
+    for _ in 0..N {
+        let cluster = tightest_cluster(&points, &energy);
+        set(&mut points, &mut energy, cluster, false);
+        let void = largest_void(&points, &energy);
+        set(&mut points, &mut energy, void, true);
+        if void == cluster {
+            break;
+        }
+    }
+
+    let mut ranks = vec![0; N];
+    // The initial points are ranked by taking away the tightest cluster each time, and the rest
+    // by filling the largest void
+    let (mut removed, mut removed_energy) = (points.clone(), energy.clone());
+    for rank in (0..initial).rev() {
+        let cluster = tightest_cluster(&removed, &removed_energy);
+        set(&mut removed, &mut removed_energy, cluster, false);
+        ranks[cluster] = rank as u16;
+    }
+    for rank in initial..N {
+        let void = largest_void(&points, &energy);
+        set(&mut points, &mut energy, void, true);
+        ranks[void] = rank as u16;
+    }
+    ranks
+}
+
+/// Scrambled Sobol points shared by every pixel, shifted (Wrapping around) by blue noise, with
+/// the tile moved around for every dimension. What's left of the error looks like blue noise,
+/// which is much less visible than white noise at low sample counts (Georgiev and Fajardo)
+#[derive(Debug, Clone, Copy)]
+pub struct BlueNoise {
+    seed: u64,
+}
+impl BlueNoise {
+    pub fn new(seed: u64) -> Self {
+        Self { seed }
+    }
+}
+impl Sampler for BlueNoise {
+    fn sample(&self, pixel: [u32; 2], index: u32, dim: u32) -> f64 {
+        let offset = hash(self.seed, [0, 0], dim, u32::MAX);
+        let size = BLUE_NOISE_SIZE as u64;
+        let x = (pixel[0] as u64 + offset % size) % size;
+        let y = (pixel[1] as u64 + (offset >> 32) % size) % size;
+        let rank = blue_noise()[(y * size + x) as usize];
+        let shift = rank as f64 / (size * size) as f64;
+        let x = unit_u32(owen_sobol(self.seed, index, dim)) + shift;
+        if x >= 1.0 {
+            x - 1.0
+        } else {
+            x
+        }
+    }
+}
+
+/// Which sampler to render with
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SamplerKind {
+    Independent,
+    Stratified,
+    Halton,
+    Sobol,
+    BlueNoise,
+}
+impl SamplerKind {
+    pub const ALL: [SamplerKind; 5] = [
+        SamplerKind::Independent,
+        SamplerKind::Stratified,
+        SamplerKind::Halton,
+        SamplerKind::Sobol,
+        SamplerKind::BlueNoise,
+    ];
+    /// Name in scene files and on the command line
+    pub fn name(self) -> &'static str {
+        match self {
+            SamplerKind::Independent => "independent",
+            SamplerKind::Stratified => "stratified",
+            SamplerKind::Halton => "halton",
+            SamplerKind::Sobol => "sobol",
+            SamplerKind::BlueNoise => "blue_noise",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<SamplerKind> {
+        Self::ALL.iter().copied().find(|kind| kind.name() == name)
+    }
+    /// `samples` is the number of samples of every pixel
+    pub fn build(self, seed: u64, samples: u32) -> EnumSampler {
+        match self {
+            SamplerKind::Independent => EnumSampler::Independent(Independent::new(seed)),
+            SamplerKind::Stratified => EnumSampler::Stratified(Stratified::new(seed, samples)),
+            SamplerKind::Halton => EnumSampler::Halton(Halton::new(seed)),
+            SamplerKind::Sobol => EnumSampler::Sobol(Sobol::new(seed)),
+            SamplerKind::BlueNoise => EnumSampler::BlueNoise(BlueNoise::new(seed)),
+        }
+    }
+}
+
+/// Every sampler, so `RandState` can hold one without boxing it
+#[derive(Debug, Clone, Copy)]
+pub enum EnumSampler {
+    Independent(Independent),
+    Stratified(Stratified),
+    Halton(Halton),
+    Sobol(Sobol),
+    BlueNoise(BlueNoise),
+}
+impl Sampler for EnumSampler {
+    fn sample(&self, pixel: [u32; 2], index: u32, dim: u32) -> f64 {
+        match self {
+            EnumSampler::Independent(sampler) => sampler.sample(pixel, index, dim),
+            EnumSampler::Stratified(sampler) => sampler.sample(pixel, index, dim),
+            EnumSampler::Halton(sampler) => sampler.sample(pixel, index, dim),
+            EnumSampler::Sobol(sampler) => sampler.sample(pixel, index, dim),
+            EnumSampler::BlueNoise(sampler) => sampler.sample(pixel, index, dim),
+        }
+    }
+}
+
+/// The sample a `RandState` takes its numbers from, and which of its dimensions come next
+#[derive(Debug, Clone)]
+struct Dimensions {
+    sampler: EnumSampler,
+    pixel: [u32; 2],
+    index: u32,
+    next: u32,
+    end: u32,
+}
+
+/// Source of uniform random numbers, from the random generator, or one dimension of a sample at
+/// a time after `use_sampler`
+#[cfg_attr(
+    not(all(feature = "wincrypt_rand", target_os = "windows")),
+    derive(Debug, Clone)
+)]
+pub struct RandState {
+    generator: Generator,
+    dimensions: Option<Dimensions>,
+}
+impl Default for RandState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl RandState {
+    pub fn new() -> Self {
+        Self {
+            generator: Generator::new(),
+            dimensions: None,
+        }
+    }
+    pub fn from_seed(seed: u64) -> Self {
+        Self {
+            generator: Generator::from_seed(seed),
+            dimensions: None,
+        }
+    }
+
+    /// Takes the next numbers from sample `index` of `pixel`, starting at its first dimension
+    pub fn use_sampler(&mut self, sampler: EnumSampler, pixel: [u32; 2], index: u32) {
+        self.dimensions = Some(Dimensions {
+            sampler,
+            pixel,
+            index,
+            next: 0,
+            end: u32::MAX,
+        });
+    }
+    /// The next numbers come from dimensions `start..start + count` of the sample, and then from
+    /// the generator, so every part of a path always uses the same dimensions. Does nothing
+    /// without a sampler
+    pub fn start_dimensions(&mut self, start: u32, count: u32) {
+        if let Some(dimensions) = &mut self.dimensions {
+            dimensions.next = start;
+            dimensions.end = start.saturating_add(count);
+        }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.generator.next_u64()
+    }
+    /// Uniform in [0, 1)
+    pub fn random_double(&mut self) -> f64 {
+        if let Some(dimensions) = &mut self.dimensions {
+            if dimensions.next < dimensions.end {
+                let dim = dimensions.next;
+                dimensions.next += 1;
+                return dimensions
+                    .sampler
+                    .sample(dimensions.pixel, dimensions.index, dim);
+            }
+        }
+        self.generator.random_double()
+    }
+    pub fn random_double_range(&mut self, min: f64, max: f64) -> f64 {
+        min + (max - min) * self.random_double()
+    }
+}
@@ -0,0 +1,1169 @@
+//! Text scene descriptions
+//!
+//! A scene file has one statement per line, and `#` starts a comment:
+//!
+//! ```text
+//! camera lookfrom 13 2 3 lookat 0 0 0 up 0 1 0 fov 20 aperture 0.1 focus 10
+//! render width 1200 height 800 samples 200 depth 50
+//! background gradient 1 1 1 0.5 0.7 1
+//! texture checker uv_checker 1 0.2 0.3 0.1 0.9 0.9 0.9
+//! material ground lambertian checker
+//! material gold metal 0.8 0.6 0.2 0.1
+//! material glass dielectric 1.5
+//! plane 0 0 0 0 1 0 ground
+//! sphere 0 1 0 1 glass
+//! moving_sphere 4 1 0 0  4 1.5 0 1  0.5 gold
+//! triangle -1 0 -2 1 0 -2 0 1 -2 gold
+//! mesh teapot.obj
+//! object pair sphere 0 0.5 0 0.5 gold
+//! object pair sphere 1 0.5 0 0.5 glass
+//! instance pair translate 2 0 3 rotate y 45
+//! ```
+//!
+//! - `camera` takes any of `lookfrom x y z`, `lookat x y z`, `up x y z`, `fov degrees`,
+//!   `aspect ratio`, `aperture a`, `focus distance` and `shutter open close` (The times when the
+//!   shutter opens and closes, for motion blur). By default the aspect ratio is the one of the
+//!   image
+//! - `render` takes any of `width`, `height`, `samples`, `depth`, `roulette depth probability`
+//!   (Bounces before Russian roulette can end a ray, and the highest chance of going on after
+//!   them, off by default) and `adaptive min noise` (Adaptive sampling, where pixels stop after
+//!   `min` samples once their noise is below `noise`, and `samples` is the most they take),
+//!   `sampler name` (`independent`, `stratified`, `halton`, `sobol` or `blue_noise`) and
+//!   `tiles size order` (`scanline`, `spiral` or `hilbert`). The width or the height can be left
+//!   out if the camera has an aspect ratio
+//! - `background` is `sky`, `black`, `color r g b` or `gradient r g b r g b` (Bottom color, then top
+//!   color). With a black background only `light` materials light the scene
+//! - `texture name` is followed by `solid r g b`, `checker size even odd` (A 3D checker, where
+//!   `even` and `odd` are textures), `uv_checker size even odd` (A checker in uv coordinates),
+//!   `noise scale [seed]`, `turbulence scale [seed]`, `marble scale [seed]` or `image path` (A PPM
+//!   or PNG, relative to the scene file). Anywhere a texture is expected, a color `r g b` can be
+//!   used instead
+//! - `material name` is followed by `lambertian texture`, `metal texture fuzzyness`,
+//!   `dielectric refraction_index`, `light r g b` (Emitted light, which can be above 1) or
+//!   `isotropic texture` (For media). Materials and textures have to be defined before they are
+//!   used
+//! - `sphere` takes a center, a radius and a material. `moving_sphere` takes the center and the time
+//!   it's there (`x y z time`) twice, then a radius and a material
+//! - `quad` takes a corner and the two sides from it (`x y z` each), then a material. It faces the
+//!   side of the cross product of the sides
+//! - `disk` takes a center, a normal, a radius and a material
+//! - Spheres, quads and disks made of a `light` material are also sampled directly when rendering,
+//!   unless they are part of an `object`
+//! - `plane` takes a point, a normal and a material. Planes are infinite, and their uv coordinates
+//!   are distances along the plane
+//! - `box` takes two opposite corners and a material, and adds the six quads of the box
+//! - `mesh` loads an .obj file, relative to the scene file
+//! - `triangle_mesh` takes `positions n` followed by n positions, `normals n` followed by n
+//!   normals, `uvs n` followed by n `u v` pairs and `indices n` followed by n triangles (3 indices
+//!   into the positions each), then a material. The normals and the uvs can be left empty with a
+//!   count of 0. This is how written scenes store meshes
+//! - `object name` followed by a statement that adds objects (Like `sphere`, `box`, `mesh` or
+//!   `instance`) defines an object (Or adds to it) without placing it in the world. `instance name`
+//!   places a copy of it, transformed by any number of `translate x y z`, `rotate x|y|z degrees`,
+//!   `scale x y z` and `matrix` (The first 3 rows of an affine matrix, row by row) steps, in order.
+//!   Instances can also be part of objects, and objects can't change once they have been instanced
+//! - `medium name density material` fills the object `name` (Which has to be closed, and can't change
+//!   afterwards like with instances) with smoke or fog of that density, usually with an `isotropic`
+//!   material
+//! - `grid name` is followed by `vol path` (A Mitsuba .vol file with one channel), `raw path nx ny nz`
+//!   (8-bit densities, x changing fastest) or `noise resolution scale [seed]` (A cloud made of
+//!   Perlin noise), and defines a density grid. `grid_medium grid x0 y0 z0 x1 y1 z1 density
+//!   material` stretches it over a box, multiplied by `density`
+use super::{
+    material::{Dielectric, DiffuseLight, Isotropic, LambertianDiffuse, MaterialType, Metal},
+    obj,
+    planar::{self, Disk, Plane, Quad},
+    sampler::SamplerKind,
+    texture::{Checker, ImageTexture, NoiseKind, NoiseTexture, SolidColor, TextureType, UvChecker},
+    tiles::TileOrder,
+    transform::{self, Matrix4, SharedHittable, Transform},
+    triangle::TriangleMesh,
+    volume::{ConstantMedium, DensityGrid, GridMedium},
+    Aabb, Background, Camera, Hittable, HittableList, MovingSphere, RenderParams, Sphere, Triangle,
+    Vec3,
+};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// The parameters of a Camera
+#[derive(Debug, Clone, PartialEq)]
+pub struct CameraDesc {
+    pub lookfrom: Vec3,
+    pub lookat: Vec3,
+    pub view_up: Vec3,
+    pub vertical_fov_degrees: f64,
+    pub aspect_ratio: f64,
+    pub aperture: f64,
+    pub focus_dist: f64,
+    pub shutter_open: f64,
+    pub shutter_close: f64,
+}
+impl Default for CameraDesc {
+    fn default() -> Self {
+        Self {
+            lookfrom: Vec3::new(13.0, 2.0, 3.0),
+            lookat: Vec3::new(0.0, 0.0, 0.0),
+            view_up: Vec3::new(0.0, 1.0, 0.0),
+            vertical_fov_degrees: 20.0,
+            aspect_ratio: 3.0 / 2.0,
+            aperture: 0.0,
+            focus_dist: 1.0,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+        }
+    }
+}
+impl CameraDesc {
+    pub fn build(&self) -> Camera {
+        Camera::new(
+            self.lookfrom,
+            self.lookat,
+            self.view_up,
+            self.vertical_fov_degrees,
+            self.aspect_ratio,
+            self.aperture,
+            self.focus_dist,
+        )
+        .with_shutter(self.shutter_open, self.shutter_close)
+    }
+}
+
+/// The parameters of a texture, used to write scenes
+#[derive(Debug, Clone, PartialEq)]
+pub enum TextureDesc {
+    Solid {
+        color: Vec3,
+    },
+    Checker {
+        size: f64,
+        even: Box<TextureDesc>,
+        odd: Box<TextureDesc>,
+    },
+    UvChecker {
+        size: f64,
+        even: Box<TextureDesc>,
+        odd: Box<TextureDesc>,
+    },
+    Noise {
+        kind: NoiseKind,
+        scale: f64,
+        seed: u64,
+    },
+    Image {
+        path: PathBuf,
+    },
+}
+
+/// The parameters of a material, used to write scenes
+#[derive(Debug, Clone, PartialEq)]
+pub enum MaterialDesc {
+    Lambertian { albedo: TextureDesc },
+    Metal { albedo: TextureDesc, fuzzyness: f64 },
+    Dielectric { refraction_idx: f64 },
+    DiffuseLight { emit: Vec3 },
+    Isotropic { albedo: TextureDesc },
+}
+
+/// Where the values of a density grid come from, used to write scenes
+#[derive(Debug, Clone, PartialEq)]
+pub enum GridDesc {
+    Vol {
+        path: PathBuf,
+    },
+    Raw {
+        path: PathBuf,
+        size: [usize; 3],
+    },
+    Noise {
+        resolution: usize,
+        scale: f64,
+        seed: u64,
+    },
+}
+
+/// The parameters of an object, used to write scenes
+#[derive(Debug, Clone, PartialEq)]
+pub enum ObjectDesc {
+    Sphere {
+        center: Vec3,
+        radius: f64,
+        material: MaterialDesc,
+    },
+    MovingSphere {
+        centers: [Vec3; 2],
+        times: [f64; 2],
+        radius: f64,
+        material: MaterialDesc,
+    },
+    Triangle {
+        vertices: [Vec3; 3],
+        material: MaterialDesc,
+    },
+    /// An indexed triangle mesh, with its vertex data
+    Mesh {
+        positions: Vec<Vec3>,
+        normals: Vec<Vec3>,
+        uvs: Vec<[f64; 2]>,
+        indices: Vec<[usize; 3]>,
+        material: MaterialDesc,
+    },
+    Quad {
+        q: Vec3,
+        u: Vec3,
+        v: Vec3,
+        material: MaterialDesc,
+    },
+    Disk {
+        center: Vec3,
+        normal: Vec3,
+        radius: f64,
+        material: MaterialDesc,
+    },
+    Plane {
+        point: Vec3,
+        normal: Vec3,
+        material: MaterialDesc,
+    },
+    /// A constant density volume inside a boundary object
+    Medium {
+        boundary: Box<ObjectDesc>,
+        density: f64,
+        material: MaterialDesc,
+    },
+    /// A volume with the densities of a grid, stretched between two corners
+    GridMedium {
+        grid: GridDesc,
+        min: Vec3,
+        max: Vec3,
+        density: f64,
+        material: MaterialDesc,
+    },
+    /// A transformed object, which can be shared with other instances
+    Instance {
+        object: Box<ObjectDesc>,
+        matrix: Matrix4,
+    },
+    /// Objects in a list
+    Group(Vec<ObjectDesc>),
+}
+
+pub struct Scene {
+    pub camera: CameraDesc,
+    pub params: RenderParams,
+    pub world: HittableList,
+    /// Copies of the lights in the world, to sample them when rendering
+    pub lights: HittableList,
+}
+
+#[derive(Debug)]
+pub struct SceneError {
+    pub file: String,
+    /// 1-based line number. 0 if the error is not about a specific line
+    pub line: usize,
+    pub message: String,
+}
+impl std::fmt::Display for SceneError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.line == 0 {
+            write!(f, "{}: {}", self.file, self.message)
+        } else {
+            write!(f, "{}:{}: {}", self.file, self.line, self.message)
+        }
+    }
+}
+impl std::error::Error for SceneError {}
+
+/// Loads a scene file. Meshes are loaded relative to its directory
+pub fn load_scene(path: impl AsRef<Path>) -> Result<Scene, SceneError> {
+    let path = path.as_ref();
+    let file = path.display().to_string();
+    let source = std::fs::read_to_string(path).map_err(|err| SceneError {
+        file: file.clone(),
+        line: 0,
+        message: err.to_string(),
+    })?;
+    parse_scene(
+        &file,
+        &source,
+        path.parent().unwrap_or_else(|| Path::new("")),
+    )
+}
+
+/// Whether objects made of the material are sampled as lights
+fn is_light(material: &MaterialType) -> bool {
+    matches!(material.describe(), Some(MaterialDesc::DiffuseLight { .. }))
+}
+
+/// Words of a statement, with helpers to parse them
+struct Args<'a> {
+    words: std::str::SplitWhitespace<'a>,
+}
+impl<'a> Args<'a> {
+    fn word(&mut self, what: &str) -> Result<&'a str, String> {
+        self.words
+            .next()
+            .ok_or_else(|| format!("expected {}", what))
+    }
+    fn number<T: std::str::FromStr>(&mut self, what: &str) -> Result<T, String> {
+        let word = self.word(what)?;
+        word.parse()
+            .map_err(|_| format!("expected {}, got '{}'", what, word))
+    }
+    fn vec3(&mut self, what: &str) -> Result<Vec3, String> {
+        Ok(Vec3::new(
+            self.number(what)?,
+            self.number(what)?,
+            self.number(what)?,
+        ))
+    }
+    /// `keyword`, a count, and that many items
+    fn list<T>(
+        &mut self,
+        keyword: &str,
+        mut item: impl FnMut(&mut Self) -> Result<T, String>,
+    ) -> Result<Vec<T>, String> {
+        let word = self.word(&format!("'{}'", keyword))?;
+        if word != keyword {
+            return Err(format!("expected '{}', got '{}'", keyword, word));
+        }
+        let count: usize = self.number("a count")?;
+        (0..count).map(|_| item(self)).collect()
+    }
+    fn end(&mut self) -> Result<(), String> {
+        match self.words.next() {
+            Some(word) => Err(format!("unexpected '{}'", word)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Everything parsed so far
+struct SceneBuilder<'a> {
+    dir: &'a Path,
+    camera: CameraDesc,
+    aspect_ratio: Option<f64>,
+    params: RenderParams,
+    width: Option<i64>,
+    height: Option<i64>,
+    textures: HashMap<String, TextureType>,
+    materials: HashMap<String, MaterialType>,
+    grids: HashMap<String, Arc<DensityGrid>>,
+    /// Objects that haven't been instanced yet
+    objects: HashMap<String, HittableList>,
+    shared: HashMap<String, SharedHittable>,
+    world: HittableList,
+    lights: HittableList,
+}
+impl<'a> SceneBuilder<'a> {
+    /// A texture name, or a color
+    fn texture(&self, args: &mut Args) -> Result<TextureType, String> {
+        let word = args.word("a color or a texture name")?;
+        if let Ok(r) = word.parse() {
+            return Ok(SolidColor::new(Vec3::new(
+                r,
+                args.number("a color")?,
+                args.number("a color")?,
+            )));
+        }
+        match self.textures.get(word) {
+            Some(texture) => Ok(texture.clone()),
+            None => Err(format!("unknown texture '{}'", word)),
+        }
+    }
+    fn material(&self, args: &mut Args) -> Result<MaterialType, String> {
+        let name = args.word("a material name")?;
+        match self.materials.get(name) {
+            Some(material) => Ok(material.clone()),
+            None => Err(format!("unknown material '{}'", name)),
+        }
+    }
+
+    /// A defined object, which can't change anymore once it's shared
+    fn shared(&mut self, name: &str) -> Result<SharedHittable, String> {
+        if let Some(object) = self.shared.get(name) {
+            return Ok(object.clone());
+        }
+        let object = self
+            .objects
+            .remove(name)
+            .ok_or_else(|| format!("unknown object '{}'", name))?;
+        let object = transform::share(object);
+        self.shared.insert(name.to_string(), object.clone());
+        Ok(object)
+    }
+    /// Adds the objects of a statement to the list, and the spheres, quads and disks made of a light
+    /// material to `lights` too. Returns false if it's not an object statement
+    fn object(
+        &mut self,
+        keyword: &str,
+        args: &mut Args,
+        list: &mut HittableList,
+        lights: Option<&mut HittableList>,
+    ) -> Result<bool, String> {
+        match keyword {
+            "sphere" => {
+                let center = args.vec3("a position")?;
+                let radius = args.number("a radius")?;
+                let material = self.material(args)?;
+                if let Some(lights) = lights.filter(|_| is_light(&material)) {
+                    lights.add(Sphere::new(center, radius, material.clone()));
+                }
+                list.add(Sphere::new(center, radius, material));
+            }
+            "moving_sphere" => {
+                let center0 = args.vec3("a position")?;
+                let time0 = args.number("a time")?;
+                let center1 = args.vec3("a position")?;
+                let time1 = args.number("a time")?;
+                let radius = args.number("a radius")?;
+                let material = self.material(args)?;
+                list.add(MovingSphere::new(
+                    (center0, time0),
+                    (center1, time1),
+                    radius,
+                    material,
+                ));
+            }
+            "triangle" => {
+                let v0 = args.vec3("a position")?;
+                let v1 = args.vec3("a position")?;
+                let v2 = args.vec3("a position")?;
+                let material = self.material(args)?;
+                list.add(Triangle::new(v0, v1, v2, material));
+            }
+            "triangle_mesh" => {
+                let positions = args.list("positions", |args| args.vec3("a position"))?;
+                let normals = args.list("normals", |args| args.vec3("a direction"))?;
+                let uvs = args.list("uvs", |args| {
+                    Ok([args.number("a uv")?, args.number("a uv")?])
+                })?;
+                let indices = args.list("indices", |args| {
+                    Ok([
+                        args.number("an index")?,
+                        args.number("an index")?,
+                        args.number("an index")?,
+                    ])
+                })?;
+                let material = self.material(args)?;
+                list.add(TriangleMesh::new(
+                    positions, normals, uvs, indices, material,
+                )?);
+            }
+            "quad" => {
+                let q = args.vec3("a position")?;
+                let u = args.vec3("a direction")?;
+                let v = args.vec3("a direction")?;
+                let material = self.material(args)?;
+                if let Some(lights) = lights.filter(|_| is_light(&material)) {
+                    lights.add(Quad::new(q, u, v, material.clone()));
+                }
+                list.add(Quad::new(q, u, v, material));
+            }
+            "disk" => {
+                let center = args.vec3("a position")?;
+                let normal = args.vec3("a direction")?;
+                let radius = args.number("a radius")?;
+                let material = self.material(args)?;
+                if let Some(lights) = lights.filter(|_| is_light(&material)) {
+                    lights.add(Disk::new(center, normal, radius, material.clone()));
+                }
+                list.add(Disk::new(center, normal, radius, material));
+            }
+            "plane" => {
+                let point = args.vec3("a position")?;
+                let normal = args.vec3("a direction")?;
+                let material = self.material(args)?;
+                list.add(Plane::new(point, normal, material));
+            }
+            "box" => {
+                let a = args.vec3("a position")?;
+                let b = args.vec3("a position")?;
+                let material = self.material(args)?;
+                list.add(planar::cuboid(a, b, material));
+            }
+            "instance" => {
+                let object = self.shared(args.word("an object name")?)?;
+                // Every step is applied after the previous ones
+                let mut matrix = Matrix4::IDENTITY;
+                while let Some(step) = args.words.next() {
+                    let step = match step {
+                        "translate" => Matrix4::translation(args.vec3("an offset")?),
+                        "rotate" => {
+                            let axis = match args.word("an axis")? {
+                                "x" => Vec3::new(1.0, 0.0, 0.0),
+                                "y" => Vec3::new(0.0, 1.0, 0.0),
+                                "z" => Vec3::new(0.0, 0.0, 1.0),
+                                axis => return Err(format!("unknown axis '{}'", axis)),
+                            };
+                            Matrix4::rotation(axis, args.number("an angle")?)
+                        }
+                        "scale" => Matrix4::scaling(args.vec3("a scale")?),
+                        "matrix" => {
+                            let mut matrix = Matrix4::IDENTITY;
+                            for row in &mut matrix.0[..3] {
+                                for x in row.iter_mut() {
+                                    *x = args.number("a matrix element")?;
+                                }
+                            }
+                            matrix
+                        }
+                        step => return Err(format!("unknown transform '{}'", step)),
+                    };
+                    matrix = step * matrix;
+                }
+                if matrix.inverse().is_none() {
+                    return Err("the transform can't be inverted".to_string());
+                }
+                list.add(Transform::new(object, matrix));
+            }
+            "medium" => {
+                let boundary = self.shared(args.word("an object name")?)?;
+                let density: f64 = args.number("a density")?;
+                if density <= 0.0 {
+                    return Err("the density must be greater than 0".to_string());
+                }
+                let material = self.material(args)?;
+                list.add(ConstantMedium::new(boundary, density, material));
+            }
+            "grid_medium" => {
+                let name = args.word("a grid name")?;
+                let grid = match self.grids.get(name) {
+                    Some(grid) => grid.clone(),
+                    None => return Err(format!("unknown grid '{}'", name)),
+                };
+                let min = args.vec3("a position")?;
+                let max = args.vec3("a position")?;
+                if (0..3).any(|axis| min.0[axis] >= max.0[axis]) {
+                    return Err(
+                        "the first corner of a grid_medium has to be the lowest".to_string()
+                    );
+                }
+                let density = args.number("a density")?;
+                let material = self.material(args)?;
+                list.add(GridMedium::new(
+                    grid,
+                    Aabb::new(min, max),
+                    density,
+                    material,
+                ));
+            }
+            "mesh" => {
+                let path = args.word("a path")?;
+                let meshes = obj::load_obj(self.dir.join(path)).map_err(|err| err.to_string())?;
+                for mesh in meshes {
+                    list.add(mesh);
+                }
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    fn statement(&mut self, keyword: &str, args: &mut Args) -> Result<(), String> {
+        match keyword {
+            "camera" => {
+                while let Some(key) = args.words.next() {
+                    let camera = &mut self.camera;
+                    match key {
+                        "lookfrom" => camera.lookfrom = args.vec3("a position")?,
+                        "lookat" => camera.lookat = args.vec3("a position")?,
+                        "up" => camera.view_up = args.vec3("a direction")?,
+                        "fov" => camera.vertical_fov_degrees = args.number("an angle")?,
+                        "aspect" => self.aspect_ratio = Some(args.number("a ratio")?),
+                        "aperture" => camera.aperture = args.number("a number")?,
+                        "focus" => camera.focus_dist = args.number("a distance")?,
+                        "shutter" => {
+                            camera.shutter_open = args.number("a time")?;
+                            camera.shutter_close = args.number("a time")?;
+                        }
+                        _ => return Err(format!("unknown camera parameter '{}'", key)),
+                    }
+                }
+            }
+            "render" => {
+                while let Some(key) = args.words.next() {
+                    match key {
+                        "width" => self.width = Some(args.number("a width")?),
+                        "height" => self.height = Some(args.number("a height")?),
+                        "samples" => {
+                            self.params.samples_per_px = args.number("a sample count")?;
+                            if self.params.samples_per_px == 0 {
+                                return Err("the sample count must be greater than 0".to_string());
+                            }
+                        }
+                        "depth" => {
+                            self.params.max_depth = args.number("a depth")?;
+                            if self.params.max_depth <= 0 {
+                                return Err("the depth must be greater than 0".to_string());
+                            }
+                        }
+                        "roulette" => {
+                            let depth = args.number("a roulette depth")?;
+                            let probability = args.number("a probability")?;
+                            if depth < 0 {
+                                return Err("the roulette depth can't be negative".to_string());
+                            }
+                            if !(0.0 < probability && probability <= 1.0) {
+                                return Err(
+                                    "the roulette probability must be between 0 and 1".to_string()
+                                );
+                            }
+                            self.params.roulette_depth = Some(depth);
+                            self.params.roulette_probability = probability;
+                        }
+                        "adaptive" => {
+                            self.params.min_samples_per_px = args.number("a sample count")?;
+                            self.params.noise_threshold = args.number("a noise threshold")?;
+                            if self.params.noise_threshold < 0.0 {
+                                return Err("the noise threshold can't be negative".to_string());
+                            }
+                        }
+                        "sampler" => {
+                            let name = args.word("a sampler")?;
+                            self.params.sampler = SamplerKind::from_name(name)
+                                .ok_or_else(|| format!("unknown sampler '{}'", name))?;
+                        }
+                        "tiles" => {
+                            self.params.tile_size = args.number("a tile size")?;
+                            if self.params.tile_size == 0 {
+                                return Err("the tile size must be greater than 0".to_string());
+                            }
+                            let name = args.word("a tile order")?;
+                            self.params.tile_order = TileOrder::from_name(name)
+                                .ok_or_else(|| format!("unknown tile order '{}'", name))?;
+                        }
+                        _ => return Err(format!("unknown render parameter '{}'", key)),
+                    }
+                }
+            }
+            "background" => {
+                self.params.background = match args.word("a background kind")? {
+                    "sky" => Background::SKY,
+                    "black" => Background::BLACK,
+                    "color" => Background::Color(args.vec3("a color")?),
+                    "gradient" => Background::Gradient {
+                        bottom: args.vec3("a color")?,
+                        top: args.vec3("a color")?,
+                    },
+                    kind => return Err(format!("unknown background '{}'", kind)),
+                };
+            }
+            "texture" => {
+                let name = args.word("a texture name")?;
+                let noise = |args: &mut Args, kind| -> Result<TextureType, String> {
+                    let scale = args.number("a scale")?;
+                    let seed = match args.words.next() {
+                        Some(word) => word
+                            .parse()
+                            .map_err(|_| format!("expected a seed, got '{}'", word))?,
+                        None => 0,
+                    };
+                    Ok(NoiseTexture::new(kind, scale, seed))
+                };
+                let texture = match args.word("a texture kind")? {
+                    "solid" => SolidColor::new(args.vec3("a color")?),
+                    "checker" => {
+                        let size = args.number("a size")?;
+                        let even = self.texture(args)?;
+                        let odd = self.texture(args)?;
+                        Checker::new(size, even, odd)
+                    }
+                    "uv_checker" => {
+                        let size = args.number("a size")?;
+                        let even = self.texture(args)?;
+                        let odd = self.texture(args)?;
+                        UvChecker::new(size, even, odd)
+                    }
+                    "noise" => noise(args, NoiseKind::Smooth)?,
+                    "turbulence" => noise(args, NoiseKind::Turbulence)?,
+                    "marble" => noise(args, NoiseKind::Marble)?,
+                    "image" => {
+                        let path = args.word("a path")?;
+                        ImageTexture::load(self.dir.join(path))
+                            .map_err(|err| format!("could not load '{}': {}", path, err))?
+                    }
+                    kind => return Err(format!("unknown texture kind '{}'", kind)),
+                };
+                self.textures.insert(name.to_string(), texture);
+            }
+            "grid" => {
+                let name = args.word("a grid name")?;
+                let grid = match args.word("a grid kind")? {
+                    "vol" => {
+                        let path = args.word("a path")?;
+                        DensityGrid::load_vol(self.dir.join(path))
+                            .map_err(|err| format!("could not load '{}': {}", path, err))?
+                    }
+                    "raw" => {
+                        let path = args.word("a path")?;
+                        let mut size = [0; 3];
+                        for n in &mut size {
+                            *n = args.number("a grid size")?;
+                        }
+                        DensityGrid::load_raw(self.dir.join(path), size)
+                            .map_err(|err| format!("could not load '{}': {}", path, err))?
+                    }
+                    "noise" => {
+                        let resolution: usize = args.number("a resolution")?;
+                        if resolution == 0 {
+                            return Err("the resolution can't be 0".to_string());
+                        }
+                        let scale = args.number("a scale")?;
+                        let seed = match args.words.next() {
+                            Some(word) => word
+                                .parse()
+                                .map_err(|_| format!("expected a seed, got '{}'", word))?,
+                            None => 0,
+                        };
+                        DensityGrid::noise(resolution, scale, seed)
+                    }
+                    kind => return Err(format!("unknown grid kind '{}'", kind)),
+                };
+                self.grids.insert(name.to_string(), Arc::new(grid));
+            }
+            "material" => {
+                let name = args.word("a material name")?;
+                let material = match args.word("a material kind")? {
+                    "lambertian" => LambertianDiffuse::textured(self.texture(args)?),
+                    "metal" => {
+                        let albedo = self.texture(args)?;
+                        Metal::textured(albedo, args.number("a fuzzyness")?)
+                    }
+                    "dielectric" => Dielectric::new(args.number("a refraction index")?),
+                    "light" => DiffuseLight::new(args.vec3("a color")?),
+                    "isotropic" => Isotropic::textured(self.texture(args)?),
+                    kind => return Err(format!("unknown material kind '{}'", kind)),
+                };
+                self.materials.insert(name.to_string(), material);
+            }
+            "object" => {
+                let name = args.word("an object name")?;
+                if self.shared.contains_key(name) {
+                    return Err(format!(
+                        "object '{}' can't change after being instanced",
+                        name
+                    ));
+                }
+                let keyword = args.word("an object")?;
+                let mut object = self.objects.remove(name).unwrap_or_default();
+                if !self.object(keyword, args, &mut object, None)? {
+                    return Err(format!("unknown object kind '{}'", keyword));
+                }
+                self.objects.insert(name.to_string(), object);
+            }
+            keyword => {
+                let mut world = std::mem::take(&mut self.world);
+                let mut lights = std::mem::take(&mut self.lights);
+                let is_object = self.object(keyword, args, &mut world, Some(&mut lights));
+                self.world = world;
+                self.lights = lights;
+                if !is_object? {
+                    return Err(format!("unknown statement '{}'", keyword));
+                }
+            }
+        }
+        args.end()
+    }
+
+    fn finish(mut self) -> Result<Scene, String> {
+        let (width, height) = match (self.width, self.height, self.aspect_ratio) {
+            (Some(width), Some(height), _) => (width, height),
+            (Some(width), None, Some(aspect)) => (width, (width as f64 / aspect) as i64),
+            (None, Some(height), Some(aspect)) => ((height as f64 * aspect) as i64, height),
+            _ => {
+                return Err(
+                    "the render needs a width and a height, or one of them and a camera aspect"
+                        .to_string(),
+                )
+            }
+        };
+        if width < 2 || height < 2 {
+            return Err(format!("the image is too small ({}x{})", width, height));
+        }
+        self.params.image_width = width;
+        self.params.image_height = height;
+        self.camera.aspect_ratio = self.aspect_ratio.unwrap_or(width as f64 / height as f64);
+
+        Ok(Scene {
+            camera: self.camera,
+            params: self.params,
+            world: self.world,
+            lights: self.lights,
+        })
+    }
+}
+
+/// Parses the contents of a scene file. `file` is only used for error messages, and meshes are
+/// loaded relative to `dir`
+pub fn parse_scene(file: &str, source: &str, dir: &Path) -> Result<Scene, SceneError> {
+    let mut builder = SceneBuilder {
+        dir,
+        camera: CameraDesc::default(),
+        aspect_ratio: None,
+        params: RenderParams::default(),
+        width: None,
+        height: None,
+        textures: HashMap::new(),
+        materials: HashMap::new(),
+        grids: HashMap::new(),
+        objects: HashMap::new(),
+        shared: HashMap::new(),
+        world: HittableList::new(),
+        lights: HittableList::new(),
+    };
+
+    for (line_idx, line) in source.lines().enumerate() {
+        let line = match line.find('#') {
+            Some(comment_start) => &line[..comment_start],
+            None => line,
+        };
+        let mut args = Args {
+            words: line.split_whitespace(),
+        };
+        if let Some(keyword) = args.words.next() {
+            builder
+                .statement(keyword, &mut args)
+                .map_err(|message| SceneError {
+                    file: file.to_string(),
+                    line: line_idx + 1,
+                    message,
+                })?;
+        }
+    }
+
+    builder.finish().map_err(|message| SceneError {
+        file: file.to_string(),
+        line: 0,
+        message,
+    })
+}
+
+fn write_vec3(out: &mut String, v: Vec3) {
+    use std::fmt::Write;
+    write!(out, " {} {} {}", v.x(), v.y(), v.z()).unwrap();
+}
+
+/// Materials, textures and instanced objects that have been written, which are deduplicated by
+/// their parameters since we can't tell which objects shared them
+#[derive(Default)]
+struct Names {
+    materials: Vec<MaterialDesc>,
+    textures: Vec<TextureDesc>,
+    grids: Vec<GridDesc>,
+    objects: Vec<ObjectDesc>,
+}
+impl Names {
+    /// Writes the texture if it's new. Returns a color for solid textures, or the texture name
+    fn texture(&mut self, out: &mut String, desc: &TextureDesc) -> String {
+        use std::fmt::Write;
+
+        if let TextureDesc::Solid { color } = *desc {
+            let mut color_str = String::new();
+            write_vec3(&mut color_str, color);
+            return color_str.trim_start().to_string();
+        }
+        if let Some(idx) = self.textures.iter().position(|tex| tex == desc) {
+            return format!("t{}", idx);
+        }
+        let definition = match desc {
+            TextureDesc::Solid { .. } => unreachable!(),
+            TextureDesc::Checker { size, even, odd } => {
+                let even = self.texture(out, even);
+                let odd = self.texture(out, odd);
+                format!("checker {} {} {}", size, even, odd)
+            }
+            TextureDesc::UvChecker { size, even, odd } => {
+                let even = self.texture(out, even);
+                let odd = self.texture(out, odd);
+                format!("uv_checker {} {} {}", size, even, odd)
+            }
+            TextureDesc::Noise { kind, scale, seed } => {
+                let kind = match kind {
+                    NoiseKind::Smooth => "noise",
+                    NoiseKind::Turbulence => "turbulence",
+                    NoiseKind::Marble => "marble",
+                };
+                format!("{} {} {}", kind, scale, seed)
+            }
+            TextureDesc::Image { path } => format!("image {}", path.display()),
+        };
+        writeln!(out, "texture t{} {}", self.textures.len(), definition).unwrap();
+        self.textures.push(desc.clone());
+        format!("t{}", self.textures.len() - 1)
+    }
+    /// Writes the material if it's new, and returns its name
+    fn material(&mut self, out: &mut String, desc: &MaterialDesc) -> String {
+        use std::fmt::Write;
+
+        if let Some(idx) = self.materials.iter().position(|mat| mat == desc) {
+            return format!("m{}", idx);
+        }
+        let definition = match desc {
+            MaterialDesc::Lambertian { albedo } => {
+                format!("lambertian {}", self.texture(out, albedo))
+            }
+            MaterialDesc::Metal { albedo, fuzzyness } => {
+                format!("metal {} {}", self.texture(out, albedo), fuzzyness)
+            }
+            MaterialDesc::Dielectric { refraction_idx } => {
+                format!("dielectric {}", refraction_idx)
+            }
+            MaterialDesc::DiffuseLight { emit } => {
+                let mut definition = "light".to_string();
+                write_vec3(&mut definition, *emit);
+                definition
+            }
+            MaterialDesc::Isotropic { albedo } => {
+                format!("isotropic {}", self.texture(out, albedo))
+            }
+        };
+        writeln!(out, "material m{} {}", self.materials.len(), definition).unwrap();
+        self.materials.push(desc.clone());
+        format!("m{}", self.materials.len() - 1)
+    }
+    /// Writes the grid if it's new, and returns its name
+    fn grid(&mut self, out: &mut String, desc: &GridDesc) -> String {
+        use std::fmt::Write;
+
+        if let Some(idx) = self.grids.iter().position(|grid| grid == desc) {
+            return format!("g{}", idx);
+        }
+        let definition = match desc {
+            GridDesc::Vol { path } => format!("vol {}", path.display()),
+            GridDesc::Raw { path, size } => {
+                format!("raw {} {} {} {}", path.display(), size[0], size[1], size[2])
+            }
+            GridDesc::Noise {
+                resolution,
+                scale,
+                seed,
+            } => format!("noise {} {} {}", resolution, scale, seed),
+        };
+        writeln!(out, "grid g{} {}", self.grids.len(), definition).unwrap();
+        self.grids.push(desc.clone());
+        format!("g{}", self.grids.len() - 1)
+    }
+    /// Writes an object definition if it's new, and returns its name
+    fn object(&mut self, out: &mut String, desc: &ObjectDesc) -> String {
+        use std::fmt::Write;
+
+        if let Some(idx) = self.objects.iter().position(|obj| obj == desc) {
+            return format!("o{}", idx);
+        }
+        for statement in self.object_statements(out, desc) {
+            writeln!(out, "object o{} {}", self.objects.len(), statement).unwrap();
+        }
+        self.objects.push(desc.clone());
+        format!("o{}", self.objects.len() - 1)
+    }
+    /// The statements that add the object, after writing what they use
+    fn object_statements(&mut self, out: &mut String, desc: &ObjectDesc) -> Vec<String> {
+        use std::fmt::Write;
+
+        let mut statement = String::new();
+        match desc {
+            ObjectDesc::Sphere {
+                center,
+                radius,
+                material,
+            } => {
+                let name = self.material(out, material);
+                statement.push_str("sphere");
+                write_vec3(&mut statement, *center);
+                write!(statement, " {} {}", radius, name).unwrap();
+            }
+            ObjectDesc::MovingSphere {
+                centers,
+                times,
+                radius,
+                material,
+            } => {
+                let name = self.material(out, material);
+                statement.push_str("moving_sphere");
+                for (&center, time) in centers.iter().zip(times) {
+                    write_vec3(&mut statement, center);
+                    write!(statement, " {}", time).unwrap();
+                }
+                write!(statement, " {} {}", radius, name).unwrap();
+            }
+            ObjectDesc::Triangle { vertices, material } => {
+                let name = self.material(out, material);
+                statement.push_str("triangle");
+                for &v in vertices {
+                    write_vec3(&mut statement, v);
+                }
+                write!(statement, " {}", name).unwrap();
+            }
+            ObjectDesc::Mesh {
+                positions,
+                normals,
+                uvs,
+                indices,
+                material,
+            } => {
+                let name = self.material(out, material);
+                write!(statement, "triangle_mesh positions {}", positions.len()).unwrap();
+                for &p in positions {
+                    write_vec3(&mut statement, p);
+                }
+                write!(statement, " normals {}", normals.len()).unwrap();
+                for &n in normals {
+                    write_vec3(&mut statement, n);
+                }
+                write!(statement, " uvs {}", uvs.len()).unwrap();
+                for [u, v] in uvs {
+                    write!(statement, " {} {}", u, v).unwrap();
+                }
+                write!(statement, " indices {}", indices.len()).unwrap();
+                for [i0, i1, i2] in indices {
+                    write!(statement, " {} {} {}", i0, i1, i2).unwrap();
+                }
+                write!(statement, " {}", name).unwrap();
+            }
+            ObjectDesc::Quad { q, u, v, material } => {
+                let name = self.material(out, material);
+                statement.push_str("quad");
+                for &x in &[*q, *u, *v] {
+                    write_vec3(&mut statement, x);
+                }
+                write!(statement, " {}", name).unwrap();
+            }
+            ObjectDesc::Disk {
+                center,
+                normal,
+                radius,
+                material,
+            } => {
+                let name = self.material(out, material);
+                statement.push_str("disk");
+                write_vec3(&mut statement, *center);
+                write_vec3(&mut statement, *normal);
+                write!(statement, " {} {}", radius, name).unwrap();
+            }
+            ObjectDesc::Plane {
+                point,
+                normal,
+                material,
+            } => {
+                let name = self.material(out, material);
+                statement.push_str("plane");
+                write_vec3(&mut statement, *point);
+                write_vec3(&mut statement, *normal);
+                write!(statement, " {}", name).unwrap();
+            }
+            ObjectDesc::Medium {
+                boundary,
+                density,
+                material,
+            } => {
+                let boundary = self.object(out, boundary);
+                let name = self.material(out, material);
+                write!(statement, "medium {} {} {}", boundary, density, name).unwrap();
+            }
+            ObjectDesc::GridMedium {
+                grid,
+                min,
+                max,
+                density,
+                material,
+            } => {
+                let grid = self.grid(out, grid);
+                let name = self.material(out, material);
+                write!(statement, "grid_medium {}", grid).unwrap();
+                write_vec3(&mut statement, *min);
+                write_vec3(&mut statement, *max);
+                write!(statement, " {} {}", density, name).unwrap();
+            }
+            ObjectDesc::Instance { object, matrix } => {
+                let name = self.object(out, object);
+                write!(statement, "instance {} matrix", name).unwrap();
+                for x in matrix.0[..3].iter().flatten() {
+                    write!(statement, " {}", x).unwrap();
+                }
+            }
+            ObjectDesc::Group(objects) => {
+                return objects
+                    .iter()
+                    .flat_map(|obj| self.object_statements(out, obj))
+                    .collect();
+            }
+        }
+        vec![statement]
+    }
+}
+
+/// Writes a scene in the format parse_scene reads. Fails if the world has objects that can't be
+/// described in it (Like objects made of a custom material). Meshes are written out with all
+/// their vertices, since we don't know which file they came from
+pub fn write_scene(
+    camera: &CameraDesc,
+    params: &RenderParams,
+    world: &HittableList,
+) -> Result<String, String> {
+    use std::fmt::Write;
+
+    let mut out = String::new();
+    out.push_str("camera lookfrom");
+    write_vec3(&mut out, camera.lookfrom);
+    out.push_str(" lookat");
+    write_vec3(&mut out, camera.lookat);
+    out.push_str(" up");
+    write_vec3(&mut out, camera.view_up);
+    writeln!(
+        out,
+        " fov {} aspect {} aperture {} focus {} shutter {} {}",
+        camera.vertical_fov_degrees,
+        camera.aspect_ratio,
+        camera.aperture,
+        camera.focus_dist,
+        camera.shutter_open,
+        camera.shutter_close
+    )
+    .unwrap();
+    write!(
+        out,
+        "render width {} height {} samples {} depth {}",
+        params.image_width, params.image_height, params.samples_per_px, params.max_depth,
+    )
+    .unwrap();
+    if let Some(depth) = params.roulette_depth {
+        write!(out, " roulette {} {}", depth, params.roulette_probability).unwrap();
+    }
+    writeln!(
+        out,
+        " adaptive {} {} sampler {} tiles {} {}",
+        params.min_samples_per_px,
+        params.noise_threshold,
+        params.sampler.name(),
+        params.tile_size,
+        params.tile_order.name()
+    )
+    .unwrap();
+    match params.background {
+        background if background == Background::SKY => out.push_str("background sky"),
+        background if background == Background::BLACK => out.push_str("background black"),
+        Background::Color(color) => {
+            out.push_str("background color");
+            write_vec3(&mut out, color);
+        }
+        Background::Gradient { bottom, top } => {
+            out.push_str("background gradient");
+            write_vec3(&mut out, bottom);
+            write_vec3(&mut out, top);
+        }
+    }
+    out.push('\n');
+
+    let mut names = Names::default();
+    for (idx, obj) in world.0.iter().enumerate() {
+        let desc = obj
+            .describe()
+            .ok_or_else(|| format!("object {} can't be written to a scene file", idx))?;
+        for statement in names.object_statements(&mut out, &desc) {
+            writeln!(out, "{}", statement).unwrap();
+        }
+    }
+
+    Ok(out)
+}
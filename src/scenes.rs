@@ -5,11 +5,11 @@ pub fn random_scene(rand: &mut RandState) -> HittableList {
     let mut world = HittableList(Vec::with_capacity(22 * 22 + 5));
 
     let ground_material = LambertianDiffuse::new(Vec3::new(0.5, 0.5, 0.5));
-    world.add(Sphere {
-        center: Vec3::new(0.0, -1000.0, 0.0),
-        radius: 1000.0,
-        material: ground_material,
-    });
+    world.add(Plane::new(
+        Vec3::zero(),
+        Vec3::new(0.0, 1.0, 0.0),
+        ground_material,
+    ));
 
     for a in -11..11 {
         for b in -11..11 {
@@ -81,11 +81,11 @@ pub fn pastel_scene(rand: &mut RandState) -> HittableList {
     let mut world = HittableList(Vec::with_capacity(22 * 22 + 5));
 
     let ground_material = LambertianDiffuse::new(Vec3::new(0.95, 0.95, 0.8));
-    world.add(Sphere {
-        center: Vec3::new(0.0, -1000.0, 0.0),
-        radius: 1000.0,
-        material: ground_material,
-    });
+    world.add(Plane::new(
+        Vec3::zero(),
+        Vec3::new(0.0, 1.0, 0.0),
+        ground_material,
+    ));
 
     for a in -11..11 {
         for b in -11..11 {
@@ -161,11 +161,11 @@ pub fn moon_scene(rand: &mut RandState) -> HittableList {
     let mut world = HittableList(Vec::with_capacity(22 * 22 + 5));
 
     let ground_material = LambertianDiffuse::new(Vec3::new(0.95, 0.95, 0.8));
-    world.add(Sphere {
-        center: Vec3::new(0.0, -1000.0, 0.0),
-        radius: 1000.0,
-        material: ground_material,
-    });
+    world.add(Plane::new(
+        Vec3::zero(),
+        Vec3::new(0.0, 1.0, 0.0),
+        ground_material,
+    ));
 
     for a in -11..11 {
         for b in -11..11 {
@@ -406,3 +406,412 @@ pub fn perf_scene() -> HittableList {
 
     world
 }
+
+/// Octahedron centered at `center`, used to show off triangle meshes
+fn octahedron(center: Vec3, radius: f64, smooth: bool, material: MaterialType) -> TriangleMesh {
+    let normals = vec![
+        Vec3::new(1.0, 0.0, 0.0),
+        Vec3::new(-1.0, 0.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        Vec3::new(0.0, -1.0, 0.0),
+        Vec3::new(0.0, 0.0, 1.0),
+        Vec3::new(0.0, 0.0, -1.0),
+    ];
+    let positions = normals.iter().map(|&n| center + radius * n).collect();
+    let indices = vec![
+        [0, 2, 4],
+        [4, 2, 1],
+        [1, 2, 5],
+        [5, 2, 0],
+        [4, 3, 0],
+        [1, 3, 4],
+        [5, 3, 1],
+        [0, 3, 5],
+    ];
+    let normals = if smooth { normals } else { vec![] };
+    TriangleMesh::new(positions, normals, vec![], indices, material).unwrap()
+}
+
+/// A couple triangle meshes and a triangle on the ground
+pub fn triangle_scene() -> HittableList {
+    let mut world = HittableList::new();
+
+    let ground_material = LambertianDiffuse::new(Vec3::new(0.5, 0.5, 0.5));
+    world.add(Sphere {
+        center: Vec3::new(0.0, -1000.0, 0.0),
+        radius: 1000.0,
+        material: ground_material,
+    });
+
+    world.add(octahedron(
+        Vec3::new(-4.0, 1.0, 0.0),
+        1.0,
+        false,
+        LambertianDiffuse::new(Vec3::new(0.4, 0.2, 0.1)),
+    ));
+    world.add(octahedron(
+        Vec3::new(0.0, 1.0, 0.0),
+        1.0,
+        true,
+        Dielectric::new(1.5),
+    ));
+    world.add(octahedron(
+        Vec3::new(4.0, 1.0, 0.0),
+        1.0,
+        true,
+        Metal::new(0.7, 0.6, 0.5, 0.0),
+    ));
+    world.add(Triangle::new(
+        Vec3::new(-6.0, 0.0, -3.0),
+        Vec3::new(6.0, 0.0, -3.0),
+        Vec3::new(0.0, 3.0, -3.0),
+        LambertianDiffuse::new(Vec3::new(0.1, 0.2, 0.5)),
+    ));
+
+    world
+}
+
+/// Only lit by a rectangular light and a spherical one, like the "simple light" scene of the
+/// second book. Meant to be rendered with a black background
+pub fn light_scene() -> HittableList {
+    let mut world = HittableList::new();
+
+    world.add(Sphere {
+        center: Vec3::new(0.0, -1000.0, 0.0),
+        radius: 1000.0,
+        material: LambertianDiffuse::new(Vec3::new(0.5, 0.5, 0.5)),
+    });
+    world.add(Sphere {
+        center: Vec3::new(0.0, 2.0, 0.0),
+        radius: 2.0,
+        material: LambertianDiffuse::new(Vec3::new(0.2, 0.4, 0.8)),
+    });
+
+    add_light_scene_lights(&mut world);
+
+    world
+}
+fn add_light_scene_lights(list: &mut HittableList) {
+    list.add(Quad::xy_rect(
+        3.0,
+        5.0,
+        1.0,
+        3.0,
+        -2.0,
+        DiffuseLight::new(Vec3::repeat(4.0)),
+    ));
+    list.add(Sphere {
+        center: Vec3::new(0.0, 7.0, 0.0),
+        radius: 2.0,
+        material: DiffuseLight::new(Vec3::repeat(4.0)),
+    });
+}
+/// The lights of `light_scene`, to sample them when rendering it
+pub fn light_scene_lights() -> HittableList {
+    let mut lights = HittableList::new();
+    add_light_scene_lights(&mut lights);
+    lights
+}
+
+/// A checkered ground under spheres with each kind of Perlin noise texture
+pub fn texture_scene() -> HittableList {
+    use texture::{NoiseKind, NoiseTexture, SolidColor, UvChecker};
+
+    let mut world = HittableList::new();
+
+    world.add(Plane::new(
+        Vec3::zero(),
+        Vec3::new(0.0, 1.0, 0.0),
+        LambertianDiffuse::textured(UvChecker::new(
+            1.0,
+            SolidColor::new(Vec3::new(0.2, 0.3, 0.1)),
+            SolidColor::new(Vec3::new(0.9, 0.9, 0.9)),
+        )),
+    ));
+    world.add(Sphere {
+        center: Vec3::new(0.0, 1.0, 0.0),
+        radius: 1.0,
+        material: LambertianDiffuse::textured(NoiseTexture::new(NoiseKind::Marble, 4.0, 0)),
+    });
+    world.add(Sphere {
+        center: Vec3::new(-4.0, 1.0, 0.0),
+        radius: 1.0,
+        material: LambertianDiffuse::textured(NoiseTexture::new(NoiseKind::Smooth, 4.0, 1)),
+    });
+    world.add(Sphere {
+        center: Vec3::new(4.0, 1.0, 0.0),
+        radius: 1.0,
+        material: Metal::textured(NoiseTexture::new(NoiseKind::Turbulence, 2.0, 2), 0.2),
+    });
+
+    world
+}
+
+/// The random scene from the end of the first book, but the diffuse spheres bounce up while the
+/// shutter is open, like the first scene of the second book. Meant to be rendered with a shutter
+/// from 0 to 1
+pub fn bouncing_scene(rand: &mut RandState) -> HittableList {
+    let mut world = HittableList(Vec::with_capacity(22 * 22 + 5));
+
+    world.add(Plane::new(
+        Vec3::zero(),
+        Vec3::new(0.0, 1.0, 0.0),
+        LambertianDiffuse::new(Vec3::new(0.5, 0.5, 0.5)),
+    ));
+
+    for a in -11..11 {
+        for b in -11..11 {
+            let a = a as f64;
+            let b = b as f64;
+            let choose_mat = rand.random_double();
+            let center = Vec3::new(
+                a + 0.9 * rand.random_double(),
+                0.2,
+                b + 0.9 * rand.random_double(),
+            );
+            if choose_mat < 0.8 {
+                // diffuse, and moving
+                let albedo = Vec3::random(rand) * Vec3::random(rand);
+                let center1 = center + Vec3::new(0.0, rand.random_double_range(0.0, 0.5), 0.0);
+                world.add(MovingSphere::new(
+                    (center, 0.0),
+                    (center1, 1.0),
+                    0.2,
+                    LambertianDiffuse::new(albedo),
+                ));
+            } else if choose_mat < 0.95 {
+                // metal
+                let material = Metal::new(
+                    rand.random_double_range(0.5, 1.0),
+                    rand.random_double_range(0.5, 1.0),
+                    rand.random_double_range(0.5, 1.0),
+                    rand.random_double_range(0.0, 0.5),
+                );
+                world.add(Sphere {
+                    center,
+                    radius: 0.2,
+                    material,
+                });
+            } else {
+                // glass
+                world.add(Sphere {
+                    center,
+                    radius: 0.2,
+                    material: Dielectric::new(1.5),
+                });
+            }
+        }
+    }
+    world.add(Sphere {
+        center: Vec3::new(0.0, 1.0, 0.0),
+        radius: 1.0,
+        material: Dielectric::new(1.5),
+    });
+    world.add(Sphere {
+        center: Vec3::new(-4.0, 1.0, 0.0),
+        radius: 1.0,
+        material: LambertianDiffuse::new(Vec3::new(0.4, 0.2, 0.1)),
+    });
+    world.add(Sphere {
+        center: Vec3::new(4.0, 1.0, 0.0),
+        radius: 1.0,
+        material: Metal::new(0.7, 0.6, 0.5, 0.0),
+    });
+
+    world
+}
+
+/// Copies of a single snowman (A group of spheres) of different sizes in a circle, all sharing the
+/// same spheres through transforms
+pub fn instance_scene() -> HittableList {
+    use transform::{share, Matrix4, Transform};
+
+    let mut world = HittableList::new();
+
+    world.add(Sphere {
+        center: Vec3::new(0.0, -1000.0, 0.0),
+        radius: 1000.0,
+        material: LambertianDiffuse::new(Vec3::new(0.5, 0.6, 0.5)),
+    });
+
+    let mut snowman = HittableList::new();
+    let snow = LambertianDiffuse::new(Vec3::new(0.9, 0.9, 0.9));
+    for &(height, radius) in &[(0.5, 0.5), (1.25, 0.35), (1.8, 0.25)] {
+        snowman.add(Sphere {
+            center: Vec3::new(0.0, height, 0.0),
+            radius,
+            material: snow.clone(),
+        });
+    }
+    snowman.add(Sphere {
+        center: Vec3::new(0.0, 1.8, 0.25),
+        radius: 0.06,
+        material: Metal::new(0.9, 0.4, 0.1, 0.3),
+    });
+    let snowman = share(snowman);
+
+    for i in 0..8 {
+        // Scaled first, then moved out and turned around the center, so they all face outwards
+        let matrix = Matrix4::rotation(Vec3::new(0.0, 1.0, 0.0), 45.0 * i as f64)
+            * Matrix4::translation(Vec3::new(0.0, 0.0, 3.0))
+            * Matrix4::scaling(Vec3::repeat(0.6 + 0.1 * i as f64));
+        world.add(Transform::new(snowman.clone(), matrix));
+    }
+    world.add(Sphere {
+        center: Vec3::new(0.0, 1.0, 0.0),
+        radius: 1.0,
+        material: Dielectric::new(1.5),
+    });
+
+    world
+}
+
+/// The Cornell box from the second book: a red and a green wall, a light in the ceiling and two
+/// rotated boxes. Meant to be rendered with a black background and a square image
+pub fn cornell_box() -> HittableList {
+    use transform::{share, Matrix4, Transform};
+
+    let mut world = HittableList::new();
+
+    let red = LambertianDiffuse::new(Vec3::new(0.65, 0.05, 0.05));
+    let white = LambertianDiffuse::new(Vec3::new(0.73, 0.73, 0.73));
+    let green = LambertianDiffuse::new(Vec3::new(0.12, 0.45, 0.15));
+
+    world.add(Quad::yz_rect(0.0, 555.0, 0.0, 555.0, 555.0, green));
+    world.add(Quad::yz_rect(0.0, 555.0, 0.0, 555.0, 0.0, red));
+    add_cornell_box_lights(&mut world);
+    world.add(Quad::xz_rect(0.0, 555.0, 0.0, 555.0, 0.0, white.clone()));
+    world.add(Quad::xz_rect(0.0, 555.0, 0.0, 555.0, 555.0, white.clone()));
+    world.add(Quad::xy_rect(0.0, 555.0, 0.0, 555.0, 555.0, white.clone()));
+
+    let y = Vec3::new(0.0, 1.0, 0.0);
+    let tall = share(planar::cuboid(
+        Vec3::zero(),
+        Vec3::new(165.0, 330.0, 165.0),
+        white.clone(),
+    ));
+    world.add(Transform::new(
+        tall,
+        Matrix4::translation(Vec3::new(265.0, 0.0, 295.0)) * Matrix4::rotation(y, 15.0),
+    ));
+    let short = share(planar::cuboid(
+        Vec3::zero(),
+        Vec3::new(165.0, 165.0, 165.0),
+        white,
+    ));
+    world.add(Transform::new(
+        short,
+        Matrix4::translation(Vec3::new(130.0, 0.0, 65.0)) * Matrix4::rotation(y, -18.0),
+    ));
+
+    world
+}
+
+fn add_cornell_box_lights(list: &mut HittableList) {
+    let light = DiffuseLight::new(Vec3::repeat(15.0));
+    list.add(Quad::xz_rect(213.0, 343.0, 227.0, 332.0, 554.0, light));
+}
+/// The light of `cornell_box`, to sample it when rendering it
+pub fn cornell_box_lights() -> HittableList {
+    let mut lights = HittableList::new();
+    add_cornell_box_lights(&mut lights);
+    lights
+}
+
+/// The Cornell box with the boxes made of smoke, from the second book
+pub fn cornell_smoke() -> HittableList {
+    use transform::{share, Matrix4, Transform};
+    use volume::ConstantMedium;
+
+    let mut world = HittableList::new();
+
+    let red = LambertianDiffuse::new(Vec3::new(0.65, 0.05, 0.05));
+    let white = LambertianDiffuse::new(Vec3::new(0.73, 0.73, 0.73));
+    let green = LambertianDiffuse::new(Vec3::new(0.12, 0.45, 0.15));
+
+    world.add(Quad::yz_rect(0.0, 555.0, 0.0, 555.0, 555.0, green));
+    world.add(Quad::yz_rect(0.0, 555.0, 0.0, 555.0, 0.0, red));
+    add_cornell_smoke_lights(&mut world);
+    world.add(Quad::xz_rect(0.0, 555.0, 0.0, 555.0, 0.0, white.clone()));
+    world.add(Quad::xz_rect(0.0, 555.0, 0.0, 555.0, 555.0, white.clone()));
+    world.add(Quad::xy_rect(0.0, 555.0, 0.0, 555.0, 555.0, white.clone()));
+
+    let y = Vec3::new(0.0, 1.0, 0.0);
+    let tall = share(Transform::new(
+        share(planar::cuboid(
+            Vec3::zero(),
+            Vec3::new(165.0, 330.0, 165.0),
+            white.clone(),
+        )),
+        Matrix4::translation(Vec3::new(265.0, 0.0, 295.0)) * Matrix4::rotation(y, 15.0),
+    ));
+    world.add(ConstantMedium::new(
+        tall,
+        0.01,
+        Isotropic::new(Vec3::zero()),
+    ));
+    let short = share(Transform::new(
+        share(planar::cuboid(
+            Vec3::zero(),
+            Vec3::new(165.0, 165.0, 165.0),
+            white,
+        )),
+        Matrix4::translation(Vec3::new(130.0, 0.0, 65.0)) * Matrix4::rotation(y, -18.0),
+    ));
+    world.add(ConstantMedium::new(
+        short,
+        0.01,
+        Isotropic::new(Vec3::repeat(1.0)),
+    ));
+
+    world
+}
+
+fn add_cornell_smoke_lights(list: &mut HittableList) {
+    let light = DiffuseLight::new(Vec3::repeat(7.0));
+    list.add(Quad::xz_rect(113.0, 443.0, 127.0, 432.0, 554.0, light));
+}
+/// The light of `cornell_smoke`, to sample it when rendering it
+pub fn cornell_smoke_lights() -> HittableList {
+    let mut lights = HittableList::new();
+    add_cornell_smoke_lights(&mut lights);
+    lights
+}
+
+/// A cloud of Perlin noise over the three big spheres of the first book
+pub fn cloud_scene() -> HittableList {
+    use volume::{DensityGrid, GridMedium};
+
+    let mut world = HittableList::new();
+
+    world.add(Plane::new(
+        Vec3::zero(),
+        Vec3::new(0.0, 1.0, 0.0),
+        LambertianDiffuse::new(Vec3::new(0.5, 0.5, 0.5)),
+    ));
+    world.add(Sphere {
+        center: Vec3::new(0.0, 1.0, 0.0),
+        radius: 1.0,
+        material: Dielectric::new(1.5),
+    });
+    world.add(Sphere {
+        center: Vec3::new(-2.5, 1.0, 0.0),
+        radius: 1.0,
+        material: LambertianDiffuse::new(Vec3::new(0.4, 0.2, 0.1)),
+    });
+    world.add(Sphere {
+        center: Vec3::new(2.5, 1.0, 0.0),
+        radius: 1.0,
+        material: Metal::new(0.7, 0.6, 0.5, 0.0),
+    });
+
+    let cloud = std::sync::Arc::new(DensityGrid::noise(64, 4.0, 0));
+    world.add(GridMedium::new(
+        cloud,
+        Aabb::new(Vec3::new(-5.0, 1.5, -7.0), Vec3::new(5.0, 5.5, -3.0)),
+        10.0,
+        Isotropic::new(Vec3::repeat(0.9)),
+    ));
+
+    world
+}
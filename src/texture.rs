@@ -0,0 +1,441 @@
+// Constructors return the TextureType (Rc or enum) instead of Self
+#![allow(clippy::new_ret_no_self)]
+
+use super::{png, scene_file::TextureDesc, RandState, Vec3};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+#[cfg(feature = "dyn_mat")]
+use std::rc::Rc;
+
+/// Textures follow the dyn_mat feature, since they are only used by materials
+#[cfg(feature = "dyn_mat")]
+pub type TextureType = Rc<dyn Texture>;
+#[cfg(not(feature = "dyn_mat"))]
+pub type TextureType = EnumTexture;
+
+pub trait Texture {
+    /// Color at the surface coordinates (u, v), or at the point p for solid textures
+    fn value(&self, u: f64, v: f64, p: Vec3) -> Vec3;
+    /// Parameters used to write the texture to a scene file, if it can be written
+    fn describe(&self) -> Option<TextureDesc> {
+        None
+    }
+}
+
+#[derive(Clone)]
+pub enum EnumTexture {
+    Solid(SolidColor),
+    Checker(Checker),
+    UvChecker(UvChecker),
+    Noise(NoiseTexture),
+    Image(ImageTexture),
+}
+impl EnumTexture {
+    pub fn value(&self, u: f64, v: f64, p: Vec3) -> Vec3 {
+        match self {
+            EnumTexture::Solid(tex) => tex.value(u, v, p),
+            EnumTexture::Checker(tex) => tex.value(u, v, p),
+            EnumTexture::UvChecker(tex) => tex.value(u, v, p),
+            EnumTexture::Noise(tex) => tex.value(u, v, p),
+            EnumTexture::Image(tex) => tex.value(u, v, p),
+        }
+    }
+    pub fn describe(&self) -> Option<TextureDesc> {
+        match self {
+            EnumTexture::Solid(tex) => tex.describe(),
+            EnumTexture::Checker(tex) => tex.describe(),
+            EnumTexture::UvChecker(tex) => tex.describe(),
+            EnumTexture::Noise(tex) => tex.describe(),
+            EnumTexture::Image(tex) => tex.describe(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct SolidColor {
+    color: Vec3,
+}
+impl SolidColor {
+    #[cfg(feature = "dyn_mat")]
+    pub fn new(color: Vec3) -> TextureType {
+        Rc::new(Self { color })
+    }
+    #[cfg(not(feature = "dyn_mat"))]
+    pub fn new(color: Vec3) -> TextureType {
+        EnumTexture::Solid(Self { color })
+    }
+}
+impl Texture for SolidColor {
+    fn value(&self, _u: f64, _v: f64, _p: Vec3) -> Vec3 {
+        self.color
+    }
+    fn describe(&self) -> Option<TextureDesc> {
+        Some(TextureDesc::Solid { color: self.color })
+    }
+}
+
+/// A texture inside another one. The enum needs the indirection, and an Arc keeps it cheap to clone
+#[cfg(feature = "dyn_mat")]
+type NestedTexture = TextureType;
+#[cfg(not(feature = "dyn_mat"))]
+type NestedTexture = Arc<TextureType>;
+
+/// 3D checker pattern of cubes of side `size`, alternating between two textures
+#[derive(Clone)]
+pub struct Checker {
+    size: f64,
+    even: NestedTexture,
+    odd: NestedTexture,
+}
+impl Checker {
+    #[cfg(feature = "dyn_mat")]
+    pub fn new(size: f64, even: TextureType, odd: TextureType) -> TextureType {
+        Rc::new(Self { size, even, odd })
+    }
+    #[cfg(not(feature = "dyn_mat"))]
+    pub fn new(size: f64, even: TextureType, odd: TextureType) -> TextureType {
+        EnumTexture::Checker(Self {
+            size,
+            even: Arc::new(even),
+            odd: Arc::new(odd),
+        })
+    }
+}
+impl Texture for Checker {
+    fn value(&self, u: f64, v: f64, p: Vec3) -> Vec3 {
+        let cell = |x: f64| (x / self.size).floor() as i64;
+        if (cell(p.x()) + cell(p.y()) + cell(p.z())) % 2 == 0 {
+            self.even.value(u, v, p)
+        } else {
+            self.odd.value(u, v, p)
+        }
+    }
+    fn describe(&self) -> Option<TextureDesc> {
+        Some(TextureDesc::Checker {
+            size: self.size,
+            even: Box::new(self.even.describe()?),
+            odd: Box::new(self.odd.describe()?),
+        })
+    }
+}
+
+/// Checker pattern of squares of side `size` in uv coordinates, for surfaces like planes where they
+/// aren't limited to [0, 1]
+#[derive(Clone)]
+pub struct UvChecker {
+    size: f64,
+    even: NestedTexture,
+    odd: NestedTexture,
+}
+impl UvChecker {
+    #[cfg(feature = "dyn_mat")]
+    pub fn new(size: f64, even: TextureType, odd: TextureType) -> TextureType {
+        Rc::new(Self { size, even, odd })
+    }
+    #[cfg(not(feature = "dyn_mat"))]
+    pub fn new(size: f64, even: TextureType, odd: TextureType) -> TextureType {
+        EnumTexture::UvChecker(Self {
+            size,
+            even: Arc::new(even),
+            odd: Arc::new(odd),
+        })
+    }
+}
+impl Texture for UvChecker {
+    fn value(&self, u: f64, v: f64, p: Vec3) -> Vec3 {
+        let cell = |x: f64| (x / self.size).floor() as i64;
+        if (cell(u) + cell(v)) % 2 == 0 {
+            self.even.value(u, v, p)
+        } else {
+            self.odd.value(u, v, p)
+        }
+    }
+    fn describe(&self) -> Option<TextureDesc> {
+        Some(TextureDesc::UvChecker {
+            size: self.size,
+            even: Box::new(self.even.describe()?),
+            odd: Box::new(self.odd.describe()?),
+        })
+    }
+}
+
+const POINT_COUNT: usize = 256;
+
+/// Perlin noise with random gradient vectors, as in the second book
+pub struct Perlin {
+    gradients: Vec<Vec3>,
+    perm: [Vec<usize>; 3],
+}
+impl Perlin {
+    pub fn new(rand: &mut RandState) -> Self {
+        let gradients = (0..POINT_COUNT)
+            .map(|_| Vec3::random_range(rand, -1.0, 1.0).unit_vector())
+            .collect();
+        let mut permutation = || {
+            let mut perm = (0..POINT_COUNT).collect::<Vec<_>>();
+            // Fisher-Yates shuffle
+            for i in (1..POINT_COUNT).rev() {
+                let target = (rand.random_double() * (i + 1) as f64) as usize;
+                perm.swap(i, target.min(i));
+            }
+            perm
+        };
+        let perm = [permutation(), permutation(), permutation()];
+        Self { gradients, perm }
+    }
+    /// Smooth noise in [-1, 1]
+    pub fn noise(&self, p: Vec3) -> f64 {
+        let floor = [p.x().floor(), p.y().floor(), p.z().floor()];
+        let [u, v, w] = [p.x() - floor[0], p.y() - floor[1], p.z() - floor[2]];
+        let [i, j, k] = [floor[0] as i64, floor[1] as i64, floor[2] as i64];
+
+        let mut corners = [[[Vec3::zero(); 2]; 2]; 2];
+        for (di, plane) in corners.iter_mut().enumerate() {
+            for (dj, row) in plane.iter_mut().enumerate() {
+                for (dk, corner) in row.iter_mut().enumerate() {
+                    let wrap = |x: i64| (x & (POINT_COUNT as i64 - 1)) as usize;
+                    let idx = self.perm[0][wrap(i + di as i64)]
+                        ^ self.perm[1][wrap(j + dj as i64)]
+                        ^ self.perm[2][wrap(k + dk as i64)];
+                    *corner = self.gradients[idx];
+                }
+            }
+        }
+
+        // Hermite smoothing of the trilinear interpolation
+        let [uu, vv, ww] = [
+            u * u * (3.0 - 2.0 * u),
+            v * v * (3.0 - 2.0 * v),
+            w * w * (3.0 - 2.0 * w),
+        ];
+        let mut accum = 0.0;
+        for (di, plane) in corners.iter().enumerate() {
+            for (dj, row) in plane.iter().enumerate() {
+                for (dk, gradient) in row.iter().enumerate() {
+                    let [fi, fj, fk] = [di as f64, dj as f64, dk as f64];
+                    let weight = Vec3::new(u - fi, v - fj, w - fk);
+                    accum += (fi * uu + (1.0 - fi) * (1.0 - uu))
+                        * (fj * vv + (1.0 - fj) * (1.0 - vv))
+                        * (fk * ww + (1.0 - fk) * (1.0 - ww))
+                        * gradient.dot(weight);
+                }
+            }
+        }
+        accum
+    }
+    /// Sum of `depth` octaves of noise, each with half the weight and twice the frequency
+    pub fn turbulence(&self, p: Vec3, depth: u32) -> f64 {
+        let mut accum = 0.0;
+        let mut p = p;
+        let mut weight = 1.0;
+        for _ in 0..depth {
+            accum += weight * self.noise(p);
+            weight *= 0.5;
+            p = 2.0 * p;
+        }
+        accum.abs()
+    }
+}
+
+const TURBULENCE_DEPTH: u32 = 7;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NoiseKind {
+    /// Plain perlin noise
+    Smooth,
+    Turbulence,
+    /// Turbulence used to shift the phase of a sine wave along z
+    Marble,
+}
+
+/// Grayscale noise texture. `scale` is the frequency of the noise
+#[derive(Clone)]
+pub struct NoiseTexture {
+    perlin: Arc<Perlin>,
+    kind: NoiseKind,
+    scale: f64,
+    seed: u64,
+}
+impl NoiseTexture {
+    fn build(kind: NoiseKind, scale: f64, seed: u64) -> Self {
+        Self {
+            perlin: Arc::new(Perlin::new(&mut RandState::from_seed(seed))),
+            kind,
+            scale,
+            seed,
+        }
+    }
+    /// The same seed always gives the same noise
+    #[cfg(feature = "dyn_mat")]
+    pub fn new(kind: NoiseKind, scale: f64, seed: u64) -> TextureType {
+        Rc::new(Self::build(kind, scale, seed))
+    }
+    #[cfg(not(feature = "dyn_mat"))]
+    pub fn new(kind: NoiseKind, scale: f64, seed: u64) -> TextureType {
+        EnumTexture::Noise(Self::build(kind, scale, seed))
+    }
+}
+impl Texture for NoiseTexture {
+    fn value(&self, _u: f64, _v: f64, p: Vec3) -> Vec3 {
+        let p = self.scale * p;
+        let intensity = match self.kind {
+            NoiseKind::Smooth => 0.5 * (1.0 + self.perlin.noise(p)),
+            NoiseKind::Turbulence => self.perlin.turbulence(p, TURBULENCE_DEPTH),
+            NoiseKind::Marble => {
+                0.5 * (1.0 + (p.z() + 10.0 * self.perlin.turbulence(p, TURBULENCE_DEPTH)).sin())
+            }
+        };
+        Vec3::repeat(intensity)
+    }
+    fn describe(&self) -> Option<TextureDesc> {
+        Some(TextureDesc::Noise {
+            kind: self.kind,
+            scale: self.scale,
+            seed: self.seed,
+        })
+    }
+}
+
+/// 8-bit RGB pixels, top row first
+struct ImageData {
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+}
+
+/// Texture mapped with (u, v), where (0, 0) is the bottom left corner of the image
+///
+/// Images are assumed to have the same gamma 2 that renders are written with, so their colors are
+/// squared to get linear albedos
+#[derive(Clone)]
+pub struct ImageTexture {
+    image: Arc<ImageData>,
+    path: Option<PathBuf>,
+}
+impl ImageTexture {
+    fn build(width: usize, height: usize, pixels: Vec<u8>, path: Option<PathBuf>) -> Self {
+        assert_eq!(pixels.len(), width * height * 3);
+        assert!(width > 0 && height > 0);
+        Self {
+            image: Arc::new(ImageData {
+                width,
+                height,
+                pixels,
+            }),
+            path,
+        }
+    }
+    /// `pixels` is 8-bit RGB, top row first
+    #[cfg(feature = "dyn_mat")]
+    pub fn new(width: usize, height: usize, pixels: Vec<u8>) -> TextureType {
+        Rc::new(Self::build(width, height, pixels, None))
+    }
+    #[cfg(not(feature = "dyn_mat"))]
+    pub fn new(width: usize, height: usize, pixels: Vec<u8>) -> TextureType {
+        EnumTexture::Image(Self::build(width, height, pixels, None))
+    }
+    /// Loads a PPM (P3 or P6) or PNG image
+    pub fn load(path: impl AsRef<Path>) -> Result<TextureType, String> {
+        let path = path.as_ref();
+        let data = std::fs::read(path).map_err(|err| err.to_string())?;
+        let (width, height, pixels) = if data.starts_with(b"P3") || data.starts_with(b"P6") {
+            decode_ppm(&data)?
+        } else if data.starts_with(&png::SIGNATURE) {
+            png::decode_png(&data)?
+        } else {
+            return Err("unknown image format (Expected PPM or PNG)".to_string());
+        };
+        let texture = Self::build(width, height, pixels, Some(path.to_path_buf()));
+        #[cfg(feature = "dyn_mat")]
+        let texture = Rc::new(texture);
+        #[cfg(not(feature = "dyn_mat"))]
+        let texture = EnumTexture::Image(texture);
+        Ok(texture)
+    }
+}
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _p: Vec3) -> Vec3 {
+        let image = &*self.image;
+        let u = u.clamp(0.0, 1.0);
+        // Flip v, since the image starts from the top
+        let v = 1.0 - v.clamp(0.0, 1.0);
+        let i = ((u * image.width as f64) as usize).min(image.width - 1);
+        let j = ((v * image.height as f64) as usize).min(image.height - 1);
+        let px = &image.pixels[3 * (j * image.width + i)..][..3];
+        let channel = |c: u8| (c as f64 / 255.0).powi(2);
+        Vec3::new(channel(px[0]), channel(px[1]), channel(px[2]))
+    }
+    fn describe(&self) -> Option<TextureDesc> {
+        Some(TextureDesc::Image {
+            path: self.path.clone()?,
+        })
+    }
+}
+
+/// Parses an ASCII (P3) or binary (P6) PPM into 8-bit RGB
+fn decode_ppm(data: &[u8]) -> Result<(usize, usize, Vec<u8>), String> {
+    let binary = data.starts_with(b"P6");
+    // Header fields are separated by whitespace, and comments can appear between them
+    let mut pos = 2;
+    let header_field = |pos: &mut usize| -> Result<usize, String> {
+        loop {
+            match data.get(*pos) {
+                Some(b'#') => {
+                    while data.get(*pos).is_some_and(|&c| c != b'\n') {
+                        *pos += 1;
+                    }
+                }
+                Some(c) if c.is_ascii_whitespace() => *pos += 1,
+                _ => break,
+            }
+        }
+        let start = *pos;
+        while data.get(*pos).is_some_and(|c| c.is_ascii_digit()) {
+            *pos += 1;
+        }
+        std::str::from_utf8(&data[start..*pos])
+            .unwrap()
+            .parse()
+            .map_err(|_| "invalid PPM header".to_string())
+    };
+    let width = header_field(&mut pos)?;
+    let height = header_field(&mut pos)?;
+    let max_value = header_field(&mut pos)?;
+    if width == 0 || height == 0 || max_value == 0 || max_value > 65535 {
+        return Err("invalid PPM header".to_string());
+    }
+    let count = width
+        .checked_mul(height)
+        .and_then(|pixels| pixels.checked_mul(3))
+        .ok_or("invalid PPM header")?;
+    let to_u8 = |value: usize| (value.min(max_value) * 255 / max_value) as u8;
+
+    let pixels = if binary {
+        // A single whitespace character separates the header from the pixels
+        let bytes = &data[(pos + 1).min(data.len())..];
+        let sample_size = if max_value < 256 { 1 } else { 2 };
+        if bytes.len() / sample_size < count {
+            return Err("the PPM file is truncated".to_string());
+        }
+        (0..count)
+            .map(|i| match sample_size {
+                1 => to_u8(bytes[i] as usize),
+                _ => to_u8((bytes[2 * i] as usize) << 8 | bytes[2 * i + 1] as usize),
+            })
+            .collect()
+    } else {
+        let text = std::str::from_utf8(&data[pos..]).map_err(|err| err.to_string())?;
+        let values = text
+            .split_whitespace()
+            .take(count)
+            .map(|word| word.parse().map(to_u8))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| "invalid PPM pixel value".to_string())?;
+        if values.len() < count {
+            return Err("the PPM file is truncated".to_string());
+        }
+        values
+    };
+    Ok((width, height, pixels))
+}
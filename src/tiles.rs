@@ -0,0 +1,96 @@
+//! Splitting the image into square tiles, and the order they're rendered in
+//!
+//! Tiles balance the work between threads better than scanlines, because a slow part of the
+//! image (A band of glass, say) is spread over many small tiles instead of a few rows. The image
+//! doesn't depend on the tiles: every sample has its own random numbers either way
+
+/// A rectangle of pixels. Rows count from the top, like the rendered images
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tile {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TileOrder {
+    /// Left to right, top to bottom
+    Scanline,
+    /// Outwards from the center, where the subject usually is
+    Spiral,
+    /// Along a Hilbert curve, so consecutive tiles are next to each other
+    Hilbert,
+}
+impl TileOrder {
+    pub const ALL: [TileOrder; 3] = [TileOrder::Scanline, TileOrder::Spiral, TileOrder::Hilbert];
+    /// Name in scene files and on the command line
+    pub fn name(self) -> &'static str {
+        match self {
+            TileOrder::Scanline => "scanline",
+            TileOrder::Spiral => "spiral",
+            TileOrder::Hilbert => "hilbert",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<TileOrder> {
+        Self::ALL.iter().copied().find(|order| order.name() == name)
+    }
+}
+
+/// Position of tile (`x`, `y`) along a Hilbert curve through a `size` by `size` grid, with `size`
+/// a power of 2
+fn hilbert_index(size: u32, mut x: u32, mut y: u32) -> u64 {
+    let mut index = 0;
+    let mut s = size / 2;
+    while s > 0 {
+        let (rx, ry) = ((x & s > 0) as u32, (y & s > 0) as u32);
+        index += s as u64 * s as u64 * ((3 * rx) ^ ry) as u64;
+        // Rotate the quadrant so the curve inside it starts and ends in the right corners
+        if ry == 0 {
+            if rx == 1 {
+                x = s - 1 - (x & (s - 1));
+                y = s - 1 - (y & (s - 1));
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        s /= 2;
+    }
+    index
+}
+
+/// Splits a `width` by `height` image into tiles of `size` by `size` pixels (Smaller at the right
+/// and bottom edges), in the order to render them
+pub fn tiles(width: u32, height: u32, size: u32, order: TileOrder) -> Vec<Tile> {
+    let size = size.max(1);
+    let (columns, rows) = (width.div_ceil(size), height.div_ceil(size));
+    let mut grid = (0..rows)
+        .flat_map(|row| (0..columns).map(move |column| (column, row)))
+        .collect::<Vec<_>>();
+    match order {
+        TileOrder::Scanline => {}
+        TileOrder::Spiral => {
+            let center = (
+                columns.saturating_sub(1) as f64 / 2.0,
+                rows.saturating_sub(1) as f64 / 2.0,
+            );
+            // By square ring around the center, and then by angle around it
+            let key = |&(column, row): &(u32, u32)| {
+                let (dx, dy) = (column as f64 - center.0, row as f64 - center.1);
+                (dx.abs().max(dy.abs()).ceil(), dy.atan2(dx))
+            };
+            grid.sort_by(|a, b| key(a).partial_cmp(&key(b)).unwrap());
+        }
+        TileOrder::Hilbert => {
+            let size = columns.max(rows).next_power_of_two();
+            grid.sort_by_key(|&(column, row)| hilbert_index(size, column, row));
+        }
+    }
+    grid.into_iter()
+        .map(|(column, row)| Tile {
+            x: column * size,
+            y: row * size,
+            width: size.min(width - column * size),
+            height: size.min(height - row * size),
+        })
+        .collect()
+}
@@ -0,0 +1,226 @@
+//! Affine transforms, and instances of objects placed with them
+use super::{scene_file::ObjectDesc, Aabb, HitRecord, Hittable, Ray, Vec3};
+
+#[cfg(not(feature = "dyn_hit"))]
+use super::EnumHit;
+#[cfg(feature = "dyn_hit")]
+use super::HittableType;
+#[cfg(feature = "dyn_hit")]
+use std::rc::Rc;
+use std::sync::Arc;
+
+/// Row major 4x4 matrix. Points are column vectors, so `a * b` applies `b` first
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Matrix4(pub [[f64; 4]; 4]);
+
+impl Matrix4 {
+    pub const IDENTITY: Matrix4 = Matrix4([
+        [1.0, 0.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ]);
+
+    pub fn translation(offset: Vec3) -> Self {
+        let mut m = Self::IDENTITY;
+        for (row, &x) in m.0.iter_mut().zip(&offset.0) {
+            row[3] = x;
+        }
+        m
+    }
+    pub fn scaling(factors: Vec3) -> Self {
+        let mut m = Self::IDENTITY;
+        for (i, &x) in factors.0.iter().enumerate() {
+            m.0[i][i] = x;
+        }
+        m
+    }
+    /// Counter clockwise rotation when the axis points at the viewer. The axis doesn't need to be
+    /// normalized
+    pub fn rotation(axis: Vec3, degrees: f64) -> Self {
+        let [x, y, z] = axis.unit_vector().0;
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        let t = 1.0 - cos;
+        Matrix4([
+            [
+                t * x * x + cos,
+                t * x * y - sin * z,
+                t * x * z + sin * y,
+                0.0,
+            ],
+            [
+                t * x * y + sin * z,
+                t * y * y + cos,
+                t * y * z - sin * x,
+                0.0,
+            ],
+            [
+                t * x * z - sin * y,
+                t * y * z + sin * x,
+                t * z * z + cos,
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn transpose(&self) -> Self {
+        let mut m = Self::IDENTITY;
+        for i in 0..4 {
+            for j in 0..4 {
+                m.0[i][j] = self.0[j][i];
+            }
+        }
+        m
+    }
+    /// Gauss-Jordan elimination with partial pivoting. Returns None for singular matrices
+    pub fn inverse(&self) -> Option<Self> {
+        let mut a = self.0;
+        let mut inv = Self::IDENTITY.0;
+        for col in 0..4 {
+            let pivot = (col..4)
+                .max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))
+                .unwrap();
+            if a[pivot][col].abs() < 1e-12 {
+                return None;
+            }
+            a.swap(col, pivot);
+            inv.swap(col, pivot);
+            let scale = 1.0 / a[col][col];
+            for j in 0..4 {
+                a[col][j] *= scale;
+                inv[col][j] *= scale;
+            }
+            for row in 0..4 {
+                let factor = a[row][col];
+                if row == col || factor == 0.0 {
+                    continue;
+                }
+                for j in 0..4 {
+                    a[row][j] -= factor * a[col][j];
+                    inv[row][j] -= factor * inv[col][j];
+                }
+            }
+        }
+        Some(Matrix4(inv))
+    }
+
+    pub fn transform_point(&self, p: Vec3) -> Vec3 {
+        self.transform_vector(p) + Vec3::new(self.0[0][3], self.0[1][3], self.0[2][3])
+    }
+    /// Ignores the translation, for directions
+    pub fn transform_vector(&self, v: Vec3) -> Vec3 {
+        let row = |i: usize| Vec3::new(self.0[i][0], self.0[i][1], self.0[i][2]).dot(v);
+        Vec3::new(row(0), row(1), row(2))
+    }
+}
+impl std::ops::Mul for Matrix4 {
+    type Output = Matrix4;
+
+    fn mul(self, rhs: Matrix4) -> Matrix4 {
+        let mut m = Matrix4([[0.0; 4]; 4]);
+        for i in 0..4 {
+            for j in 0..4 {
+                m.0[i][j] = (0..4).map(|k| self.0[i][k] * rhs.0[k][j]).sum();
+            }
+        }
+        m
+    }
+}
+
+/// Wraps data that is shared between objects, like an instanced object or the data of a mesh
+pub(crate) fn new_arc<T>(value: T) -> Arc<T> {
+    // Only Send and Sync without dyn_mat, which is the only way the parallel feature works anyway
+    #[allow(clippy::arc_with_non_send_sync)]
+    Arc::new(value)
+}
+
+/// An object that can be shared by many instances
+#[cfg(feature = "dyn_hit")]
+pub type SharedHittable = HittableType;
+#[cfg(not(feature = "dyn_hit"))]
+pub type SharedHittable = Arc<EnumHit>;
+
+#[cfg(feature = "dyn_hit")]
+pub fn share<T: Hittable + 'static>(object: T) -> SharedHittable {
+    Rc::new(object)
+}
+#[cfg(not(feature = "dyn_hit"))]
+pub fn share<T: Into<EnumHit>>(object: T) -> SharedHittable {
+    new_arc(object.into())
+}
+
+/// An instance of an object, placed in the world with an affine transform
+///
+/// Rays are moved into the space of the object instead of moving the object, so any number of
+/// instances can share it
+pub struct Transform {
+    object: SharedHittable,
+    matrix: Matrix4,
+    inverse: Matrix4,
+    /// Transpose of the inverse, for the normals
+    normal_matrix: Matrix4,
+}
+impl Transform {
+    /// Panics if the matrix can't be inverted
+    pub fn new(object: SharedHittable, matrix: Matrix4) -> Self {
+        let inverse = matrix
+            .inverse()
+            .expect("transform matrices have to be invertible");
+        Self {
+            object,
+            matrix,
+            inverse,
+            normal_matrix: inverse.transpose(),
+        }
+    }
+}
+impl Hittable for Transform {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, out: &mut HitRecord) -> bool {
+        // The direction isn't normalized, so t is the same in both spaces
+        let object_ray = Ray {
+            orig: self.inverse.transform_point(ray.orig),
+            dir: self.inverse.transform_vector(ray.dir),
+            time: ray.time,
+        };
+        if !self.object.hit(&object_ray, t_min, t_max, out) {
+            return false;
+        }
+        out.p = self.matrix.transform_point(out.p);
+        // Normals are transformed by the inverse transpose, which keeps them on the same side of
+        // the ray, so front_face stays valid
+        out.normal = self
+            .normal_matrix
+            .transform_vector(out.normal)
+            .unit_vector();
+        true
+    }
+    fn bounding_box(&self, output_box: &mut Aabb) -> bool {
+        let mut object_box = Aabb::empty();
+        if !self.object.bounding_box(&mut object_box) {
+            return false;
+        }
+        let mut result = Aabb::empty();
+        for corner in 0..8 {
+            let pick = |axis: usize| {
+                if corner & (1 << axis) == 0 {
+                    object_box.min.0[axis]
+                } else {
+                    object_box.max.0[axis]
+                }
+            };
+            let p = self
+                .matrix
+                .transform_point(Vec3::new(pick(0), pick(1), pick(2)));
+            result = result.surrounding(&Aabb::new(p, p));
+        }
+        *output_box = result;
+        true
+    }
+    fn describe(&self) -> Option<ObjectDesc> {
+        Some(ObjectDesc::Instance {
+            object: Box::new(self.object.describe()?),
+            matrix: self.matrix,
+        })
+    }
+}
@@ -0,0 +1,237 @@
+use super::{
+    scene_file::ObjectDesc, transform::new_arc, Aabb, Bvh, HitRecord, Hittable, MaterialType, Ray,
+    Vec3,
+};
+use std::sync::Arc;
+
+fn triangle_bbox(v0: Vec3, v1: Vec3, v2: Vec3) -> Aabb {
+    let bbox = Aabb::new(v0, v0).surrounding(&Aabb::new(v1, v1));
+    bbox.surrounding(&Aabb::new(v2, v2)).padded()
+}
+
+/// Möller–Trumbore ray/triangle intersection. Returns the ray parameter and the barycentric
+/// coordinates of v1 and v2
+fn intersect(
+    ray: &Ray,
+    v0: Vec3,
+    v1: Vec3,
+    v2: Vec3,
+    t_min: f64,
+    t_max: f64,
+) -> Option<(f64, f64, f64)> {
+    let edge1 = v1 - v0;
+    let edge2 = v2 - v0;
+    let pvec = ray.dir.cross(edge2);
+    let det = edge1.dot(pvec);
+    if det.abs() < 1e-12 {
+        // The ray is parallel to the triangle
+        return None;
+    }
+    let inv_det = 1.0 / det;
+
+    let tvec = ray.orig - v0;
+    let b1 = tvec.dot(pvec) * inv_det;
+    if !(0.0..=1.0).contains(&b1) {
+        return None;
+    }
+    let qvec = tvec.cross(edge1);
+    let b2 = ray.dir.dot(qvec) * inv_det;
+    if b2 < 0.0 || b1 + b2 > 1.0 {
+        return None;
+    }
+    let t = edge2.dot(qvec) * inv_det;
+    if t < t_min || t_max < t {
+        return None;
+    }
+    Some((t, b1, b2))
+}
+
+/// A single flat triangle. Its uv coordinates are the barycentric coordinates of v1 and v2
+pub struct Triangle {
+    v0: Vec3,
+    v1: Vec3,
+    v2: Vec3,
+    material: MaterialType,
+}
+impl Triangle {
+    pub fn new(v0: Vec3, v1: Vec3, v2: Vec3, material: MaterialType) -> Self {
+        Self {
+            v0,
+            v1,
+            v2,
+            material,
+        }
+    }
+}
+impl Hittable for Triangle {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, out: &mut HitRecord) -> bool {
+        let (t, b1, b2) = match intersect(ray, self.v0, self.v1, self.v2, t_min, t_max) {
+            Some(hit) => hit,
+            None => return false,
+        };
+        out.t = t;
+        out.p = ray.at(t);
+        out.u = b1;
+        out.v = b2;
+        let outward_normal = (self.v1 - self.v0).cross(self.v2 - self.v0).unit_vector();
+        out.set_face_normal(ray, outward_normal);
+        out.material = self.material.clone();
+        true
+    }
+    fn bounding_box(&self, output_box: &mut Aabb) -> bool {
+        *output_box = triangle_bbox(self.v0, self.v1, self.v2);
+        true
+    }
+    fn describe(&self) -> Option<ObjectDesc> {
+        Some(ObjectDesc::Triangle {
+            vertices: [self.v0, self.v1, self.v2],
+            material: self.material.describe()?,
+        })
+    }
+}
+
+/// Vertex and index buffers shared by every triangle of a mesh
+struct MeshData {
+    positions: Vec<Vec3>,
+    normals: Vec<Vec3>,
+    uvs: Vec<[f64; 2]>,
+    indices: Vec<[usize; 3]>,
+    material: MaterialType,
+}
+
+struct MeshTriangle {
+    mesh: Arc<MeshData>,
+    idx: usize,
+}
+impl MeshTriangle {
+    fn vertices(&self) -> (Vec3, Vec3, Vec3) {
+        let [i0, i1, i2] = self.mesh.indices[self.idx];
+        let positions = &self.mesh.positions;
+        (positions[i0], positions[i1], positions[i2])
+    }
+}
+impl Hittable for MeshTriangle {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, out: &mut HitRecord) -> bool {
+        let (v0, v1, v2) = self.vertices();
+        let (t, b1, b2) = match intersect(ray, v0, v1, v2, t_min, t_max) {
+            Some(hit) => hit,
+            None => return false,
+        };
+        let b0 = 1.0 - b1 - b2;
+        let mesh = &*self.mesh;
+        let [i0, i1, i2] = mesh.indices[self.idx];
+
+        out.t = t;
+        out.p = ray.at(t);
+        if mesh.uvs.is_empty() {
+            out.u = b1;
+            out.v = b2;
+        } else {
+            let (uv0, uv1, uv2) = (mesh.uvs[i0], mesh.uvs[i1], mesh.uvs[i2]);
+            out.u = b0 * uv0[0] + b1 * uv1[0] + b2 * uv2[0];
+            out.v = b0 * uv0[1] + b1 * uv1[1] + b2 * uv2[1];
+        }
+        let geometric_normal = (v1 - v0).cross(v2 - v0).unit_vector();
+        out.set_face_normal(ray, geometric_normal);
+        if !mesh.normals.is_empty() {
+            // Smooth shading, keeping the interpolated normal on the same side as the geometric one
+            let shading_normal =
+                (b0 * mesh.normals[i0] + b1 * mesh.normals[i1] + b2 * mesh.normals[i2])
+                    .unit_vector();
+            out.normal = if shading_normal.dot(out.normal) < 0.0 {
+                -shading_normal
+            } else {
+                shading_normal
+            };
+        }
+        out.material = mesh.material.clone();
+        true
+    }
+    fn bounding_box(&self, output_box: &mut Aabb) -> bool {
+        let (v0, v1, v2) = self.vertices();
+        *output_box = triangle_bbox(v0, v1, v2);
+        true
+    }
+}
+
+/// Indexed triangle mesh with optional per-vertex normals and uvs
+///
+/// The triangles are kept in their own bvh, so the mesh is a single object in a HittableList
+pub struct TriangleMesh {
+    mesh: Arc<MeshData>,
+    bvh: Bvh<MeshTriangle>,
+}
+impl TriangleMesh {
+    /// `normals` and `uvs` can be empty. Otherwise they need one entry per position. Without
+    /// normals the mesh is flat shaded, and without uvs the barycentric coordinates are used
+    pub fn new(
+        positions: Vec<Vec3>,
+        normals: Vec<Vec3>,
+        uvs: Vec<[f64; 2]>,
+        indices: Vec<[usize; 3]>,
+        material: MaterialType,
+    ) -> Result<Self, String> {
+        if !normals.is_empty() && normals.len() != positions.len() {
+            return Err(format!(
+                "{} normals for {} positions",
+                normals.len(),
+                positions.len()
+            ));
+        }
+        if !uvs.is_empty() && uvs.len() != positions.len() {
+            return Err(format!(
+                "{} uvs for {} positions",
+                uvs.len(),
+                positions.len()
+            ));
+        }
+        if let Some(idx) = indices
+            .iter()
+            .flatten()
+            .find(|&&idx| idx >= positions.len())
+        {
+            return Err(format!(
+                "vertex index {} is out of range for {} positions",
+                idx,
+                positions.len()
+            ));
+        }
+
+        let triangle_count = indices.len();
+        let mesh = new_arc(MeshData {
+            positions,
+            normals,
+            uvs,
+            indices,
+            material,
+        });
+        let triangles = (0..triangle_count)
+            .map(|idx| MeshTriangle {
+                mesh: mesh.clone(),
+                idx,
+            })
+            .collect();
+        Ok(Self {
+            mesh,
+            bvh: Bvh::from_objects(triangles),
+        })
+    }
+}
+impl Hittable for TriangleMesh {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, out: &mut HitRecord) -> bool {
+        self.bvh.hit(ray, t_min, t_max, out)
+    }
+    fn bounding_box(&self, output_box: &mut Aabb) -> bool {
+        self.bvh.bounding_box(output_box)
+    }
+    fn describe(&self) -> Option<ObjectDesc> {
+        let mesh = &*self.mesh;
+        Some(ObjectDesc::Mesh {
+            positions: mesh.positions.clone(),
+            normals: mesh.normals.clone(),
+            uvs: mesh.uvs.clone(),
+            indices: mesh.indices.clone(),
+            material: mesh.material.describe()?,
+        })
+    }
+}
@@ -1,6 +1,6 @@
 use super::RandState;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Vec3(pub [f64; 3]);
 
 impl Vec3 {
@@ -0,0 +1,340 @@
+//! Participating media, like smoke and fog
+use super::{
+    scene_file::{GridDesc, ObjectDesc},
+    texture::Perlin,
+    transform::SharedHittable,
+    Aabb, HitRecord, Hittable, MaterialType, RandState, Ray, Vec3,
+};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Hash of the bits of the ray, used as its random numbers
+///
+/// `Hittable::hit` doesn't get a random generator, and since rays already start from random points
+/// in random directions, hashing them is enough. It also means a ray tested again with a smaller
+/// t_max gets the same answer
+fn ray_seed(ray: &Ray) -> u64 {
+    let bits = ray.orig.0.iter().chain(&ray.dir.0).chain(Some(&ray.time));
+    let mut hash = 0x9E37_79B9_7F4A_7C15u64;
+    for x in bits {
+        hash = (hash ^ x.to_bits()).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        hash ^= hash >> 31;
+    }
+    hash = (hash ^ (hash >> 30)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    hash ^ (hash >> 31)
+}
+
+/// Uniform random number in [0, 1) for the ray
+fn ray_random(ray: &Ray) -> f64 {
+    (ray_seed(ray) >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Volume with the same density everywhere inside a boundary, which has to be a closed object
+///
+/// Rays going through it scatter with a probability that grows with the distance they travel
+/// inside, at a point where the phase function material (Usually `Isotropic`) decides the new
+/// direction
+pub struct ConstantMedium {
+    boundary: SharedHittable,
+    density: f64,
+    phase_function: MaterialType,
+}
+impl ConstantMedium {
+    pub fn new(boundary: SharedHittable, density: f64, phase_function: MaterialType) -> Self {
+        Self {
+            boundary,
+            density,
+            phase_function,
+        }
+    }
+}
+impl Hittable for ConstantMedium {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, out: &mut HitRecord) -> bool {
+        // Where the ray enters and leaves the boundary, even behind its origin
+        let mut enter = HitRecord::default();
+        let mut exit = HitRecord::default();
+        if !self
+            .boundary
+            .hit(ray, f64::NEG_INFINITY, f64::INFINITY, &mut enter)
+        {
+            return false;
+        }
+        if !self
+            .boundary
+            .hit(ray, enter.t + 0.0001, f64::INFINITY, &mut exit)
+        {
+            return false;
+        }
+
+        let t_enter = enter.t.max(t_min).max(0.0);
+        let t_exit = exit.t.min(t_max);
+        if t_enter >= t_exit {
+            return false;
+        }
+
+        let ray_length = ray.dir.length();
+        let distance_inside = (t_exit - t_enter) * ray_length;
+        let hit_distance = -(1.0 - ray_random(ray)).ln() / self.density;
+        if hit_distance > distance_inside {
+            return false;
+        }
+
+        out.t = t_enter + hit_distance / ray_length;
+        out.p = ray.at(out.t);
+        // There's no surface, so these don't mean anything
+        out.normal = Vec3::new(1.0, 0.0, 0.0);
+        out.front_face = true;
+        out.u = 0.0;
+        out.v = 0.0;
+        out.material = self.phase_function.clone();
+        true
+    }
+    fn bounding_box(&self, output_box: &mut Aabb) -> bool {
+        self.boundary.bounding_box(output_box)
+    }
+    fn describe(&self) -> Option<ObjectDesc> {
+        Some(ObjectDesc::Medium {
+            boundary: Box::new(self.boundary.describe()?),
+            density: self.density,
+            material: self.phase_function.describe()?,
+        })
+    }
+}
+
+/// Number of values in a grid of `size`, or None if it overflows
+fn grid_count(size: [usize; 3]) -> Option<usize> {
+    size[0].checked_mul(size[1])?.checked_mul(size[2])
+}
+
+/// Densities on a regular 3D grid, stored with x changing fastest and then y
+pub struct DensityGrid {
+    size: [usize; 3],
+    values: Vec<f32>,
+    max: f64,
+    /// Where the values came from, to write scenes
+    source: Option<GridDesc>,
+}
+impl DensityGrid {
+    fn build(size: [usize; 3], values: Vec<f32>, source: Option<GridDesc>) -> Self {
+        assert_eq!(values.len(), size[0] * size[1] * size[2]);
+        assert!(size.iter().all(|&n| n > 0));
+        let max = values.iter().fold(0.0f32, |max, &x| max.max(x)) as f64;
+        Self {
+            size,
+            values,
+            max,
+            source,
+        }
+    }
+    /// `values` has `size[0] * size[1] * size[2]` densities, with x changing fastest and then y
+    pub fn new(size: [usize; 3], values: Vec<f32>) -> Self {
+        Self::build(size, values, None)
+    }
+    /// Loads a Mitsuba .vol file with one channel of 32-bit floats or 8-bit values (Mapped to
+    /// [0, 1]). The bounding box in the file is ignored
+    pub fn load_vol(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let data = std::fs::read(path).map_err(|err| err.to_string())?;
+        if data.len() < 48 || &data[..3] != b"VOL" || data[3] != 3 {
+            return Err("not a version 3 .vol file".to_string());
+        }
+        let int = |i: usize| i32::from_le_bytes([data[i], data[i + 1], data[i + 2], data[i + 3]]);
+        let (encoding, channels) = (int(4), int(20));
+        let size = [int(8), int(12), int(16)];
+        if size.iter().any(|&n| n <= 0) {
+            return Err("invalid .vol size".to_string());
+        }
+        if channels != 1 {
+            return Err("only .vol files with one channel are supported".to_string());
+        }
+        let size = [size[0] as usize, size[1] as usize, size[2] as usize];
+        let count = grid_count(size).ok_or("invalid .vol size")?;
+        let body = &data[48..];
+        let values = match encoding {
+            1 if body.len() / 4 >= count => body
+                .chunks_exact(4)
+                .take(count)
+                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect(),
+            3 if body.len() >= count => body[..count].iter().map(|&x| x as f32 / 255.0).collect(),
+            1 | 3 => return Err("the .vol file is truncated".to_string()),
+            _ => return Err("only float32 and uint8 .vol files are supported".to_string()),
+        };
+        let source = GridDesc::Vol {
+            path: path.to_path_buf(),
+        };
+        Ok(Self::build(size, values, Some(source)))
+    }
+    /// Loads 8-bit densities without a header, mapped to [0, 1]
+    pub fn load_raw(path: impl AsRef<Path>, size: [usize; 3]) -> Result<Self, String> {
+        let path = path.as_ref();
+        let data = std::fs::read(path).map_err(|err| err.to_string())?;
+        let count = match grid_count(size) {
+            Some(count) if count > 0 => count,
+            _ => return Err("invalid grid size".to_string()),
+        };
+        if data.len() != count {
+            return Err(format!(
+                "expected {} bytes for a {}x{}x{} grid, got {}",
+                count,
+                size[0],
+                size[1],
+                size[2],
+                data.len()
+            ));
+        }
+        let values = data.iter().map(|&x| x as f32 / 255.0).collect();
+        let source = GridDesc::Raw {
+            path: PathBuf::from(path),
+            size,
+        };
+        Ok(Self::build(size, values, Some(source)))
+    }
+    /// A puffy cloud made of Perlin noise of frequency `scale`, fading out before the sides of the
+    /// grid. The same seed always gives the same cloud
+    pub fn noise(resolution: usize, scale: f64, seed: u64) -> Self {
+        let perlin = Perlin::new(&mut RandState::from_seed(seed));
+        let mut values = Vec::with_capacity(resolution.pow(3));
+        for k in 0..resolution {
+            for j in 0..resolution {
+                for i in 0..resolution {
+                    let coord = |i: usize| (i as f64 + 0.5) / resolution as f64;
+                    let p = Vec3::new(coord(i), coord(j), coord(k));
+                    let falloff = 1.0 - 2.5 * (p - Vec3::repeat(0.5)).length();
+                    let density = falloff + 0.5 * perlin.noise(scale * p);
+                    values.push(density.clamp(0.0, 1.0) as f32);
+                }
+            }
+        }
+        let source = GridDesc::Noise {
+            resolution,
+            scale,
+            seed,
+        };
+        Self::build([resolution; 3], values, Some(source))
+    }
+
+    pub fn size(&self) -> [usize; 3] {
+        self.size
+    }
+    pub fn max_density(&self) -> f64 {
+        self.max
+    }
+    /// Trilinear interpolation between the voxel centers, where `p` goes from 0 to 1 across the
+    /// whole grid. Outside of it the density is 0
+    pub fn density(&self, p: Vec3) -> f64 {
+        if p.0.iter().any(|x| !(0.0..=1.0).contains(x)) {
+            return 0.0;
+        }
+        let mut corner = [0; 3];
+        let mut frac = [0.0; 3];
+        for axis in 0..3 {
+            let n = self.size[axis];
+            let x = (p.0[axis] * n as f64 - 0.5).clamp(0.0, (n - 1) as f64);
+            corner[axis] = (x as usize).min(n.saturating_sub(2));
+            frac[axis] = x - corner[axis] as f64;
+        }
+        let value = |i: usize, j: usize, k: usize| {
+            let [i, j, k] = [
+                (corner[0] + i).min(self.size[0] - 1),
+                (corner[1] + j).min(self.size[1] - 1),
+                (corner[2] + k).min(self.size[2] - 1),
+            ];
+            self.values[(k * self.size[1] + j) * self.size[0] + i] as f64
+        };
+        let lerp = |a: f64, b: f64, t: f64| a + t * (b - a);
+        let [fx, fy, fz] = frac;
+        let plane = |k| {
+            lerp(
+                lerp(value(0, 0, k), value(1, 0, k), fx),
+                lerp(value(0, 1, k), value(1, 1, k), fx),
+                fy,
+            )
+        };
+        lerp(plane(0), plane(1), fz)
+    }
+    pub fn describe(&self) -> Option<GridDesc> {
+        self.source.clone()
+    }
+}
+
+/// Volume with a density that changes inside a box, given by a `DensityGrid` stretched over it and
+/// multiplied by `density`
+///
+/// Uses delta tracking: the ray takes steps as if the whole box had the highest density, and
+/// stops at each step with the probability of the real density there over the highest one
+pub struct GridMedium {
+    grid: Arc<DensityGrid>,
+    bounds: Aabb,
+    density: f64,
+    phase_function: MaterialType,
+}
+impl GridMedium {
+    pub fn new(
+        grid: Arc<DensityGrid>,
+        bounds: Aabb,
+        density: f64,
+        phase_function: MaterialType,
+    ) -> Self {
+        Self {
+            grid,
+            bounds,
+            density,
+            phase_function,
+        }
+    }
+    /// Density at a point in the world
+    pub fn density_at(&self, p: Vec3) -> f64 {
+        let (min, max) = (self.bounds.min, self.bounds.max);
+        let local = |axis: usize| (p.0[axis] - min.0[axis]) / (max.0[axis] - min.0[axis]);
+        self.density * self.grid.density(Vec3::new(local(0), local(1), local(2)))
+    }
+}
+impl Hittable for GridMedium {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, out: &mut HitRecord) -> bool {
+        let (t_enter, t_exit) = match self.bounds.interval(ray, t_min, t_max) {
+            Some(interval) => interval,
+            None => return false,
+        };
+        let majorant = self.density * self.grid.max_density();
+        if majorant <= 0.0 {
+            return false;
+        }
+
+        let rand = &mut RandState::from_seed(ray_seed(ray));
+        // Steps in t, which doesn't have the same scale as distances
+        let step_scale = 1.0 / (majorant * ray.dir.length());
+        let mut t = t_enter;
+        loop {
+            t -= (1.0 - rand.random_double()).ln() * step_scale;
+            if t >= t_exit {
+                return false;
+            }
+            let p = ray.at(t);
+            // Otherwise it's a null collision, and the ray keeps going
+            if rand.random_double() * majorant < self.density_at(p) {
+                out.t = t;
+                out.p = p;
+                out.normal = Vec3::new(1.0, 0.0, 0.0);
+                out.front_face = true;
+                out.u = 0.0;
+                out.v = 0.0;
+                out.material = self.phase_function.clone();
+                return true;
+            }
+        }
+    }
+    fn bounding_box(&self, output_box: &mut Aabb) -> bool {
+        *output_box = self.bounds;
+        true
+    }
+    fn describe(&self) -> Option<ObjectDesc> {
+        Some(ObjectDesc::GridMedium {
+            grid: self.grid.describe()?,
+            min: self.bounds.min,
+            max: self.bounds.max,
+            density: self.density,
+            material: self.phase_function.describe()?,
+        })
+    }
+}
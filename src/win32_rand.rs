@@ -68,6 +68,10 @@ impl RandState {
         ret.reload_buf();
         ret
     }
+    /// BCryptGenRandom can't be seeded, so the seed is ignored and the numbers are never reproducible
+    pub fn from_seed(_seed: u64) -> Self {
+        Self::new()
+    }
 
     fn gen_random_bytes(&mut self, buf: &mut [u8]) {
         let to_copy = buf.len();
@@ -82,6 +86,12 @@ impl RandState {
         }
     }
 
+    pub fn next_u64(&mut self) -> u64 {
+        let mut random_bytes = [0u8; 8];
+        self.gen_random_bytes(&mut random_bytes[..]);
+        u64::from_ne_bytes(random_bytes)
+    }
+
     pub fn random_double(&mut self) -> f64 {
         // Gen random bytes
         let mut random_bytes = [0u8; 8];
@@ -102,9 +112,6 @@ impl RandState {
         // Combine them and floor so it's never 1
         f64::from_bits(fraction | exponent_bits) - (1.0 - f64::EPSILON / 2.0)
     }
-    pub fn random_double_range(&mut self, min: f64, max: f64) -> f64 {
-        min + (max - min) * self.random_double()
-    }
 }
 
 impl Drop for RandState {
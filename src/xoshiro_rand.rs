@@ -0,0 +1,57 @@
+/// xoshiro256++ (https://prng.di.unimi.it/), seeded using splitmix64
+///
+/// Pure rust, so every RandState has its own state and the same seed gives the same numbers on
+/// every platform
+#[derive(Debug, Clone)]
+pub struct RandState {
+    s: [u64; 4],
+}
+/// Seed used by RandState::new, so that runs are reproducible by default
+const DEFAULT_SEED: u64 = 0x5EED;
+
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+impl Default for RandState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl RandState {
+    pub fn new() -> Self {
+        Self::from_seed(DEFAULT_SEED)
+    }
+    pub fn from_seed(seed: u64) -> Self {
+        let mut sm = seed;
+        Self {
+            s: [
+                splitmix64(&mut sm),
+                splitmix64(&mut sm),
+                splitmix64(&mut sm),
+                splitmix64(&mut sm),
+            ],
+        }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let s = &mut self.s;
+        let result = s[0].wrapping_add(s[3]).rotate_left(23).wrapping_add(s[0]);
+        let t = s[1] << 17;
+        s[2] ^= s[0];
+        s[3] ^= s[1];
+        s[1] ^= s[2];
+        s[0] ^= s[3];
+        s[2] ^= t;
+        s[3] = s[3].rotate_left(45);
+        result
+    }
+    /// Uniform in [0, 1), using the top 53 bits
+    pub fn random_double(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 * (1.0 / (1u64 << 53) as f64)
+    }
+}
@@ -0,0 +1,123 @@
+use rtweekend::{
+    render, render_counting_samples, render_hdr, sample_heatmap,
+    scene_file::{parse_scene, write_scene},
+    scenes, Camera, HittableList, RandState, RenderParams, Vec3,
+};
+use std::path::Path;
+
+fn camera() -> Camera {
+    Camera::new(
+        Vec3::new(0.0, 0.0, 0.0),
+        Vec3::new(0.0, 0.0, -1.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        90.0,
+        3.0 / 2.0,
+        0.0,
+        1.0,
+    )
+}
+
+fn params(noise_threshold: f64) -> RenderParams {
+    RenderParams {
+        image_width: 24,
+        image_height: 16,
+        samples_per_px: 64,
+        min_samples_per_px: 8,
+        noise_threshold,
+        max_depth: 10,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn off_by_default() {
+    let rand = &mut RandState::from_seed(1);
+    let image = render(
+        camera(),
+        scenes::normal_scene(),
+        &HittableList::new(),
+        params(0.0),
+        rand,
+    );
+    let rand = &mut RandState::from_seed(1);
+    let (counted, samples) = render_counting_samples(
+        camera(),
+        scenes::normal_scene(),
+        &HittableList::new(),
+        params(0.0),
+        rand,
+    );
+    assert_eq!(image, counted);
+    assert_eq!(samples.len(), 24 * 16);
+    assert!(samples.iter().all(|&samples| samples == 64));
+}
+
+#[test]
+fn noisy_pixels_get_more_samples() {
+    let rand = &mut RandState::from_seed(2);
+    let (_, samples) = render_counting_samples(
+        camera(),
+        scenes::normal_scene(),
+        &HittableList::new(),
+        params(0.05),
+        rand,
+    );
+    assert!(samples.iter().all(|&samples| (8..=64).contains(&samples)));
+    // The sky at the top converges right away, and the diffuse ground and spheres take longer
+    assert!(samples[..24].iter().all(|&samples| samples == 8));
+    assert!(samples.contains(&64));
+    let total: u32 = samples.iter().sum();
+    assert!(total < 24 * 16 * 64 * 3 / 4, "{}", total);
+
+    // The image is still the same, only noisier where it stopped early
+    let mean = |noise_threshold, seed| {
+        let rand = &mut RandState::from_seed(seed);
+        let image = render_hdr(
+            camera(),
+            scenes::normal_scene(),
+            &HittableList::new(),
+            params(noise_threshold),
+            rand,
+        );
+        image.iter().sum::<f32>() / image.len() as f32
+    };
+    let (full, adaptive) = (mean(0.0, 3), mean(0.05, 3));
+    assert!(
+        (full - adaptive).abs() < 0.01 * full,
+        "{} {}",
+        full,
+        adaptive
+    );
+}
+
+#[test]
+fn heatmap() {
+    let pixels = sample_heatmap(&[0, 32, 48, 64, 100], 64);
+    assert_eq!(
+        pixels,
+        [0, 0, 0, 255, 128, 0, 255, 255, 64, 255, 255, 255, 255, 255, 255]
+    );
+}
+
+#[test]
+fn scene_file_adaptive() {
+    let source = "render width 10 height 10 samples 256 adaptive 16 0.05\n";
+    let scene = parse_scene("adaptive.scene", source, Path::new("")).unwrap();
+    assert_eq!(scene.params.samples_per_px, 256);
+    assert_eq!(scene.params.min_samples_per_px, 16);
+    assert_eq!(scene.params.noise_threshold, 0.05);
+    let written = write_scene(&scene.camera, &scene.params, &scene.world).unwrap();
+    assert!(written.contains("adaptive 16 0.05"));
+    let parsed = parse_scene("written.scene", &written, Path::new("")).unwrap();
+    assert_eq!(parsed.params.noise_threshold, 0.05);
+
+    let error = parse_scene(
+        "test.scene",
+        "render width 10 height 10 adaptive 16 -1\n",
+        Path::new(""),
+    )
+    .err()
+    .expect("should fail")
+    .message;
+    assert_eq!(error, "the noise threshold can't be negative");
+}
@@ -0,0 +1,69 @@
+use rtweekend::{bvh::Aabb, scenes, Bvh, HitRecord, Hittable, RandState, Ray, Vec3};
+
+#[test]
+fn bvh_matches_list() {
+    let list = scenes::perf_scene();
+    let bvh = Bvh::new(scenes::perf_scene());
+
+    let mut list_box = Aabb::empty();
+    let mut bvh_box = Aabb::empty();
+    assert!(list.bounding_box(&mut list_box));
+    assert!(bvh.bounding_box(&mut bvh_box));
+    assert_eq!(list_box.min.0, bvh_box.min.0);
+    assert_eq!(list_box.max.0, bvh_box.max.0);
+
+    let orig = Vec3::new(13.0, 2.0, 3.0);
+    let mut hits = 0;
+    for i in 0..64 {
+        for j in 0..64 {
+            let target = Vec3::new(0.0, i as f64 / 16.0 - 1.0, j as f64 / 8.0 - 4.0);
+            let ray = Ray {
+                orig,
+                dir: target - orig,
+                time: 0.0,
+            };
+            let (list_rec, bvh_rec) = (&mut HitRecord::default(), &mut HitRecord::default());
+            let list_hit = list.hit(&ray, 0.001, f64::INFINITY, list_rec);
+            let bvh_hit = bvh.hit(&ray, 0.001, f64::INFINITY, bvh_rec);
+            assert_eq!(list_hit, bvh_hit);
+            if list_hit {
+                // Both find the closest hit
+                assert_eq!(list_rec.t(), bvh_rec.t());
+                assert_eq!(list_rec.normal().0, bvh_rec.normal().0);
+            }
+            hits += list_hit as u32;
+        }
+    }
+    assert!(hits > 0);
+}
+
+#[test]
+fn unbounded_objects() {
+    // The ground of the random scene is a plane, which can't go in the tree
+    let rand = &mut RandState::from_seed(3);
+    let list = scenes::random_scene(rand);
+    let bvh = Bvh::new(scenes::random_scene(&mut RandState::from_seed(3)));
+    assert!(!list.bounding_box(&mut Aabb::empty()));
+    assert!(!bvh.bounding_box(&mut Aabb::empty()));
+
+    let orig = Vec3::new(13.0, 2.0, 3.0);
+    let mut ground_hits = 0;
+    for i in 0..32 {
+        for j in 0..32 {
+            let target = Vec3::new(0.0, i as f64 / 8.0 - 2.0, j as f64 / 4.0 - 4.0);
+            let ray = Ray {
+                orig,
+                dir: target - orig,
+                time: 0.0,
+            };
+            // Same hits for every t_max means the same closest hit
+            for &t_max in [0.5, 0.9, 1.0, 1.5, f64::INFINITY].iter() {
+                let list_hit = list.hit(&ray, 0.001, t_max, &mut HitRecord::default());
+                let bvh_hit = bvh.hit(&ray, 0.001, t_max, &mut HitRecord::default());
+                assert_eq!(list_hit, bvh_hit);
+            }
+            ground_hits += (target.y() < 0.0) as u32;
+        }
+    }
+    assert!(ground_hits > 0);
+}
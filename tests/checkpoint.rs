@@ -0,0 +1,108 @@
+use rtweekend::{
+    sampler::SamplerKind, scenes, Camera, HittableList, ProgressiveRender, RandState, RenderParams,
+    Vec3,
+};
+
+fn camera() -> Camera {
+    Camera::new(
+        Vec3::new(0.0, 0.0, 0.0),
+        Vec3::new(0.0, 0.0, -1.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        90.0,
+        3.0 / 2.0,
+        0.0,
+        1.0,
+    )
+}
+
+fn params() -> RenderParams {
+    RenderParams {
+        image_width: 24,
+        image_height: 16,
+        samples_per_px: 40,
+        min_samples_per_px: 8,
+        noise_threshold: 0.05,
+        sampler: SamplerKind::Sobol,
+        max_depth: 10,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn resume_gives_the_same_image() {
+    let lights = HittableList::new();
+    let rand = &mut RandState::from_seed(1);
+    let mut uninterrupted =
+        ProgressiveRender::new(camera(), scenes::normal_scene(), &lights, params(), rand);
+    uninterrupted.render_passes(5, |_| true);
+
+    let rand = &mut RandState::from_seed(1);
+    let mut interrupted =
+        ProgressiveRender::new(camera(), scenes::normal_scene(), &lights, params(), rand);
+    interrupted.render_passes(5, |progressive| progressive.passes() < 2);
+    let mut checkpoint = Vec::new();
+    interrupted.write_checkpoint(&mut checkpoint).unwrap();
+    drop(interrupted);
+
+    let mut resumed = ProgressiveRender::from_checkpoint(
+        camera(),
+        scenes::normal_scene(),
+        &lights,
+        params(),
+        &checkpoint,
+    )
+    .unwrap();
+    assert_eq!(resumed.passes(), 2);
+    resumed.render_passes(5, |_| true);
+    assert_eq!(resumed.passes(), 5);
+    assert_eq!(resumed.samples(), uninterrupted.samples());
+    assert_eq!(resumed.image_hdr(), uninterrupted.image_hdr());
+}
+
+#[test]
+fn checkpoint_files() {
+    let dir = std::env::temp_dir().join("rtweekend_checkpoint_test");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("render.ckpt");
+    let lights = HittableList::new();
+    let rand = &mut RandState::from_seed(2);
+    let mut progressive =
+        ProgressiveRender::new(camera(), scenes::normal_scene(), &lights, params(), rand);
+    progressive.render_pass(4);
+    progressive.save_checkpoint(&path).unwrap();
+    let resumed =
+        ProgressiveRender::resume(camera(), scenes::normal_scene(), &lights, params(), &path)
+            .unwrap();
+    assert_eq!(resumed.image(), progressive.image());
+
+    let error = |checkpoint: &[u8], params| match ProgressiveRender::from_checkpoint(
+        camera(),
+        scenes::normal_scene(),
+        &lights,
+        params,
+        checkpoint,
+    ) {
+        Ok(_) => panic!("should fail"),
+        Err(err) => err,
+    };
+    let checkpoint = std::fs::read(&path).unwrap();
+    assert_eq!(
+        error(&checkpoint[..checkpoint.len() - 1], params()),
+        "the checkpoint is truncated"
+    );
+    assert_eq!(error(b"P3\n24 16\n255\n", params()), "not a checkpoint");
+    let mut old_version = checkpoint.clone();
+    old_version[b"rtweekend checkpoint ".len()] = b'1';
+    assert_eq!(
+        error(&old_version, params()),
+        "the checkpoint is from another version of rtweekend"
+    );
+    let more_samples = RenderParams {
+        samples_per_px: 80,
+        ..params()
+    };
+    assert_eq!(
+        error(&checkpoint, more_samples),
+        "the checkpoint is of a render with a different camera or parameters"
+    );
+}
@@ -0,0 +1,208 @@
+use rtweekend::{
+    cli::{parse_args, OutputFormat, SceneSource},
+    sampler::SamplerKind,
+    tiles::TileOrder,
+    Vec3,
+};
+use std::path::PathBuf;
+use std::time::Duration;
+
+fn args(line: &str) -> Vec<String> {
+    line.split_whitespace().map(String::from).collect()
+}
+
+#[test]
+fn parse_flags() {
+    let parsed = parse_args(args(
+        "--scene moon --width 300 --samples 10 --depth 5 --lookfrom -2,2,1 --fov 40 --seed 7 --output out.ppm",
+    ))
+    .unwrap();
+    assert_eq!(parsed.scene, Some(SceneSource::Builtin("moon".to_string())));
+    assert_eq!(parsed.width, Some(300));
+    assert_eq!(parsed.samples, Some(10));
+    assert_eq!(parsed.depth, Some(5));
+    assert_eq!(parsed.lookfrom, Some(Vec3::new(-2.0, 2.0, 1.0)));
+    assert_eq!(parsed.fov, Some(40.0));
+    assert_eq!(parsed.seed, Some(7));
+    assert!(!parsed.help);
+
+    let job = parsed.into_job().unwrap();
+    assert_eq!(job.params.image_width, 300);
+    assert_eq!(job.params.image_height, 200);
+    assert_eq!(job.params.samples_per_px, 10);
+    assert_eq!(job.camera.lookfrom, Vec3::new(-2.0, 2.0, 1.0));
+    assert_eq!(job.camera.vertical_fov_degrees, 40.0);
+    assert_eq!(job.format, OutputFormat::Ppm);
+
+    let job = parse_args(args("--scene normal --output image.PNG"))
+        .unwrap()
+        .into_job()
+        .unwrap();
+    assert_eq!(job.format, OutputFormat::Png);
+
+    assert!(parse_args(args("--help")).unwrap().help);
+
+    let job = parse_args(args("--scene bouncing --shutter 0.25,0.5"))
+        .unwrap()
+        .into_job()
+        .unwrap();
+    assert_eq!(
+        (job.camera.shutter_open, job.camera.shutter_close),
+        (0.25, 0.5)
+    );
+    let job = parse_args(args("--scene bouncing"))
+        .unwrap()
+        .into_job()
+        .unwrap();
+    assert_eq!(
+        (job.camera.shutter_open, job.camera.shutter_close),
+        (0.0, 1.0)
+    );
+
+    let job = parse_args(args("--scene normal --roulette 3,0.8"))
+        .unwrap()
+        .into_job()
+        .unwrap();
+    assert_eq!(
+        (job.params.roulette_depth, job.params.roulette_probability),
+        (Some(3), 0.8)
+    );
+
+    let job = parse_args(args(
+        "--scene normal --samples 256 --adaptive 16,0.05 --heatmap samples.png",
+    ))
+    .unwrap()
+    .into_job()
+    .unwrap();
+    assert_eq!(job.params.samples_per_px, 256);
+    assert_eq!(job.params.min_samples_per_px, 16);
+    assert_eq!(job.params.noise_threshold, 0.05);
+    assert_eq!(
+        job.heatmap,
+        Some((PathBuf::from("samples.png"), OutputFormat::Png))
+    );
+
+    let job = parse_args(args("--scene normal --sampler sobol"))
+        .unwrap()
+        .into_job()
+        .unwrap();
+    assert_eq!(job.params.sampler, SamplerKind::Sobol);
+    assert_eq!(job.passes, 1);
+
+    let job = parse_args(args("--scene normal --passes 4 --output out.png"))
+        .unwrap()
+        .into_job()
+        .unwrap();
+    assert_eq!(job.passes, 4);
+    assert_eq!(job.checkpoint, None);
+
+    let job = parse_args(args(
+        "--scene normal --passes 4 --output out.png --checkpoint out.ckpt",
+    ))
+    .unwrap()
+    .into_job()
+    .unwrap();
+    assert_eq!(
+        job.checkpoint,
+        Some((PathBuf::from("out.ckpt"), Duration::from_secs(300)))
+    );
+    assert!(job.previews);
+
+    // Checkpoints without --passes save every few minutes, between passes of one sample
+    let job = parse_args(args(
+        "--scene normal --samples 64 --checkpoint out.ckpt --checkpoint-every 60",
+    ))
+    .unwrap()
+    .into_job()
+    .unwrap();
+    assert_eq!(job.passes, 64);
+    assert!(!job.previews);
+    assert_eq!(
+        job.checkpoint,
+        Some((PathBuf::from("out.ckpt"), Duration::from_secs(60)))
+    );
+
+    let job = parse_args(args("--scene normal --tiles 16,spiral"))
+        .unwrap()
+        .into_job()
+        .unwrap();
+    assert_eq!(
+        (job.params.tile_size, job.params.tile_order),
+        (16, TileOrder::Spiral)
+    );
+}
+
+#[test]
+fn image_size() {
+    let size = |line: &str| {
+        let job = parse_args(args(line)).unwrap().into_job().unwrap();
+        (job.params.image_width, job.params.image_height)
+    };
+    assert_eq!(size("--scene normal"), (1200, 800));
+    assert_eq!(size("--scene normal --height 100"), (150, 100));
+    assert_eq!(size("--scene normal --width 400 --aspect 2"), (400, 200));
+    assert_eq!(size("--scene normal --width 64 --height 48"), (64, 48));
+}
+
+#[test]
+fn parse_errors() {
+    let error = |line: &str| match parse_args(args(line)).and_then(|parsed| parsed.into_job()) {
+        Ok(_) => panic!("'{}' should be an error", line),
+        Err(err) => err,
+    };
+    assert_eq!(error("--scene nope"), "unknown scene 'nope'");
+    assert_eq!(error("--bogus 1"), "unknown option '--bogus'");
+    assert_eq!(error("--width"), "missing value for --width");
+    assert_eq!(error("--width abc"), "invalid value 'abc' for --width");
+    assert_eq!(error("--samples 0"), "--samples must be greater than 0");
+    assert_eq!(error("--format gif"), "unknown format 'gif'");
+    assert_eq!(
+        error("--lookat 1,2"),
+        "invalid value '1,2' for --lookat (Expected X,Y,Z)"
+    );
+    assert_eq!(
+        error("--fov 180"),
+        "--fov must be between 0 and 180 degrees"
+    );
+    assert_eq!(
+        error("--scene normal --width 1"),
+        "the image is too small (1x0)"
+    );
+    assert_eq!(
+        error("--scene normal --lookfrom 0,0,-1"),
+        "the camera can't look at its own position"
+    );
+    assert_eq!(
+        error("--shutter 1,0"),
+        "the shutter can't close before it opens"
+    );
+    assert_eq!(
+        error("--roulette 5"),
+        "invalid value '5' for --roulette (Expected DEPTH,P)"
+    );
+    assert_eq!(
+        error("--roulette 5,1.5"),
+        "--roulette probability must be between 0 and 1"
+    );
+    assert_eq!(
+        error("--adaptive 16,0"),
+        "--adaptive must be greater than 0"
+    );
+    assert_eq!(
+        error("--heatmap samples.exr"),
+        "the heatmap can only be written as png or ppm"
+    );
+    assert_eq!(error("--sampler random"), "unknown sampler 'random'");
+    assert_eq!(error("--passes 4"), "--passes needs --output");
+    assert_eq!(error("--tiles 0,spiral"), "--tiles must be greater than 0");
+    assert_eq!(error("--tiles 16,zigzag"), "unknown tile order 'zigzag'");
+    assert_eq!(
+        error("--tiles 16"),
+        "invalid value '16' for --tiles (Expected SIZE,ORDER)"
+    );
+    assert_eq!(error("--passes 0"), "--passes must be greater than 0");
+    assert_eq!(
+        error("--passes 1 --checkpoint out.ckpt"),
+        "--checkpoint needs more than one pass"
+    );
+}
@@ -0,0 +1,180 @@
+use rtweekend::{
+    hdr, render, render_hdr, scenes, Camera, HittableList, RandState, RenderParams, Vec3,
+};
+use std::convert::TryInto;
+
+const WIDTH: u32 = 24;
+const HEIGHT: u32 = 16;
+
+fn camera() -> Camera {
+    Camera::new(
+        Vec3::new(-2.0, 2.0, 1.0),
+        Vec3::new(0.0, 0.0, -1.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        40.0,
+        WIDTH as f64 / HEIGHT as f64,
+        0.0,
+        1.0,
+    )
+}
+fn params() -> RenderParams {
+    RenderParams {
+        image_width: WIDTH as i64,
+        image_height: HEIGHT as i64,
+        samples_per_px: 4,
+        ..Default::default()
+    }
+}
+
+fn f32_at(bytes: &[u8], offset: usize) -> f32 {
+    f32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+#[test]
+fn hdr_matches_ldr() {
+    let ldr = render(
+        camera(),
+        scenes::normal_scene(),
+        &HittableList::new(),
+        params(),
+        &mut RandState::from_seed(3),
+    );
+    let hdr = render_hdr(
+        camera(),
+        scenes::normal_scene(),
+        &HittableList::new(),
+        params(),
+        &mut RandState::from_seed(3),
+    );
+    assert_eq!(ldr.len(), hdr.len());
+    for (ldr, hdr) in ldr.iter().zip(&hdr) {
+        assert!(*hdr >= 0.0);
+        // Only off by one when the f32 rounding lands on the other side of a quantization step
+        let quantized = ((hdr.sqrt().clamp(0.0, 0.99)) * 256.0) as i32;
+        assert!((quantized - *ldr as i32).abs() <= 1, "{} vs {}", hdr, ldr);
+    }
+}
+
+fn test_pixels() -> Vec<f32> {
+    (0..WIDTH * HEIGHT * 3)
+        .map(|i| (i % 7) as f32 * 0.75 + (i / 50) as f32 * 3.0)
+        .collect()
+}
+
+#[test]
+fn pfm() {
+    let pixels = test_pixels();
+    let mut file = vec![];
+    hdr::write_pfm(&mut file, WIDTH, HEIGHT, &pixels).unwrap();
+    let header = format!("PF\n{} {}\n-1.0\n", WIDTH, HEIGHT);
+    assert!(file.starts_with(header.as_bytes()));
+    let data = &file[header.len()..];
+    assert_eq!(data.len(), pixels.len() * 4);
+    let stride = 3 * WIDTH as usize;
+    for (i, value) in pixels.iter().enumerate() {
+        // PFM starts from the bottom row
+        let (row, col) = (i / stride, i % stride);
+        let flipped = (HEIGHT as usize - 1 - row) * stride + col;
+        assert_eq!(f32_at(data, flipped * 4), *value);
+    }
+}
+
+#[test]
+fn rgbe() {
+    let mut pixels = test_pixels();
+    pixels[0] = 0.0;
+    pixels[1] = 0.0;
+    pixels[2] = 0.0;
+    let mut file = vec![];
+    hdr::write_rgbe(&mut file, WIDTH, HEIGHT, &pixels).unwrap();
+    let header = format!(
+        "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+        HEIGHT, WIDTH
+    );
+    assert!(file.starts_with(header.as_bytes()));
+    let data = &file[header.len()..];
+    assert_eq!(data.len(), pixels.len() / 3 * 4);
+    assert_eq!(&data[..4], &[0; 4]);
+    for (pixel, encoded) in pixels.chunks(3).zip(data.chunks(4)).skip(1) {
+        let scale = 2f32.powi(encoded[3] as i32 - 128 - 8);
+        let max = pixel.iter().cloned().fold(0.0, f32::max);
+        for c in 0..3 {
+            let decoded = encoded[c] as f32 * scale;
+            assert!(decoded <= pixel[c] && pixel[c] - decoded <= max / 128.0);
+        }
+    }
+}
+
+#[test]
+fn exr() {
+    let pixels = test_pixels();
+    let mut file = vec![];
+    hdr::write_exr(&mut file, WIDTH, HEIGHT, &pixels).unwrap();
+    assert_eq!(&file[..8], &[0x76, 0x2f, 0x31, 0x01, 2, 0, 0, 0]);
+
+    // Read the attributes until the empty name that ends the header
+    let mut pos = 8;
+    let mut names = vec![];
+    let read_str = |pos: &mut usize| {
+        let end = *pos + file[*pos..].iter().position(|&b| b == 0).unwrap();
+        let s = String::from_utf8(file[*pos..end].to_vec()).unwrap();
+        *pos = end + 1;
+        s
+    };
+    loop {
+        let name = read_str(&mut pos);
+        if name.is_empty() {
+            break;
+        }
+        let kind = read_str(&mut pos);
+        let size = i32::from_le_bytes(file[pos..pos + 4].try_into().unwrap()) as usize;
+        if name == "dataWindow" {
+            let coord =
+                |i: usize| i32::from_le_bytes(file[pos + 4 + 4 * i..][..4].try_into().unwrap());
+            assert_eq!(
+                [coord(0), coord(1), coord(2), coord(3)],
+                [0, 0, WIDTH as i32 - 1, HEIGHT as i32 - 1]
+            );
+        }
+        names.push((name, kind));
+        pos += 4 + size;
+    }
+    for required in &[
+        ("channels", "chlist"),
+        ("compression", "compression"),
+        ("dataWindow", "box2i"),
+        ("displayWindow", "box2i"),
+        ("lineOrder", "lineOrder"),
+        ("pixelAspectRatio", "float"),
+        ("screenWindowCenter", "v2f"),
+        ("screenWindowWidth", "float"),
+    ] {
+        assert!(names
+            .iter()
+            .any(|(name, kind)| name == required.0 && kind == required.1));
+    }
+
+    // Follow the offset table to every scanline, which stores B, G and R one after the other
+    let w = WIDTH as usize;
+    for y in 0..HEIGHT as usize {
+        let offset = u64::from_le_bytes(file[pos + 8 * y..][..8].try_into().unwrap()) as usize;
+        assert_eq!(
+            i32::from_le_bytes(file[offset..offset + 4].try_into().unwrap()),
+            y as i32
+        );
+        let size = i32::from_le_bytes(file[offset + 4..offset + 8].try_into().unwrap());
+        assert_eq!(size as usize, 4 * 3 * w);
+        for x in 0..w {
+            for (channel_idx, channel) in [2, 1, 0].iter().enumerate() {
+                let value = f32_at(&file, offset + 8 + 4 * (channel_idx * w + x));
+                assert_eq!(value, pixels[3 * (y * w + x) + channel]);
+            }
+        }
+    }
+    let last = u64::from_le_bytes(
+        file[pos + 8 * (HEIGHT as usize - 1)..][..8]
+            .try_into()
+            .unwrap(),
+    );
+    assert_eq!(file.len(), last as usize + 8 + 4 * 3 * w);
+}
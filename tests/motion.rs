@@ -0,0 +1,75 @@
+use rtweekend::{
+    bvh::Aabb, material::LambertianDiffuse, render, scene_file::parse_scene, HitRecord, Hittable,
+    HittableList, MovingSphere, RandState, Ray, Vec3,
+};
+use std::path::Path;
+
+#[test]
+fn moving_sphere() {
+    let sphere = MovingSphere::new(
+        (Vec3::new(0.0, 0.0, 0.0), 1.0),
+        (Vec3::new(4.0, 0.0, 0.0), 3.0),
+        0.5,
+        LambertianDiffuse::new(Vec3::repeat(0.5)),
+    );
+    assert_eq!(sphere.center(2.0), Vec3::new(2.0, 0.0, 0.0));
+    // It stays still outside of its interval
+    assert_eq!(sphere.center(0.0), Vec3::new(0.0, 0.0, 0.0));
+    assert_eq!(sphere.center(5.0), Vec3::new(4.0, 0.0, 0.0));
+
+    // Straight down at x
+    let hits = |x: f64, time: f64| {
+        let ray = Ray {
+            orig: Vec3::new(x, 5.0, 0.0),
+            dir: Vec3::new(0.0, -1.0, 0.0),
+            time,
+        };
+        let mut rec = HitRecord::default();
+        sphere.hit(&ray, 0.001, f64::INFINITY, &mut rec)
+    };
+    assert!(hits(0.0, 1.0));
+    assert!(!hits(0.0, 3.0));
+    assert!(hits(2.0, 2.0));
+    assert!(!hits(2.0, 1.0));
+    assert!(hits(4.0, 3.0));
+
+    let mut bbox = Aabb::empty();
+    assert!(sphere.bounding_box(&mut bbox));
+    assert_eq!(bbox.min, Vec3::new(-0.5, -0.5, -0.5));
+    assert_eq!(bbox.max, Vec3::new(4.5, 0.5, 0.5));
+}
+
+fn render_source(source: &str) -> Vec<u8> {
+    let scene = parse_scene("motion.scene", source, Path::new("")).unwrap();
+    render(
+        scene.camera.build(),
+        scene.world,
+        &HittableList::new(),
+        scene.params,
+        &mut RandState::from_seed(4),
+    )
+}
+
+#[test]
+fn motion_blur() {
+    let scene = |camera: &str, sphere: &str| {
+        format!(
+            "camera lookfrom 0 0 5 lookat 0 0 0 fov 40 {}
+render width 24 height 16 samples 8 depth 4
+material red lambertian 0.8 0.1 0.1
+{}
+",
+            camera, sphere
+        )
+    };
+    let moving = "moving_sphere -1 0 0 0  1 0 0 1  0.5 red";
+    let still = render_source(&scene("", "sphere -1 0 0 0.5 red"));
+    // Without a shutter interval every ray is cast at time 0
+    assert!(render_source(&scene("", moving)) == still);
+    let blurred = render_source(&scene("shutter 0 1", moving));
+    assert!(blurred != still);
+    assert!(render_source(&scene("shutter 0 1", moving)) == blurred);
+    // After the sphere stopped it's the same as a still sphere at the end
+    let end = render_source(&scene("", "sphere 1 0 0 0.5 red"));
+    assert!(render_source(&scene("shutter 1 1", moving)) == end);
+}
@@ -0,0 +1,217 @@
+use rtweekend::{
+    bvh::Aabb,
+    obj::{load_obj, parse_mtl, parse_obj},
+    scene_file::{MaterialDesc, TextureDesc},
+    HitRecord, Hittable, Ray, Vec3,
+};
+
+const CUBE_OBJ: &str = "
+# Unit cube, with a glass top and red sides
+mtllib cube.mtl
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+v 0 0 1
+v 1 0 1
+v 1 1 1
+v 0 1 1
+vt 0 0
+vt 1 0
+vt 1 1
+vt 0 1
+vn 0 0 -1
+vn 0 0 1
+s off
+usemtl red
+f 1/1/1 4/4/1 3/3/1 2/2/1
+f 5/1/2 6/2/2 7/3/2 8/4/2
+f 1 2 6 5
+f 4 8 7 3
+usemtl glass
+f -7 -6 -2 -3
+usemtl red
+f -8 -4 -1 -5
+";
+const CUBE_MTL: &str = "
+newmtl red
+Kd 0.8 0.1 0.1
+Ks 0.5 0.5 0.5
+illum 2
+
+newmtl glass
+Ni 1.45
+d 0.2
+";
+
+fn read_cube_mtl(name: &str) -> Result<String, String> {
+    match name {
+        "cube.mtl" => Ok(CUBE_MTL.to_string()),
+        _ => Err("not found".to_string()),
+    }
+}
+
+#[test]
+fn parse_cube() {
+    let meshes = parse_obj("cube.obj", CUBE_OBJ, read_cube_mtl).unwrap();
+    // One mesh per material
+    assert_eq!(meshes.len(), 2);
+
+    let mut bbox = Aabb::empty();
+    let mut total = Aabb::empty();
+    for mesh in &meshes {
+        assert!(mesh.bounding_box(&mut bbox));
+        total = total.surrounding(&bbox);
+    }
+    assert!(total.min.x() <= 0.0 && total.max.x() >= 1.0);
+    assert!(total.min.z() <= 0.0 && total.max.z() >= 1.0);
+
+    // Every side of the cube is there, at distance 1.5 from a point 2 units away from the center
+    let center = Vec3::repeat(0.5);
+    for dir in &[
+        Vec3::new(1.0, 0.0, 0.0),
+        Vec3::new(-1.0, 0.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        Vec3::new(0.0, -1.0, 0.0),
+        Vec3::new(0.0, 0.0, 1.0),
+        Vec3::new(0.0, 0.0, -1.0),
+    ] {
+        let ray = Ray {
+            orig: center + 2.0 * *dir,
+            dir: -*dir,
+            time: 0.0,
+        };
+        let hit_before = |t_max| {
+            meshes
+                .iter()
+                .any(|mesh| mesh.hit(&ray, 0.001, t_max, &mut HitRecord::default()))
+        };
+        assert!(hit_before(1.501));
+        assert!(!hit_before(1.499));
+    }
+}
+
+#[test]
+fn mtl_materials() {
+    // One triangle of each kind of material, side by side
+    let source = "
+mtllib kinds.mtl
+v 0 0 0
+v 1 0 0
+v 0 1 0
+usemtl matte
+f 1 2 3
+v 2 0 0
+v 3 0 0
+v 2 1 0
+usemtl mirror
+f 4 5 6
+v 4 0 0
+v 5 0 0
+v 4 1 0
+usemtl glass
+f 7 8 9
+v 6 0 0
+v 7 0 0
+v 6 1 0
+usemtl lamp
+f 10 11 12
+";
+    let mtl = "
+newmtl matte
+Kd 0.8 0.1 0.1
+
+newmtl mirror
+Kd 0 0 0
+Ks 0.9 0.8 0.7
+Ns 1000
+
+newmtl glass
+Kd 1 1 1
+Ni 1.5
+d 0.5
+
+newmtl lamp
+Kd 0.5 0.5 0.5
+Ke 4 4 4
+";
+    let meshes = parse_obj("kinds.obj", source, |_| Ok(mtl.to_string())).unwrap();
+    let material_at = |x| {
+        let ray = Ray {
+            orig: Vec3::new(x, 0.25, 1.0),
+            dir: Vec3::new(0.0, 0.0, -1.0),
+            time: 0.0,
+        };
+        let rec = &mut HitRecord::default();
+        assert!(meshes
+            .iter()
+            .any(|mesh| mesh.hit(&ray, 0.001, f64::INFINITY, rec)));
+        rec.material().describe().unwrap()
+    };
+    let solid = |color| TextureDesc::Solid { color };
+    assert_eq!(
+        material_at(0.25),
+        MaterialDesc::Lambertian {
+            albedo: solid(Vec3::new(0.8, 0.1, 0.1))
+        }
+    );
+    assert_eq!(
+        material_at(2.25),
+        MaterialDesc::Metal {
+            albedo: solid(Vec3::new(0.9, 0.8, 0.7)),
+            fuzzyness: 0.0
+        }
+    );
+    assert_eq!(
+        material_at(4.25),
+        MaterialDesc::Dielectric {
+            refraction_idx: 1.5
+        }
+    );
+    assert_eq!(
+        material_at(6.25),
+        MaterialDesc::DiffuseLight {
+            emit: Vec3::repeat(4.0)
+        }
+    );
+}
+
+#[test]
+fn parse_errors() {
+    let error = |source: &str| {
+        parse_obj("test.obj", source, read_cube_mtl)
+            .err()
+            .expect("should fail")
+    };
+
+    let err = error("v 0 0 0\nv 1 0\n");
+    assert_eq!(err.line, 2);
+    assert_eq!(err.to_string(), "test.obj:2: expected 3 numbers, got 2");
+
+    let err = error("v 0 0 0\nv 1 0 0\nv 0 1 0\n\nf 1 2 4\n");
+    assert_eq!(err.line, 5);
+    assert!(err.message.contains("position index 4 is out of range"));
+
+    assert_eq!(error("v 0 0 0\nv 1 0 x\n").line, 2);
+    assert_eq!(error("v 0 0 0\nf 1 1\n").line, 2);
+    assert_eq!(error("v 0 0 0\nf 1/2 1 1\n").line, 2);
+    assert_eq!(error("v 0 0 0\nf 1//a 1 1\n").line, 2);
+    assert_eq!(error("\n\nusemtl nope\n").line, 3);
+    assert_eq!(error("mtllib missing.mtl\n").line, 1);
+
+    let err = parse_mtl("test.mtl", "newmtl a\nKd 1 1\n").err().unwrap();
+    assert_eq!(err.to_string(), "test.mtl:2: expected 3 numbers, got 2");
+    assert_eq!(parse_mtl("test.mtl", "Kd 1 1 1\n").err().unwrap().line, 1);
+}
+
+#[test]
+fn load_cube() {
+    let dir = std::env::temp_dir().join("rtweekend_obj_test");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("cube.obj"), CUBE_OBJ).unwrap();
+    std::fs::write(dir.join("cube.mtl"), CUBE_MTL).unwrap();
+
+    assert_eq!(load_obj(dir.join("cube.obj")).unwrap().len(), 2);
+    let err = load_obj(dir.join("missing.obj")).err().unwrap();
+    assert_eq!(err.line, 0);
+}
@@ -0,0 +1,250 @@
+use rtweekend::{
+    material::{DiffuseLight, LambertianDiffuse, Metal},
+    pdf::{CosinePdf, HittablePdf, Pdf, SpherePdf},
+    planar::Quad,
+    render_hdr, scenes, Background, Camera, HitRecord, Hittable, HittableList, RandState, Ray,
+    RenderParams, Sphere, Vec3,
+};
+use std::f64::consts::PI;
+
+/// Integral of the density over every direction, estimated with uniform directions
+fn integral(pdf: &impl Pdf, rand: &mut RandState) -> f64 {
+    let n = 100000;
+    let sum: f64 = (0..n)
+        .map(|_| pdf.value(Vec3::random_unit_vector(rand)))
+        .sum();
+    4.0 * PI * sum / n as f64
+}
+
+#[test]
+fn cosine() {
+    let rand = &mut RandState::from_seed(1);
+    let normal = Vec3::new(0.0, 1.0, 0.0);
+    let pdf = CosinePdf::new(normal);
+    assert!((integral(&pdf, rand) - 1.0).abs() < 0.02);
+    assert!((integral(&SpherePdf, rand) - 1.0).abs() < 1e-9);
+    assert_eq!(pdf.value(-normal), 0.0);
+    assert!((pdf.value(Vec3::new(0.0, 5.0, 0.0)) - 1.0 / PI).abs() < 1e-12);
+
+    // The average cosine of cosine distributed directions is 2/3
+    let n = 100000;
+    let mut sum = 0.0;
+    for _ in 0..n {
+        let dir = pdf.generate(rand).unit_vector();
+        assert!(dir.dot(normal) >= 0.0);
+        sum += dir.dot(normal);
+    }
+    assert!((sum / n as f64 - 2.0 / 3.0).abs() < 0.01);
+}
+
+#[test]
+fn hittable() {
+    let rand = &mut RandState::from_seed(2);
+    let white = LambertianDiffuse::new(Vec3::repeat(0.5));
+    let origin = Vec3::new(0.2, -0.1, 0.0);
+    let rec = &mut HitRecord::default();
+
+    // A sphere is seen in a cone, where every direction has the same density
+    let sphere = Sphere::new(Vec3::new(0.0, 0.0, -2.0), 1.0, white.clone());
+    let pdf = HittablePdf::new(&sphere, origin);
+    let distance = (Vec3::new(0.0, 0.0, -2.0) - origin).length();
+    let solid_angle = 2.0 * PI * (1.0 - (1.0 - 1.0 / distance.powi(2)).sqrt());
+    for _ in 0..1000 {
+        let dir = pdf.generate(rand);
+        let ray = Ray {
+            orig: origin,
+            dir,
+            time: 0.0,
+        };
+        assert!(sphere.hit(&ray, 0.001, f64::INFINITY, rec));
+        assert!((pdf.value(dir) * solid_angle - 1.0).abs() < 1e-9);
+    }
+    assert!((integral(&pdf, rand) - 1.0).abs() < 0.02);
+    assert_eq!(pdf.value(Vec3::new(0.0, 0.0, 1.0)), 0.0);
+
+    // For a quad the density changes, but 1 over it still averages to the solid angle
+    let quad = Quad::new(
+        Vec3::new(-1.0, -1.0, 1.0),
+        Vec3::new(2.0, 0.0, 0.0),
+        Vec3::new(0.0, 1.5, 0.5),
+        white,
+    );
+    let pdf = HittablePdf::new(&quad, origin);
+    let n = 100000;
+    let mut sum = 0.0;
+    for _ in 0..n {
+        let dir = pdf.generate(rand);
+        let ray = Ray {
+            orig: origin,
+            dir,
+            time: 0.0,
+        };
+        assert!(quad.hit(&ray, 0.001, f64::INFINITY, rec));
+        sum += 1.0 / pdf.value(dir);
+    }
+    let hits = (0..n)
+        .filter(|_| {
+            let ray = Ray {
+                orig: origin,
+                dir: Vec3::random_unit_vector(rand),
+                time: 0.0,
+            };
+            quad.hit(&ray, 0.001, f64::INFINITY, rec)
+        })
+        .count();
+    let solid_angle = 4.0 * PI * hits as f64 / n as f64;
+    assert!((sum / n as f64 - solid_angle).abs() < 0.02 * solid_angle);
+    assert!((integral(&pdf, rand) - 1.0).abs() < 0.02);
+
+    // Lists pick each object half of the time
+    let mut list = HittableList::new();
+    list.add(sphere);
+    list.add(quad);
+    assert!((integral(&HittablePdf::new(&list, origin), rand) - 1.0).abs() < 0.02);
+    // An empty list can't be sampled
+    let empty = HittableList::new();
+    assert_eq!(
+        HittablePdf::new(&empty, origin).value(Vec3::repeat(1.0)),
+        0.0
+    );
+}
+
+#[test]
+fn material_pdfs() {
+    let albedo = Vec3::new(0.2, 0.4, 0.6);
+    let floor = Quad::xz_rect(-1.0, 1.0, -1.0, 1.0, 0.0, LambertianDiffuse::new(albedo));
+    let down = Ray {
+        orig: Vec3::new(0.0, 1.0, 0.0),
+        dir: Vec3::new(0.0, -1.0, 0.0),
+        time: 0.0,
+    };
+    let rec = &mut HitRecord::default();
+    assert!(floor.hit(&down, 0.001, f64::INFINITY, rec));
+
+    let lambertian = LambertianDiffuse::new(albedo);
+    assert!(lambertian.pdf(rec).is_some());
+    let slanted = Vec3::new(1.0, 1.0, 0.0);
+    let expected = albedo * (0.5f64.sqrt() / PI);
+    assert!((lambertian.eval(&down, rec, slanted) - expected).length() < 1e-12);
+    assert_eq!(lambertian.eval(&down, rec, -slanted), Vec3::zero());
+
+    // Mirrors and lights have no density to sample
+    assert!(Metal::new(0.5, 0.5, 0.5, 0.0).pdf(rec).is_none());
+    assert!(DiffuseLight::new(Vec3::repeat(1.0)).pdf(rec).is_none());
+}
+
+#[test]
+fn sampling_lights() {
+    let params = RenderParams {
+        image_width: 12,
+        image_height: 12,
+        samples_per_px: 16,
+        max_depth: 8,
+        background: Background::BLACK,
+        ..Default::default()
+    };
+    let camera = || {
+        Camera::new(
+            Vec3::new(278.0, 278.0, -800.0),
+            Vec3::new(278.0, 278.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            40.0,
+            1.0,
+            0.0,
+            10.0,
+        )
+    };
+    let light = || {
+        let mut lights = HittableList::new();
+        lights.add(Quad::xz_rect(
+            213.0,
+            343.0,
+            227.0,
+            332.0,
+            554.0,
+            DiffuseLight::new(Vec3::repeat(15.0)),
+        ));
+        lights
+    };
+    let render = |lights: &HittableList, seed| {
+        let rand = &mut RandState::from_seed(seed);
+        render_hdr(
+            camera(),
+            scenes::cornell_box(),
+            lights,
+            params.clone(),
+            rand,
+        )
+    };
+    // How different two renders with other seeds are, and how bright they are. Clamped, so the
+    // edges of the light don't count more than everything else
+    let noise_and_mean = |lights: &HittableList| {
+        let (a, b) = (render(lights, 1), render(lights, 2));
+        let difference = |(a, b): (&f32, &f32)| (a.min(1.0) - b.min(1.0)).powi(2);
+        let noise: f32 = a.iter().zip(&b).map(difference).sum();
+        let mean: f32 = a.iter().chain(&b).sum::<f32>() / (2 * a.len()) as f32;
+        (noise, mean)
+    };
+
+    let (noise, mean) = noise_and_mean(&HittableList::new());
+    let (sampled_noise, sampled_mean) = noise_and_mean(&light());
+    assert!(sampled_noise < 0.5 * noise, "{} {}", sampled_noise, noise);
+    // Both converge to the same image
+    assert!(
+        (sampled_mean - mean).abs() < 0.1 * mean,
+        "{} {}",
+        sampled_mean,
+        mean
+    );
+}
+
+#[test]
+fn next_event_estimation() {
+    // Lit by a quad and a sphere, both smaller than the lights of the Cornell box
+    let params = RenderParams {
+        image_width: 16,
+        image_height: 12,
+        samples_per_px: 32,
+        max_depth: 8,
+        background: Background::BLACK,
+        ..Default::default()
+    };
+    let camera = || {
+        Camera::new(
+            Vec3::new(26.0, 3.0, 6.0),
+            Vec3::new(0.0, 2.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            20.0,
+            16.0 / 12.0,
+            0.0,
+            10.0,
+        )
+    };
+    let render = |lights: &HittableList, seed| {
+        let rand = &mut RandState::from_seed(seed);
+        render_hdr(
+            camera(),
+            scenes::light_scene(),
+            lights,
+            params.clone(),
+            rand,
+        )
+    };
+    let noise_and_mean = |lights: &HittableList| {
+        let (a, b) = (render(lights, 1), render(lights, 2));
+        let difference = |(a, b): (&f32, &f32)| (a.min(1.0) - b.min(1.0)).powi(2);
+        let noise: f32 = a.iter().zip(&b).map(difference).sum();
+        let mean: f32 = a.iter().chain(&b).sum::<f32>() / (2 * a.len()) as f32;
+        (noise, mean)
+    };
+
+    let (noise, mean) = noise_and_mean(&HittableList::new());
+    let (sampled_noise, sampled_mean) = noise_and_mean(&scenes::light_scene_lights());
+    assert!(sampled_noise < 0.6 * noise, "{} {}", sampled_noise, noise);
+    assert!(
+        (sampled_mean - mean).abs() < 0.05 * mean,
+        "{} {}",
+        sampled_mean,
+        mean
+    );
+}
@@ -0,0 +1,183 @@
+use rtweekend::{
+    bvh::Aabb,
+    material::LambertianDiffuse,
+    planar::{cuboid, Disk, Plane, Quad},
+    scene_file::{parse_scene, write_scene, ObjectDesc},
+    HitRecord, Hittable, Ray, Vec3,
+};
+use std::path::Path;
+
+fn ray(orig: Vec3, dir: Vec3) -> Ray {
+    Ray {
+        orig,
+        dir,
+        time: 0.0,
+    }
+}
+
+/// Checks that `object` is hit at distance `t` and not before
+fn hits_at(object: &impl Hittable, ray: &Ray, t: f64) -> bool {
+    let rec = &mut HitRecord::default();
+    object.hit(ray, 0.001, t + 1e-6, rec) && !object.hit(ray, 0.001, t - 1e-6, rec)
+}
+
+#[test]
+fn quad() {
+    let quad = Quad::new(
+        Vec3::new(0.0, 0.0, 0.0),
+        Vec3::new(2.0, 0.0, 0.0),
+        Vec3::new(1.0, 1.0, 0.0),
+        LambertianDiffuse::new(Vec3::repeat(0.5)),
+    );
+    let down = Vec3::new(0.0, 0.0, -1.0);
+    assert!(hits_at(&quad, &ray(Vec3::new(1.5, 0.5, 3.0), down), 3.0));
+    // Hit from behind too
+    assert!(hits_at(&quad, &ray(Vec3::new(1.5, 0.5, -2.0), -down), 2.0));
+    // Inside the bounding box, but outside the parallelogram
+    let rec = &mut HitRecord::default();
+    assert!(!quad.hit(&ray(Vec3::new(0.2, 0.8, 3.0), down), 0.001, 10.0, rec));
+    // Parallel to the plane
+    let side = ray(Vec3::new(-1.0, 0.5, 0.0), Vec3::new(1.0, 0.0, 0.0));
+    assert!(!quad.hit(&side, 0.001, 10.0, rec));
+
+    let mut bbox = Aabb::empty();
+    assert!(quad.bounding_box(&mut bbox));
+    assert!(bbox.min.x().abs() < 1e-3 && (bbox.max.x() - 3.0).abs() < 1e-3);
+    assert!(bbox.min.y().abs() < 1e-3 && (bbox.max.y() - 1.0).abs() < 1e-3);
+    // Padded, so the box isn't flat
+    assert!(bbox.min.z() < 0.0 && bbox.max.z() > 0.0);
+}
+
+#[test]
+fn rects() {
+    let white = LambertianDiffuse::new(Vec3::repeat(0.5));
+    let rects = [
+        (
+            Quad::xy_rect(0.0, 1.0, 0.0, 1.0, 2.0, white.clone()),
+            Vec3::new(0.0, 0.0, 1.0),
+        ),
+        (
+            Quad::xz_rect(0.0, 1.0, 0.0, 1.0, 2.0, white.clone()),
+            Vec3::new(0.0, 1.0, 0.0),
+        ),
+        (
+            Quad::yz_rect(0.0, 1.0, 0.0, 1.0, 2.0, white),
+            Vec3::new(1.0, 0.0, 0.0),
+        ),
+    ];
+    for (rect, normal) in rects.iter() {
+        // Coming from the side the rect faces, through its middle
+        let orig = *normal * 5.0 + Vec3::repeat(0.5) - *normal * 0.5;
+        assert!(hits_at(rect, &ray(orig, -*normal), 3.0), "{:?}", normal);
+        let outside = orig + Vec3::repeat(1.0) - *normal;
+        let rec = &mut HitRecord::default();
+        assert!(!rect.hit(&ray(outside, -*normal), 0.001, 10.0, rec));
+    }
+}
+
+#[test]
+fn disk() {
+    let disk = Disk::new(
+        Vec3::new(1.0, 2.0, 3.0),
+        Vec3::new(0.0, 2.0, 0.0),
+        1.0,
+        LambertianDiffuse::new(Vec3::repeat(0.5)),
+    );
+    let down = Vec3::new(0.0, -1.0, 0.0);
+    assert!(hits_at(&disk, &ray(Vec3::new(1.5, 5.0, 3.5), down), 3.0));
+    // Inside the square around it, but outside the circle
+    let rec = &mut HitRecord::default();
+    assert!(!disk.hit(&ray(Vec3::new(1.8, 5.0, 3.8), down), 0.001, 10.0, rec));
+
+    let mut bbox = Aabb::empty();
+    assert!(disk.bounding_box(&mut bbox));
+    assert!((bbox.min.x() - 0.0).abs() < 1e-3 && (bbox.max.x() - 2.0).abs() < 1e-3);
+    assert!((bbox.min.z() - 2.0).abs() < 1e-3 && (bbox.max.z() - 4.0).abs() < 1e-3);
+    assert!((bbox.min.y() - 2.0).abs() < 1e-3 && (bbox.max.y() - 2.0).abs() < 1e-3);
+}
+
+#[test]
+fn boxes() {
+    let cube = cuboid(
+        Vec3::new(1.0, 1.0, 1.0),
+        Vec3::new(-1.0, -1.0, -1.0),
+        LambertianDiffuse::new(Vec3::repeat(0.5)),
+    );
+    let axes = [
+        Vec3::new(1.0, 0.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        Vec3::new(0.0, 0.0, 1.0),
+    ];
+    for axis in axes.iter() {
+        for &sign in [1.0, -1.0].iter() {
+            let dir = *axis * sign;
+            let offset = Vec3::new(0.1, 0.2, 0.3);
+            let orig = dir * -5.0 + offset - *axis * axis.dot(offset);
+            assert!(hits_at(&cube, &ray(orig, dir), 4.0), "{:?}", dir);
+        }
+    }
+    // Every side faces out
+    let sides = match cube.describe() {
+        Some(ObjectDesc::Group(sides)) => sides,
+        other => panic!("{:?}", other),
+    };
+    assert_eq!(sides.len(), 6);
+    for side in sides {
+        match side {
+            ObjectDesc::Quad { q, u, v, .. } => {
+                let middle = q + (u + v) * 0.5;
+                assert!(u.cross(v).dot(middle) > 0.0, "{:?}", middle);
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+
+    let mut bbox = Aabb::empty();
+    assert!(cube.bounding_box(&mut bbox));
+    assert!((bbox.min - Vec3::repeat(-1.0)).length() < 1e-3);
+    assert!((bbox.max - Vec3::repeat(1.0)).length() < 1e-3);
+}
+
+#[test]
+fn scene_file() {
+    let source = "render width 10 height 10
+material white lambertian 0.8 0.8 0.8
+quad 0 0 0  1 0 0  0 1 0 white
+disk 0 1 0  0 1 0  0.5 white
+plane 0 -1 0  0 1 0 white
+box 0 0 0  1 2 3 white
+";
+    let scene = parse_scene("planar.scene", source, Path::new("")).unwrap();
+    let written = write_scene(&scene.camera, &scene.params, &scene.world).unwrap();
+    assert!(written.contains("\nquad "));
+    assert!(written.contains("\ndisk "));
+    assert!(written.contains("\nplane "));
+    // A box is written as its six sides
+    assert_eq!(written.matches("\nquad ").count(), 7);
+    let parsed = parse_scene("written.scene", &written, Path::new("")).unwrap();
+    assert_eq!(
+        write_scene(&parsed.camera, &parsed.params, &parsed.world).unwrap(),
+        written
+    );
+}
+
+#[test]
+fn plane() {
+    let plane = Plane::new(
+        Vec3::new(0.0, 1.0, 0.0),
+        Vec3::new(0.0, 3.0, 0.0),
+        LambertianDiffuse::new(Vec3::repeat(0.5)),
+    );
+    let down = Vec3::new(0.0, -1.0, 0.0);
+    assert!(hits_at(&plane, &ray(Vec3::new(0.0, 3.0, 0.0), down), 2.0));
+    // Far away it's still flat
+    assert!(hits_at(&plane, &ray(Vec3::new(1e6, 3.0, -1e6), down), 2.0));
+    assert!(hits_at(&plane, &ray(Vec3::new(0.0, -1.0, 0.0), -down), 2.0));
+    let slanted = ray(Vec3::new(0.0, 3.0, 0.0), Vec3::new(1.0, -1.0, 0.0));
+    assert!(hits_at(&plane, &slanted, 2.0));
+    let rec = &mut HitRecord::default();
+    let parallel = ray(Vec3::new(0.0, 3.0, 0.0), Vec3::new(1.0, 0.0, 1.0));
+    assert!(!plane.hit(&parallel, 0.001, f64::INFINITY, rec));
+    // Planes are infinite, so they have no bounding box
+    assert!(!plane.bounding_box(&mut Aabb::empty()));
+}
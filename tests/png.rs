@@ -0,0 +1,284 @@
+use rtweekend::png::{decode_png, encode_png};
+use std::convert::TryInto;
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let crc_table = (0..256u32)
+        .map(|n| {
+            (0..8).fold(n, |c, _| {
+                if c & 1 != 0 {
+                    0xEDB8_8320 ^ (c >> 1)
+                } else {
+                    c >> 1
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    bytes.iter().fold(0xFFFF_FFFFu32, |c, &b| {
+        crc_table[((c ^ b as u32) & 0xFF) as usize] ^ (c >> 8)
+    }) ^ 0xFFFF_FFFF
+}
+
+/// Splits a PNG file into (type, data) chunks, checking the signature and every CRC
+fn chunks(file: &[u8]) -> Vec<([u8; 4], Vec<u8>)> {
+    assert_eq!(&file[..8], b"\x89PNG\r\n\x1a\n");
+    let mut out = vec![];
+    let mut rest = &file[8..];
+    while !rest.is_empty() {
+        let len = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+        let kind: [u8; 4] = rest[4..8].try_into().unwrap();
+        let data = rest[8..8 + len].to_vec();
+        let crc = u32::from_be_bytes(rest[8 + len..12 + len].try_into().unwrap());
+        assert_eq!(crc, crc32(&rest[4..8 + len]), "bad crc for {:?}", kind);
+        out.push((kind, data));
+        rest = &rest[12 + len..];
+    }
+    out
+}
+
+/// Inflates a zlib stream made of fixed huffman blocks, which is all the encoder writes
+fn inflate_fixed(stream: &[u8]) -> Vec<u8> {
+    assert_eq!(((stream[0] as u32) << 8 | stream[1] as u32) % 31, 0);
+    let data = &stream[2..stream.len() - 4];
+    let mut bit = 0;
+    let mut read = |count: u32| {
+        let mut value = 0;
+        for i in 0..count {
+            value |= ((data[bit / 8] >> (bit % 8)) as u32 & 1) << i;
+            bit += 1;
+        }
+        value
+    };
+    const LENGTH_BASE: [usize; 29] = [
+        3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115,
+        131, 163, 195, 227, 258,
+    ];
+    const DIST_BASE: [usize; 30] = [
+        1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+        2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+    ];
+    let length_extra = |code: usize| {
+        if (8..28).contains(&code) {
+            code / 4 - 1
+        } else {
+            0
+        }
+    };
+    let dist_extra = |code: usize| if code >= 4 { code / 2 - 1 } else { 0 };
+
+    let mut out: Vec<u8> = vec![];
+    loop {
+        let last = read(1);
+        assert_eq!(read(2), 1, "not a fixed huffman block");
+        loop {
+            // Read the code bit by bit, most significant first
+            let mut code = 0;
+            let mut len = 0;
+            let symbol = loop {
+                code = code << 1 | read(1);
+                len += 1;
+                match (len, code) {
+                    (7, 0..=0b10111) => break code + 256,
+                    (8, 0b0011_0000..=0b1011_1111) => break code - 0b0011_0000,
+                    (8, 0b1100_0000..=0b1100_0111) => break code - 0b1100_0000 + 280,
+                    (9, 0b1_1001_0000..=0b1_1111_1111) => break code - 0b1_1001_0000 + 144,
+                    _ => assert!(len < 9),
+                }
+            } as usize;
+            if symbol < 256 {
+                out.push(symbol as u8);
+                continue;
+            }
+            if symbol == 256 {
+                break;
+            }
+            let code = symbol - 257;
+            let length = LENGTH_BASE[code] + read(length_extra(code) as u32) as usize;
+            let dist_code = (0..5).fold(0, |c, _| c << 1 | read(1)) as usize;
+            let dist = DIST_BASE[dist_code] + read(dist_extra(dist_code) as u32) as usize;
+            for _ in 0..length {
+                out.push(out[out.len() - dist]);
+            }
+        }
+        if last == 1 {
+            break;
+        }
+    }
+
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in &out {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    let adler = u32::from_be_bytes(stream[stream.len() - 4..].try_into().unwrap());
+    assert_eq!(adler, b << 16 | a);
+    out
+}
+
+/// Undoes the filters of every scanline
+fn unfilter(width: usize, height: usize, filtered: &[u8]) -> Vec<u8> {
+    let stride = width * 3;
+    assert_eq!(filtered.len(), height * (stride + 1));
+    let mut out = vec![0u8; stride * height];
+    for row in 0..height {
+        let filter = filtered[row * (stride + 1)];
+        for i in 0..stride {
+            let x = filtered[row * (stride + 1) + 1 + i];
+            let a = if i >= 3 { out[row * stride + i - 3] } else { 0 } as i16;
+            let b = if row > 0 {
+                out[(row - 1) * stride + i]
+            } else {
+                0
+            } as i16;
+            let c = if row > 0 && i >= 3 {
+                out[(row - 1) * stride + i - 3]
+            } else {
+                0
+            } as i16;
+            let predictor = match filter {
+                0 => 0,
+                1 => a,
+                2 => b,
+                3 => (a + b) / 2,
+                4 => {
+                    let p = a + b - c;
+                    let (pa, pb, pc) = ((p - a).abs(), (p - b).abs(), (p - c).abs());
+                    if pa <= pb && pa <= pc {
+                        a
+                    } else if pb <= pc {
+                        b
+                    } else {
+                        c
+                    }
+                }
+                _ => panic!("unknown filter {}", filter),
+            };
+            out[row * stride + i] = x.wrapping_add(predictor as u8);
+        }
+    }
+    out
+}
+
+fn decode(file: &[u8]) -> (u32, u32, Vec<u8>) {
+    let chunks = chunks(file);
+    let kinds = chunks.iter().map(|(kind, _)| kind).collect::<Vec<_>>();
+    assert_eq!(kinds, [b"IHDR", b"IDAT", b"IEND"]);
+    let ihdr = &chunks[0].1;
+    let width = u32::from_be_bytes(ihdr[0..4].try_into().unwrap());
+    let height = u32::from_be_bytes(ihdr[4..8].try_into().unwrap());
+    assert_eq!(&ihdr[8..], &[8, 2, 0, 0, 0]);
+    let filtered = inflate_fixed(&chunks[1].1);
+    let pixels = unfilter(width as usize, height as usize, &filtered);
+    (width, height, pixels)
+}
+
+#[test]
+fn round_trip() {
+    // A gradient, some noise and a flat area, so every filter and long matches get used
+    let (width, height) = (67u32, 41u32);
+    let mut state = 0x1234_5678u32;
+    let mut pixels = vec![];
+    for y in 0..height {
+        for x in 0..width {
+            state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            let px = if y < 10 {
+                [x as u8 * 3, y as u8 * 5, 128]
+            } else if y < 25 {
+                [(state >> 24) as u8, (state >> 16) as u8, (state >> 8) as u8]
+            } else {
+                [200, 10, 30]
+            };
+            pixels.extend_from_slice(&px);
+        }
+    }
+    let file = encode_png(width, height, &pixels);
+    assert_eq!(decode(&file), (width, height, pixels.clone()));
+    assert_eq!(
+        decode_png(&file).unwrap(),
+        (width as usize, height as usize, pixels.clone())
+    );
+    assert!(file.len() < pixels.len());
+
+    let flat = vec![7u8; 3 * 100 * 100];
+    let file = encode_png(100, 100, &flat);
+    assert_eq!(decode(&file), (100, 100, flat));
+    assert!(file.len() < 1000, "a flat image should compress well");
+}
+
+#[test]
+fn render_round_trip() {
+    use rtweekend::{scenes, Camera, RandState, RenderParams, Vec3};
+    let camera = Camera::new(
+        Vec3::new(-2.0, 2.0, 1.0),
+        Vec3::new(0.0, 0.0, -1.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        40.0,
+        1.5,
+        0.0,
+        1.0,
+    );
+    let params = RenderParams {
+        image_width: 30,
+        image_height: 20,
+        samples_per_px: 2,
+        ..Default::default()
+    };
+    let pixels = rtweekend::render(
+        camera,
+        scenes::normal_scene(),
+        &rtweekend::HittableList::new(),
+        params,
+        &mut RandState::from_seed(1),
+    );
+    assert_eq!(decode(&encode_png(30, 20, &pixels)), (30, 20, pixels));
+}
+
+/// A PNG file with the given IHDR data and zlib stream
+fn png_file(header: &[u8], zlib: &[u8]) -> Vec<u8> {
+    let mut file = b"\x89PNG\r\n\x1a\n".to_vec();
+    let mut chunk = |kind: &[u8], data: &[u8]| {
+        file.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        let mut body = kind.to_vec();
+        body.extend_from_slice(data);
+        file.extend_from_slice(&body);
+        file.extend_from_slice(&crc32(&body).to_be_bytes());
+    };
+    chunk(b"IHDR", header);
+    chunk(b"IDAT", zlib);
+    chunk(b"IEND", &[]);
+    file
+}
+
+#[test]
+fn decode_gray() {
+    // A 3x2 image with 2 bits per gray pixel, in a stored (Uncompressed) deflate block
+    let filtered = [0, 0b0001_1000, 0, 0b1111_0100];
+    let mut zlib = vec![0x78, 0x01, 0x01, 4, 0, !4, !0];
+    zlib.extend_from_slice(&filtered);
+    let (a, b) = filtered.iter().fold((1u32, 0u32), |(a, b), &byte| {
+        (a + byte as u32, b + a + byte as u32)
+    });
+    zlib.extend_from_slice(&(b << 16 | a).to_be_bytes());
+
+    let file = png_file(&[0, 0, 0, 3, 0, 0, 0, 2, 2, 0, 0, 0, 0], &zlib);
+    let (width, height, pixels) = decode_png(&file).unwrap();
+    assert_eq!((width, height), (3, 2));
+    let gray = [0, 85, 170, 255, 255, 85];
+    let expected = gray.iter().flat_map(|&g| vec![g; 3]).collect::<Vec<u8>>();
+    assert_eq!(pixels, expected);
+
+    assert!(decode_png(&file[..file.len() - 20]).is_err());
+    assert!(decode_png(b"not a png").is_err());
+}
+
+#[test]
+fn decode_huge() {
+    // A 16 bit RGBA header of the largest size, with an empty stored deflate block
+    let zlib = [0x78, 0x01, 0x01, 0, 0, 0xff, 0xff, 0, 0, 0, 1];
+    let header = [
+        0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 16, 6, 0, 0, 0,
+    ];
+    assert_eq!(
+        decode_png(&png_file(&header, &zlib)),
+        Err("the image is too large".to_string())
+    );
+}
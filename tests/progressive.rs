@@ -0,0 +1,114 @@
+use rtweekend::{
+    render, render_counting_samples, render_hdr, render_progressive, scenes, Camera, HittableList,
+    ProgressiveRender, RandState, RenderParams, Vec3,
+};
+
+fn camera() -> Camera {
+    Camera::new(
+        Vec3::new(0.0, 0.0, 0.0),
+        Vec3::new(0.0, 0.0, -1.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        90.0,
+        3.0 / 2.0,
+        0.0,
+        1.0,
+    )
+}
+
+fn params(samples_per_px: u32) -> RenderParams {
+    RenderParams {
+        image_width: 24,
+        image_height: 16,
+        samples_per_px,
+        max_depth: 10,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn passes_add_up_to_render() {
+    let lights = HittableList::new();
+    let rand = &mut RandState::from_seed(1);
+    let image = render_hdr(camera(), scenes::normal_scene(), &lights, params(32), rand);
+
+    let rand = &mut RandState::from_seed(1);
+    let mut previews = Vec::new();
+    let progressive = render_progressive(
+        camera(),
+        scenes::normal_scene(),
+        &lights,
+        params(32),
+        rand,
+        3,
+        |progressive| {
+            previews.push((progressive.passes(), progressive.samples()[0]));
+            true
+        },
+    );
+    // 32 samples in 3 passes
+    assert_eq!(previews, [(1, 10), (2, 21), (3, 32)]);
+    assert_eq!(progressive.image_hdr(), image);
+
+    // Passes can keep going past the samples per pixel
+    let rand = &mut RandState::from_seed(1);
+    let more = render(camera(), scenes::normal_scene(), &lights, params(48), rand);
+    let rand = &mut RandState::from_seed(1);
+    let mut progressive =
+        ProgressiveRender::new(camera(), scenes::normal_scene(), &lights, params(32), rand);
+    progressive.render_pass(32);
+    progressive.render_pass(16);
+    assert_eq!(progressive.passes(), 2);
+    assert!(progressive.samples().iter().all(|&samples| samples == 48));
+    assert_eq!(progressive.image(), more);
+}
+
+#[test]
+fn stop_early() {
+    let lights = HittableList::new();
+    let rand = &mut RandState::from_seed(2);
+    let progressive = render_progressive(
+        camera(),
+        scenes::normal_scene(),
+        &lights,
+        params(40),
+        rand,
+        4,
+        |progressive| progressive.passes() < 2,
+    );
+    assert_eq!(progressive.passes(), 2);
+    assert!(progressive.samples().iter().all(|&samples| samples == 20));
+    // Not a black image, the average of the samples so far
+    let image = progressive.image();
+    assert!(image.iter().map(|&c| c as u32).sum::<u32>() > 100 * image.len() as u32);
+}
+
+#[test]
+fn adaptive_passes() {
+    let lights = HittableList::new();
+    let params = RenderParams {
+        min_samples_per_px: 8,
+        noise_threshold: 0.05,
+        ..params(64)
+    };
+    let rand = &mut RandState::from_seed(3);
+    let (image, samples) = render_counting_samples(
+        camera(),
+        scenes::normal_scene(),
+        &lights,
+        params.clone(),
+        rand,
+    );
+    let rand = &mut RandState::from_seed(3);
+    let progressive = render_progressive(
+        camera(),
+        scenes::normal_scene(),
+        &lights,
+        params,
+        rand,
+        8,
+        |_| true,
+    );
+    // Pixels that stopped in one pass stop after the same sample in the next
+    assert_eq!(progressive.samples(), samples);
+    assert_eq!(progressive.image(), image);
+}
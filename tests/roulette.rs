@@ -0,0 +1,107 @@
+use rtweekend::{
+    material::{Dielectric, LambertianDiffuse, Metal},
+    render_hdr,
+    scene_file::{parse_scene, write_scene},
+    Background, Camera, HittableList, RandState, RenderParams, Sphere, Vec3,
+};
+use std::path::Path;
+
+/// Glass spheres inside glass spheres between two mirrors, where rays bounce for a long time
+fn glass_scene() -> HittableList {
+    let mut world = HittableList::new();
+    world.add(Sphere::new(
+        Vec3::new(0.0, -100.5, -1.0),
+        100.0,
+        LambertianDiffuse::new(Vec3::new(0.5, 0.6, 0.4)),
+    ));
+    for &x in [-1.0, 0.0, 1.0].iter() {
+        for &radius in [0.5, -0.45, 0.3, -0.25].iter() {
+            world.add(Sphere::new(
+                Vec3::new(x, 0.0, -1.0),
+                radius,
+                Dielectric::new(1.5),
+            ));
+        }
+    }
+    for &z in [-3.0, 1.5].iter() {
+        world.add(Sphere::new(
+            Vec3::new(0.0, 0.0, z * 100.0),
+            100.0 * z.abs() - 1.0,
+            Metal::new(0.9, 0.9, 0.9, 0.0),
+        ));
+    }
+    world
+}
+
+#[test]
+fn unbiased() {
+    let camera = || {
+        Camera::new(
+            Vec3::new(0.0, 0.5, 1.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            60.0,
+            4.0 / 3.0,
+            0.0,
+            1.0,
+        )
+    };
+    let mean = |roulette_depth, seed| {
+        let params = RenderParams {
+            image_width: 16,
+            image_height: 12,
+            samples_per_px: 64,
+            max_depth: 50,
+            roulette_depth,
+            background: Background::SKY,
+            ..Default::default()
+        };
+        let rand = &mut RandState::from_seed(seed);
+        let image = render_hdr(camera(), glass_scene(), &HittableList::new(), params, rand);
+        image.iter().sum::<f32>() / image.len() as f32
+    };
+
+    let (without, with) = (mean(None, 1), mean(Some(2), 1));
+    assert_ne!(without, with);
+    // Ending some paths early doesn't make the image darker or brighter, only a bit noisier
+    assert!(
+        (with - without).abs() < 0.04 * without,
+        "{} {}",
+        with,
+        without
+    );
+    assert!((mean(Some(0), 2) - without).abs() < 0.04 * without);
+    // Past the maximum depth roulette never happens
+    assert_eq!(mean(Some(50), 1), without);
+}
+
+#[test]
+fn scene_file_roulette() {
+    let source = "render width 10 height 10 roulette 3 0.8\n";
+    let scene = parse_scene("roulette.scene", source, Path::new("")).unwrap();
+    assert_eq!(scene.params.roulette_depth, Some(3));
+    assert_eq!(scene.params.roulette_probability, 0.8);
+    let written = write_scene(&scene.camera, &scene.params, &scene.world).unwrap();
+    assert!(written.contains("depth 50 roulette 3 0.8"));
+
+    // Off by default, so renders don't change
+    let scene = parse_scene(
+        "default.scene",
+        "render width 10 height 10\n",
+        Path::new(""),
+    )
+    .unwrap();
+    assert_eq!(scene.params.roulette_depth, None);
+    let written = write_scene(&scene.camera, &scene.params, &scene.world).unwrap();
+    assert!(!written.contains("roulette"));
+
+    let error = parse_scene(
+        "test.scene",
+        "render width 10 height 10 roulette 3 0\n",
+        Path::new(""),
+    )
+    .err()
+    .expect("should fail")
+    .message;
+    assert_eq!(error, "the roulette probability must be between 0 and 1");
+}
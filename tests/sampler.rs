@@ -0,0 +1,197 @@
+use rtweekend::{
+    render_hdr,
+    sampler::{blue_noise, Halton, Sampler, SamplerKind, Sobol, Stratified, BLUE_NOISE_SIZE},
+    scene_file::{parse_scene, write_scene},
+    scenes, Camera, HittableList, RandState, RenderParams, Vec3,
+};
+use std::path::Path;
+
+#[test]
+fn uniform() {
+    for kind in SamplerKind::ALL.iter() {
+        let sampler = kind.build(1, 16);
+        let mut sum = 0.0;
+        let mut count = 0;
+        for pixel in 0..20 {
+            for index in 0..16 {
+                for dim in (0..10).chain(100..110) {
+                    let x = sampler.sample([pixel, 2 * pixel], index, dim);
+                    assert!((0.0..1.0).contains(&x), "{:?} {}", kind, x);
+                    sum += x;
+                    count += 1;
+                }
+            }
+        }
+        assert!((sum / count as f64 - 0.5).abs() < 0.02, "{:?}", kind);
+    }
+}
+
+#[test]
+fn stratified() {
+    let sampler = Stratified::new(2, 12);
+    for dim in 0..20 {
+        let mut strata = (0..12)
+            .map(|index| (sampler.sample([3, 4], index, dim) * 12.0) as u32)
+            .collect::<Vec<_>>();
+        strata.sort_unstable();
+        assert_eq!(strata, (0..12).collect::<Vec<_>>());
+    }
+}
+
+/// Whether every box of 2^a by 2^(n - a) of the unit square, for every a, has exactly one of the
+/// 2^n points
+fn is_net(points: &[(f64, f64)]) -> bool {
+    let n = points.len().trailing_zeros();
+    (0..=n).all(|a| {
+        let (columns, rows) = (1 << a, 1 << (n - a));
+        let mut boxes = vec![0; points.len()];
+        for &(x, y) in points {
+            let (column, row) = ((x * columns as f64) as usize, (y * rows as f64) as usize);
+            boxes[row * columns + column] += 1;
+        }
+        boxes.iter().all(|&count| count == 1)
+    })
+}
+
+#[test]
+fn sobol() {
+    let sampler = Sobol::new(3);
+    for &pixel in [[0, 0], [7, 3]].iter() {
+        // The first two dimensions of every group of 4
+        for &dim in [0, 4, 40].iter() {
+            let points = (0..64)
+                .map(|i| {
+                    (
+                        sampler.sample(pixel, i, dim),
+                        sampler.sample(pixel, i, dim + 1),
+                    )
+                })
+                .collect::<Vec<_>>();
+            assert!(is_net(&points), "{:?} {}", pixel, dim);
+            assert!(is_net(&points[..16]));
+        }
+    }
+    // Pixels are scrambled differently
+    assert_ne!(sampler.sample([0, 0], 0, 0), sampler.sample([0, 1], 0, 0));
+}
+
+#[test]
+fn halton() {
+    let sampler = Halton::new(4);
+    // The first base^k points have one in every interval of size 1/base^k
+    for &(dim, count) in [(0, 16), (1, 27), (2, 25)].iter() {
+        let mut intervals = (0..count)
+            .map(|index| (sampler.sample([5, 6], index, dim) * count as f64) as u32)
+            .collect::<Vec<_>>();
+        intervals.sort_unstable();
+        assert_eq!(intervals, (0..count).collect::<Vec<_>>());
+    }
+}
+
+#[test]
+fn blue_noise_tile() {
+    let tile = blue_noise();
+    let n = BLUE_NOISE_SIZE * BLUE_NOISE_SIZE;
+    let mut ranks = tile.to_vec();
+    ranks.sort_unstable();
+    assert_eq!(ranks, (0..n as u16).collect::<Vec<_>>());
+    // Neighbors (Wrapping around) have more different ranks than with white noise, where the
+    // average difference would be n/3
+    let mut difference = 0.0;
+    for y in 0..BLUE_NOISE_SIZE {
+        for x in 0..BLUE_NOISE_SIZE {
+            let rank = tile[y * BLUE_NOISE_SIZE + x] as f64;
+            let right = tile[y * BLUE_NOISE_SIZE + (x + 1) % BLUE_NOISE_SIZE] as f64;
+            let below = tile[(y + 1) % BLUE_NOISE_SIZE * BLUE_NOISE_SIZE + x] as f64;
+            difference += (rank - right).abs() + (rank - below).abs();
+        }
+    }
+    let difference = difference / (2 * n) as f64 / n as f64;
+    assert!(difference > 0.4, "{}", difference);
+}
+
+#[test]
+fn rand_state_dimensions() {
+    let sampler = SamplerKind::Sobol.build(5, 16);
+    let rand = &mut RandState::from_seed(6);
+    rand.use_sampler(sampler, [1, 2], 3);
+    assert_eq!(rand.random_double(), sampler.sample([1, 2], 3, 0));
+    rand.start_dimensions(10, 2);
+    assert_eq!(rand.random_double(), sampler.sample([1, 2], 3, 10));
+    assert_eq!(rand.random_double(), sampler.sample([1, 2], 3, 11));
+    // Past them the numbers come from the generator
+    assert_ne!(rand.random_double(), sampler.sample([1, 2], 3, 12));
+}
+
+#[test]
+fn less_noise() {
+    let camera = || {
+        Camera::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            90.0,
+            4.0 / 3.0,
+            0.0,
+            1.0,
+        )
+    };
+    let render = |sampler, samples_per_px, seed| {
+        let params = RenderParams {
+            image_width: 16,
+            image_height: 12,
+            samples_per_px,
+            max_depth: 4,
+            sampler,
+            ..Default::default()
+        };
+        let rand = &mut RandState::from_seed(seed);
+        render_hdr(
+            camera(),
+            scenes::normal_scene(),
+            &HittableList::new(),
+            params,
+            rand,
+        )
+    };
+    let reference = render(SamplerKind::Independent, 1024, 10);
+    let error = |sampler| {
+        let squared_error = |seed| {
+            let image = render(sampler, 16, seed);
+            let error = image.iter().zip(&reference).map(|(a, b)| (a - b).powi(2));
+            error.sum::<f32>()
+        };
+        squared_error(1) + squared_error(2)
+    };
+
+    let independent = error(SamplerKind::Independent);
+    for &kind in SamplerKind::ALL[1..].iter() {
+        let error = error(kind);
+        assert!(
+            error < 0.85 * independent,
+            "{:?} {} {}",
+            kind,
+            error,
+            independent
+        );
+    }
+}
+
+#[test]
+fn scene_file_sampler() {
+    let source = "render width 10 height 10 sampler blue_noise\n";
+    let scene = parse_scene("sampler.scene", source, Path::new("")).unwrap();
+    assert_eq!(scene.params.sampler, SamplerKind::BlueNoise);
+    let written = write_scene(&scene.camera, &scene.params, &scene.world).unwrap();
+    assert!(written.contains("sampler blue_noise"));
+
+    let error = parse_scene(
+        "test.scene",
+        "render width 10 height 10 sampler random\n",
+        Path::new(""),
+    )
+    .err()
+    .expect("should fail")
+    .message;
+    assert_eq!(error, "unknown sampler 'random'");
+}
@@ -0,0 +1,182 @@
+use rtweekend::{
+    render,
+    scene_file::{parse_scene, write_scene, CameraDesc, MaterialDesc, ObjectDesc},
+    scenes, Background, Hittable, HittableList, RandState, RenderParams, Vec3,
+};
+use std::path::Path;
+
+fn params() -> RenderParams {
+    RenderParams {
+        image_width: 30,
+        image_height: 20,
+        samples_per_px: 2,
+        max_depth: 10,
+        background: Background::PASTEL_SKY,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn round_trip_builtin_scenes() {
+    let rand = &mut RandState::from_seed(5);
+    let camera = CameraDesc {
+        aperture: 0.1,
+        focus_dist: 10.0,
+        shutter_close: 1.0,
+        ..Default::default()
+    };
+    let builtin: Vec<fn(&mut RandState) -> HittableList> = vec![
+        scenes::random_scene,
+        scenes::pastel_scene,
+        scenes::moon_scene,
+        |_| scenes::red_blue_scene(),
+        |_| scenes::normal_scene(),
+        |_| scenes::perf_scene(),
+        |_| scenes::light_scene(),
+        |_| scenes::texture_scene(),
+        scenes::bouncing_scene,
+        |_| scenes::instance_scene(),
+        |_| scenes::cornell_box(),
+        |_| scenes::cornell_smoke(),
+        |_| scenes::cloud_scene(),
+        |_| scenes::triangle_scene(),
+    ];
+    for scene in builtin {
+        let seed = rand.next_u64();
+        let world = scene(&mut RandState::from_seed(seed));
+        let written = write_scene(&camera, &params(), &world).unwrap();
+        let parsed = parse_scene("test.scene", &written, Path::new("")).unwrap();
+        assert_eq!(parsed.camera, camera);
+        assert_eq!(parsed.params.background, Background::PASTEL_SKY);
+        assert_eq!(
+            write_scene(&parsed.camera, &parsed.params, &parsed.world).unwrap(),
+            written
+        );
+
+        let original = render(
+            camera.build(),
+            scene(&mut RandState::from_seed(seed)),
+            &HittableList::new(),
+            params(),
+            &mut RandState::from_seed(1),
+        );
+        let from_file = render(
+            parsed.camera.build(),
+            parsed.world,
+            &HittableList::new(),
+            parsed.params,
+            &mut RandState::from_seed(1),
+        );
+        assert!(original == from_file);
+    }
+}
+
+#[test]
+fn parse_example() {
+    let source = "
+# A couple spheres
+camera lookfrom 0 1 5 lookat 0 0 0 fov 40
+render width 300 samples 10 depth 5 # Height comes from the aspect ratio
+camera aspect 1.5
+background black
+
+material ground lambertian 0.5 0.5 0.5
+material gold metal 0.8 0.6 0.2 0.1
+material lamp light 4 4 4
+sphere 0 -100 0 100 ground
+sphere 0 5 0 1 lamp
+sphere 0 1 0 1 gold
+triangle -1 0 -2 1 0 -2 0 1 -2 gold
+";
+    let scene = parse_scene("example.scene", source, Path::new("")).unwrap();
+    assert_eq!(scene.camera.lookfrom, Vec3::new(0.0, 1.0, 5.0));
+    assert_eq!(scene.camera.vertical_fov_degrees, 40.0);
+    assert_eq!(scene.camera.aspect_ratio, 1.5);
+    assert_eq!(scene.params.image_width, 300);
+    assert_eq!(scene.params.image_height, 200);
+    assert_eq!(scene.params.samples_per_px, 10);
+    assert_eq!(scene.params.max_depth, 5);
+    assert_eq!(scene.params.background, Background::Color(Vec3::zero()));
+}
+
+#[test]
+fn scene_lights() {
+    let source = "render width 10 height 10
+material lamp light 4 4 4
+material white lambertian 0.8 0.8 0.8
+sphere 0 5 0 1 lamp
+quad 0 4 0  1 0 0  0 0 1 lamp
+disk 0 3 0  0 -1 0  0.5 lamp
+sphere 0 -100 0 100 white
+object lamps sphere 2 5 0 1 lamp
+instance lamps translate 1 0 0
+";
+    let scene = parse_scene("lights.scene", source, Path::new("")).unwrap();
+    let lights = match scene.lights.describe() {
+        Some(ObjectDesc::Group(lights)) => lights,
+        other => panic!("{:?}", other),
+    };
+    // Lights inside objects aren't sampled
+    assert_eq!(lights.len(), 3);
+    for light in lights {
+        let material = match light {
+            ObjectDesc::Sphere { material, .. }
+            | ObjectDesc::Quad { material, .. }
+            | ObjectDesc::Disk { material, .. } => material,
+            other => panic!("{:?}", other),
+        };
+        assert!(matches!(material, MaterialDesc::DiffuseLight { .. }));
+    }
+}
+
+#[test]
+fn parse_errors() {
+    let error = |source: &str| {
+        parse_scene("test.scene", source, Path::new(""))
+            .err()
+            .expect("should fail")
+    };
+    let render = "render width 10 height 10\n";
+
+    let err = error(&format!("{}sphere 0 0 0 1 nope\n", render));
+    assert_eq!(err.to_string(), "test.scene:2: unknown material 'nope'");
+    let err = error(&format!("{}\n\ncamera fov x\n", render));
+    assert_eq!(err.to_string(), "test.scene:4: expected an angle, got 'x'");
+    let err = error(&format!("{}material a lambertian 1 1\n", render));
+    assert_eq!(err.to_string(), "test.scene:2: expected a color");
+    let err = error(&format!("{}material a lambertian 1 1 1 1\n", render));
+    assert_eq!(err.to_string(), "test.scene:2: unexpected '1'");
+    let err = error(&format!("{}material a metal wood 0.1\n", render));
+    assert_eq!(err.to_string(), "test.scene:2: unknown texture 'wood'");
+    let err = error(&format!("{}texture a checker 1 1 1 1 b\n", render));
+    assert_eq!(err.to_string(), "test.scene:2: unknown texture 'b'");
+    assert_eq!(
+        error("render width 10\ntexture a image missing.png\n").line,
+        2
+    );
+    assert_eq!(error("render width 10\nbox 1 2 3\n").line, 2);
+    assert_eq!(error("render width 10\nbackground stars\n").line, 2);
+    assert_eq!(error("render width 10\nmesh missing.obj\n").line, 2);
+    let mesh =
+        "material m lambertian 1 1 1\ntriangle_mesh positions 3 0 0 0 1 0 0 0 1 0 normals 0 uvs 0";
+    let err = error(&format!("{}{} indices 1 0 1 3 m\n", render, mesh));
+    assert_eq!(
+        err.to_string(),
+        "test.scene:3: vertex index 3 is out of range for 3 positions"
+    );
+    let err = error(&format!("{}{} 1 0 1 2 m\n", render, mesh));
+    assert_eq!(err.to_string(), "test.scene:3: expected 'indices', got '1'");
+    // Settings that would only render a black image
+    let err = error("camera fov 40\nrender width 10 height 10 samples 0\n");
+    assert_eq!(
+        err.to_string(),
+        "test.scene:2: the sample count must be greater than 0"
+    );
+    let err = error("\nrender width 10 height 10 depth -1\n");
+    assert_eq!(
+        err.to_string(),
+        "test.scene:2: the depth must be greater than 0"
+    );
+    // No height or aspect ratio
+    assert_eq!(error("render width 10\n").line, 0);
+}
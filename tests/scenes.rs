@@ -1,8 +1,12 @@
 use rtweekend::{
     //material::{Dielectric, LambertianDiffuse, MaterialType, Metal}, HittableList,
+    material::DiffuseLight,
     render,
     scenes,
+    triangle::Triangle,
+    Background,
     Camera,
+    HittableList,
     RandState,
     RenderParams,
     Vec3,
@@ -24,6 +28,7 @@ fn test() {
         image_height,
         samples_per_px,
         max_depth,
+        ..Default::default()
     };
 
     let world = scenes::normal_scene();
@@ -42,5 +47,149 @@ fn test() {
         dist_to_focus,
     );
 
-    let _output = render(camera, world, render_params, rand);
+    let _output = render(camera, world, &HittableList::new(), render_params, rand);
+}
+
+#[test]
+fn same_seed_same_image() {
+    let render_seeded = |seed| {
+        let rand = &mut RandState::from_seed(seed);
+        let render_params = RenderParams {
+            image_width: 60,
+            image_height: 40,
+            samples_per_px: 4,
+            max_depth: 10,
+            ..Default::default()
+        };
+        let world = scenes::random_scene(rand);
+        let camera = Camera::new(
+            Vec3::new(13.0, 2.0, 3.0),
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            20.0,
+            3.0 / 2.0,
+            0.1,
+            10.0,
+        );
+        render(camera, world, &HittableList::new(), render_params, rand)
+    };
+
+    assert!(render_seeded(7) == render_seeded(7));
+    assert!(render_seeded(7) != render_seeded(8));
+}
+
+/// FNV-1a, to compare renders against a known good one without storing the image
+fn image_hash(image: &[u8]) -> u64 {
+    image.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+/// Renders are bit-identical across runs, thread counts and the parallel feature
+#[test]
+fn golden_image() {
+    let rand = &mut RandState::from_seed(1);
+    let render_params = RenderParams {
+        image_width: 48,
+        image_height: 32,
+        samples_per_px: 8,
+        max_depth: 20,
+        ..Default::default()
+    };
+    let camera = Camera::new(
+        Vec3::new(-2.0, 2.0, 1.0),
+        Vec3::new(0.0, 0.0, -1.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        40.0,
+        3.0 / 2.0,
+        0.0,
+        1.0,
+    );
+    let output = render(
+        camera,
+        scenes::normal_scene(),
+        &HittableList::new(),
+        render_params,
+        rand,
+    );
+
+    assert_eq!(image_hash(&output), 0x11b4_acda_f106_b128);
+}
+
+/// Russian roulette is off by default, so it has its own known good render
+#[test]
+fn golden_image_roulette() {
+    let rand = &mut RandState::from_seed(1);
+    let render_params = RenderParams {
+        image_width: 48,
+        image_height: 32,
+        samples_per_px: 8,
+        max_depth: 20,
+        roulette_depth: Some(5),
+        roulette_probability: 0.95,
+        ..Default::default()
+    };
+    let camera = Camera::new(
+        Vec3::new(-2.0, 2.0, 1.0),
+        Vec3::new(0.0, 0.0, -1.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        40.0,
+        3.0 / 2.0,
+        0.0,
+        1.0,
+    );
+    let output = render(
+        camera,
+        scenes::normal_scene(),
+        &HittableList::new(),
+        render_params,
+        rand,
+    );
+
+    assert_eq!(image_hash(&output), 0xb524_1b07_e06f_687c);
+}
+
+#[test]
+fn emission() {
+    let render_params = RenderParams {
+        image_width: 12,
+        image_height: 8,
+        samples_per_px: 4,
+        max_depth: 10,
+        background: Background::BLACK,
+        ..Default::default()
+    };
+    let camera = || {
+        Camera::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            40.0,
+            3.0 / 2.0,
+            0.0,
+            1.0,
+        )
+    };
+
+    // Without anything emitting light, everything is black
+    let rand = &mut RandState::from_seed(1);
+    let output = render(
+        camera(),
+        scenes::normal_scene(),
+        &HittableList::new(),
+        render_params.clone(),
+        rand,
+    );
+    assert!(output.iter().all(|&c| c == 0));
+
+    // A light filling the whole view. 0.25 is 0.5 after gamma correction
+    let mut world = HittableList::new();
+    world.add(Triangle::new(
+        Vec3::new(-10.0, -10.0, -1.0),
+        Vec3::new(10.0, -10.0, -1.0),
+        Vec3::new(0.0, 10.0, -1.0),
+        DiffuseLight::new(Vec3::repeat(0.25)),
+    ));
+    let output = render(camera(), world, &HittableList::new(), render_params, rand);
+    assert!(output.iter().all(|&c| c == 128));
 }
@@ -0,0 +1,122 @@
+use rtweekend::{
+    png::encode_png,
+    render,
+    scene_file::parse_scene,
+    texture::{Checker, ImageTexture, NoiseKind, NoiseTexture, SolidColor, UvChecker},
+    HittableList, RandState, Vec3,
+};
+
+const RED: Vec3 = Vec3::new(1.0, 0.0, 0.0);
+const BLUE: Vec3 = Vec3::new(0.0, 0.0, 1.0);
+
+#[test]
+fn checker() {
+    let checker = Checker::new(2.0, SolidColor::new(RED), SolidColor::new(BLUE));
+    let at = |x, y, z| checker.value(0.0, 0.0, Vec3::new(x, y, z));
+    assert_eq!(at(0.5, 0.5, 0.5), RED);
+    assert_eq!(at(2.5, 0.5, 0.5), BLUE);
+    assert_eq!(at(2.5, 2.5, 0.5), RED);
+    // Cells keep alternating across zero
+    assert_eq!(at(-0.5, 0.5, 0.5), BLUE);
+    assert_eq!(at(-0.5, -0.5, 0.5), RED);
+    assert_eq!(at(-2.5, -0.5, 0.5), BLUE);
+
+    // Only u and v matter for the uv one
+    let checker = UvChecker::new(0.5, SolidColor::new(RED), SolidColor::new(BLUE));
+    let at = |u, v| checker.value(u, v, Vec3::repeat(0.7));
+    assert_eq!(at(0.25, 0.25), RED);
+    assert_eq!(at(0.75, 0.25), BLUE);
+    assert_eq!(at(0.75, 0.75), RED);
+    assert_eq!(at(-0.25, 0.25), BLUE);
+    assert_eq!(at(-10.25, 3.75), RED);
+}
+
+#[test]
+fn noise() {
+    let rand = &mut RandState::from_seed(9);
+    let points = (0..200)
+        .map(|_| Vec3::random_range(rand, -10.0, 10.0))
+        .collect::<Vec<_>>();
+    for kind in [NoiseKind::Smooth, NoiseKind::Turbulence, NoiseKind::Marble].iter() {
+        let texture = NoiseTexture::new(*kind, 3.0, 1);
+        let same_seed = NoiseTexture::new(*kind, 3.0, 1);
+        let other_seed = NoiseTexture::new(*kind, 3.0, 2);
+        let mut differs = false;
+        for p in &points {
+            let value = texture.value(0.0, 0.0, *p);
+            assert_eq!(value, same_seed.value(0.0, 0.0, *p));
+            assert!(value.x() == value.y() && value.y() == value.z());
+            assert!(value.x() >= 0.0, "{:?} noise is {}", kind, value.x());
+            if *kind != NoiseKind::Turbulence {
+                assert!(value.x() <= 1.0, "{:?} noise is {}", kind, value.x());
+            }
+            differs |= value != other_seed.value(0.0, 0.0, *p);
+        }
+        assert!(differs, "{:?} noise doesn't depend on the seed", kind);
+    }
+}
+
+/// A 2x2 image with red and green on the top row, and blue and white on the bottom one
+const PIXELS: [u8; 12] = [255, 0, 0, 0, 255, 0, 0, 0, 255, 255, 255, 255];
+
+#[test]
+fn image() {
+    let dir = std::env::temp_dir().join("rtweekend_texture_test");
+    std::fs::create_dir_all(&dir).unwrap();
+    let ascii = "P3\n# A comment\n2 2\n255\n255 0 0 0 255 0\n0 0 255 255 255 255\n";
+    std::fs::write(dir.join("ascii.ppm"), ascii).unwrap();
+    let mut binary = b"P6 2 2 255\n".to_vec();
+    binary.extend_from_slice(&PIXELS);
+    std::fs::write(dir.join("binary.ppm"), binary).unwrap();
+    std::fs::write(dir.join("image.png"), encode_png(2, 2, &PIXELS)).unwrap();
+
+    for name in ["ascii.ppm", "binary.ppm", "image.png"].iter() {
+        let texture = ImageTexture::load(dir.join(name)).unwrap();
+        let at = |u, v| texture.value(u, v, Vec3::zero());
+        // v goes up, so the top row is at v = 1
+        assert_eq!(at(0.0, 1.0), RED, "{}", name);
+        assert_eq!(at(0.9, 0.9), Vec3::new(0.0, 1.0, 0.0), "{}", name);
+        assert_eq!(at(0.1, 0.1), BLUE, "{}", name);
+        assert_eq!(at(1.0, 0.0), Vec3::repeat(1.0), "{}", name);
+        // Out of range coordinates are clamped to the edges
+        assert_eq!(at(-1.0, 2.0), RED, "{}", name);
+    }
+
+    let gray = ImageTexture::new(1, 1, vec![128, 128, 128]);
+    let value = gray.value(0.5, 0.5, Vec3::zero()).x();
+    assert!((value - 0.25).abs() < 0.01, "colors are not linearized");
+
+    std::fs::write(dir.join("image.txt"), "not an image").unwrap();
+    assert!(ImageTexture::load(dir.join("image.txt")).is_err());
+    std::fs::write(dir.join("huge.ppm"), b"P6 4294967296 4294967296 255\n").unwrap();
+    assert!(ImageTexture::load(dir.join("huge.ppm")).is_err());
+    assert!(ImageTexture::load(dir.join("missing.ppm")).is_err());
+}
+
+#[test]
+fn image_on_sphere() {
+    // Red on top and blue below, so the sphere should look the same way from the side
+    let dir = std::env::temp_dir().join("rtweekend_texture_sphere_test");
+    std::fs::create_dir_all(&dir).unwrap();
+    let pixels = [255, 0, 0, 255, 0, 0, 0, 0, 255, 0, 0, 255];
+    std::fs::write(dir.join("halves.png"), encode_png(2, 2, &pixels)).unwrap();
+    let source = "
+camera lookfrom 0 0 5 lookat 0 0 0 fov 30 aspect 1
+render width 20 height 20 samples 4 depth 2
+texture halves image halves.png
+material ball lambertian halves
+sphere 0 0 0 1 ball
+";
+    let scene = parse_scene("sphere.scene", source, &dir).unwrap();
+    let image = render(
+        scene.camera.build(),
+        scene.world,
+        &HittableList::new(),
+        scene.params,
+        &mut RandState::from_seed(1),
+    );
+    let px = |x: usize, y: usize| &image[3 * (y * 20 + x)..][..3];
+    let (top, bottom) = (px(10, 5), px(10, 14));
+    assert!(top[0] > top[2], "top is {:?}", top);
+    assert!(bottom[2] > bottom[0], "bottom is {:?}", bottom);
+}
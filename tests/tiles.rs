@@ -0,0 +1,135 @@
+use rtweekend::{
+    render_hdr,
+    scene_file::{parse_scene, write_scene},
+    scenes,
+    tiles::{tiles, Tile, TileOrder},
+    Camera, HittableList, RandState, RenderParams, Vec3,
+};
+use std::path::Path;
+
+#[test]
+fn every_pixel_once() {
+    for &order in TileOrder::ALL.iter() {
+        for &(width, height, size) in [(50, 30, 16), (64, 64, 8), (7, 3, 32), (5, 9, 1)].iter() {
+            let mut covered = vec![0; (width * height) as usize];
+            for tile in tiles(width, height, size, order) {
+                assert!(tile.width <= size && tile.height <= size);
+                for y in tile.y..tile.y + tile.height {
+                    for x in tile.x..tile.x + tile.width {
+                        covered[(y * width + x) as usize] += 1;
+                    }
+                }
+            }
+            assert!(covered.iter().all(|&count| count == 1), "{:?}", order);
+        }
+    }
+}
+
+#[test]
+fn orders() {
+    let corners = |tiles: Vec<Tile>| tiles.iter().map(|t| (t.x, t.y)).collect::<Vec<_>>();
+    assert_eq!(
+        corners(tiles(50, 30, 16, TileOrder::Scanline)),
+        [
+            (0, 0),
+            (16, 0),
+            (32, 0),
+            (48, 0),
+            (0, 16),
+            (16, 16),
+            (32, 16),
+            (48, 16)
+        ]
+    );
+    assert_eq!(
+        tiles(50, 30, 16, TileOrder::Scanline)[3],
+        Tile {
+            x: 48,
+            y: 0,
+            width: 2,
+            height: 16
+        }
+    );
+
+    // The center first, and then rings around it
+    let spiral = corners(tiles(80, 80, 16, TileOrder::Spiral));
+    assert_eq!(spiral[0], (32, 32));
+    let ring = |&(x, y): &(u32, u32)| (x as i32 / 16 - 2).abs().max((y as i32 / 16 - 2).abs());
+    assert!(spiral[1..9].iter().all(|tile| ring(tile) == 1));
+    assert!(spiral[9..].iter().all(|tile| ring(tile) == 2));
+
+    // Every tile is next to the one before
+    let hilbert = corners(tiles(64, 64, 8, TileOrder::Hilbert));
+    assert_eq!(hilbert[0], (0, 0));
+    for pair in hilbert.windows(2) {
+        let (a, b) = (pair[0], pair[1]);
+        let distance = (a.0 as i32 - b.0 as i32).abs() + (a.1 as i32 - b.1 as i32).abs();
+        assert_eq!(distance, 8, "{:?}", pair);
+    }
+    // Even when the image isn't square
+    let hilbert = tiles(100, 40, 8, TileOrder::Hilbert);
+    assert_eq!(hilbert.len(), 13 * 5);
+}
+
+#[test]
+fn same_image() {
+    let camera = || {
+        Camera::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            90.0,
+            3.0 / 2.0,
+            0.0,
+            1.0,
+        )
+    };
+    let render = |tile_size, tile_order| {
+        let params = RenderParams {
+            image_width: 30,
+            image_height: 20,
+            samples_per_px: 8,
+            max_depth: 10,
+            tile_size,
+            tile_order,
+            ..Default::default()
+        };
+        let rand = &mut RandState::from_seed(1);
+        render_hdr(
+            camera(),
+            scenes::normal_scene(),
+            &HittableList::new(),
+            params,
+            rand,
+        )
+    };
+    let image = render(32, TileOrder::Scanline);
+    assert_eq!(render(7, TileOrder::Spiral), image);
+    assert_eq!(render(4, TileOrder::Hilbert), image);
+    assert_eq!(render(1, TileOrder::Scanline), image);
+}
+
+#[test]
+fn scene_file_tiles() {
+    let source = "render width 10 height 10 tiles 16 hilbert\n";
+    let scene = parse_scene("tiles.scene", source, Path::new("")).unwrap();
+    assert_eq!(scene.params.tile_size, 16);
+    assert_eq!(scene.params.tile_order, TileOrder::Hilbert);
+    let written = write_scene(&scene.camera, &scene.params, &scene.world).unwrap();
+    assert!(written.contains("tiles 16 hilbert"));
+
+    let error = |source| {
+        parse_scene("test.scene", source, Path::new(""))
+            .err()
+            .expect("should fail")
+            .message
+    };
+    assert_eq!(
+        error("render width 10 height 10 tiles 0 spiral\n"),
+        "the tile size must be greater than 0"
+    );
+    assert_eq!(
+        error("render width 10 height 10 tiles 8 zigzag\n"),
+        "unknown tile order 'zigzag'"
+    );
+}
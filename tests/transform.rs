@@ -0,0 +1,188 @@
+use rtweekend::{
+    bvh::Aabb,
+    material::LambertianDiffuse,
+    render,
+    scene_file::{parse_scene, write_scene},
+    transform::{share, Matrix4, Transform},
+    triangle::Triangle,
+    HitRecord, Hittable, HittableList, RandState, Ray, Vec3,
+};
+use std::path::Path;
+
+fn assert_near(a: Vec3, b: Vec3) {
+    assert!((a - b).length() < 1e-9, "{:?} != {:?}", a, b);
+}
+
+#[test]
+fn matrices() {
+    let y = Vec3::new(0.0, 1.0, 0.0);
+    let rotation = Matrix4::rotation(y, 90.0);
+    assert_near(
+        rotation.transform_vector(Vec3::new(1.0, 0.0, 0.0)),
+        Vec3::new(0.0, 0.0, -1.0),
+    );
+    let m = Matrix4::translation(Vec3::new(1.0, 2.0, 3.0))
+        * Matrix4::rotation(Vec3::new(1.0, 1.0, 0.0), 30.0)
+        * Matrix4::scaling(Vec3::new(2.0, 0.5, 3.0));
+    // Scaled, rotated and then translated
+    let p = Vec3::new(0.5, -1.0, 2.0);
+    let expected = Matrix4::rotation(Vec3::new(1.0, 1.0, 0.0), 30.0)
+        .transform_point(Vec3::new(1.0, -0.5, 6.0))
+        + Vec3::new(1.0, 2.0, 3.0);
+    assert_near(m.transform_point(p), expected);
+    // Vectors don't move
+    assert_near(
+        Matrix4::translation(Vec3::new(1.0, 2.0, 3.0)).transform_vector(p),
+        p,
+    );
+
+    let inverse = m.inverse().unwrap();
+    for (product, identity) in [m * inverse, inverse * m]
+        .iter()
+        .zip(&[Matrix4::IDENTITY; 2])
+    {
+        for (row, expected) in product.0.iter().zip(&identity.0) {
+            for (x, e) in row.iter().zip(expected) {
+                assert!((x - e).abs() < 1e-9, "{:?}", product);
+            }
+        }
+    }
+    assert!(Matrix4::scaling(Vec3::new(1.0, 0.0, 1.0))
+        .inverse()
+        .is_none());
+}
+
+#[test]
+fn instance_hit() {
+    // A triangle in the xy plane, turned to face +x and moved away
+    let triangle = share(Triangle::new(
+        Vec3::new(0.0, 0.0, 0.0),
+        Vec3::new(1.0, 0.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        LambertianDiffuse::new(Vec3::repeat(0.5)),
+    ));
+    let matrix = Matrix4::translation(Vec3::new(5.0, 0.0, 0.0))
+        * Matrix4::rotation(Vec3::new(0.0, 1.0, 0.0), 90.0)
+        * Matrix4::scaling(Vec3::repeat(2.0));
+    let instance = Transform::new(triangle.clone(), matrix);
+
+    let ray = Ray {
+        orig: Vec3::new(10.0, 0.5, -0.5),
+        dir: Vec3::new(-1.0, 0.0, 0.0),
+        time: 0.0,
+    };
+    let rec = &mut HitRecord::default();
+    assert!(instance.hit(&ray, 0.001, 5.001, rec));
+    assert!(!instance.hit(&ray, 0.001, 4.999, rec));
+    // The original is still where it was
+    assert!(!triangle.hit(&ray, 0.001, f64::INFINITY, rec));
+    let outside = Ray {
+        orig: Vec3::new(10.0, 0.5, -2.5),
+        ..ray
+    };
+    assert!(!instance.hit(&outside, 0.001, f64::INFINITY, rec));
+
+    let mut bbox = Aabb::empty();
+    assert!(instance.bounding_box(&mut bbox));
+    assert!((bbox.min.x() - 5.0).abs() < 1e-3 && (bbox.max.x() - 5.0).abs() < 1e-3);
+    assert!((bbox.min.z() + 2.0).abs() < 1e-3 && bbox.max.z().abs() < 1e-3);
+    assert!(bbox.min.y().abs() < 1e-3 && (bbox.max.y() - 2.0).abs() < 1e-3);
+}
+
+fn render_source(source: &str) -> Vec<u8> {
+    let source = format!(
+        "camera lookfrom 0 1 6 lookat 0 0.5 0 fov 40
+render width 40 height 30 samples 8 depth 6
+material white lambertian 0.8 0.8 0.8
+material red metal 0.9 0.2 0.2 0.2
+sphere 0 -1000 0 1000 white
+{}",
+        source
+    );
+    let scene = parse_scene("transform.scene", &source, Path::new("")).unwrap();
+    render(
+        scene.camera.build(),
+        scene.world,
+        &HittableList::new(),
+        scene.params,
+        &mut RandState::from_seed(6),
+    )
+}
+
+#[test]
+fn instances_render_like_copies() {
+    let copies = render_source(
+        "sphere -1 1 0 1 red
+sphere 1.5 0.5 0 0.5 red
+",
+    );
+    let instances = render_source(
+        "object ball sphere 0 0 0 1 red
+instance ball translate -1 1 0
+instance ball rotate z 30 scale 0.5 0.5 0.5 translate 1.5 0.5 0
+",
+    );
+    // Rounding can change a few samples, but not the image
+    let diff = copies
+        .iter()
+        .zip(&instances)
+        .map(|(a, b)| (*a as i32 - *b as i32).abs())
+        .sum::<i32>();
+    assert!(
+        (diff as f64 / copies.len() as f64) < 1.0,
+        "average difference of {}",
+        diff as f64 / copies.len() as f64
+    );
+}
+
+#[test]
+fn scene_file_instances() {
+    let source = "render width 10 height 10
+material white lambertian 0.8 0.8 0.8
+object pair sphere 0 0 0 1 white
+object pair sphere 2 0 0 1 white
+object row instance pair translate 0 0 1
+object row instance pair translate 0 0 -1 rotate y 90
+instance row scale 1 2 1
+instance pair matrix 1 0 0 5  0 1 0 0  0 0 1 0
+";
+    let scene = parse_scene("instances.scene", source, Path::new("")).unwrap();
+    let written = write_scene(&scene.camera, &scene.params, &scene.world).unwrap();
+    // Shared objects are only written once
+    assert_eq!(written.matches("sphere").count(), 2);
+    let parsed = parse_scene("written.scene", &written, Path::new("")).unwrap();
+    assert_eq!(
+        write_scene(&parsed.camera, &parsed.params, &parsed.world).unwrap(),
+        written
+    );
+
+    let error = |source: &str| {
+        parse_scene("test.scene", source, Path::new(""))
+            .err()
+            .expect("should fail")
+            .message
+    };
+    let base = "render width 10 height 10\nmaterial m lambertian 1 1 1\n";
+    assert_eq!(
+        error(&format!("{}instance nope\n", base)),
+        "unknown object 'nope'"
+    );
+    assert_eq!(
+        error(&format!(
+            "{}object a sphere 0 0 0 1 m\ninstance a scale 1 0 1\n",
+            base
+        )),
+        "the transform can't be inverted"
+    );
+    assert_eq!(
+        error(&format!(
+            "{}object a sphere 0 0 0 1 m\ninstance a\nobject a sphere 1 0 0 1 m\n",
+            base
+        )),
+        "object 'a' can't change after being instanced"
+    );
+    assert_eq!(
+        error(&format!("{}object a camera fov 3\n", base)),
+        "unknown object kind 'camera'"
+    );
+}
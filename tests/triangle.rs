@@ -0,0 +1,178 @@
+use rtweekend::{
+    bvh::Aabb,
+    material::LambertianDiffuse,
+    render, scenes,
+    triangle::{Triangle, TriangleMesh},
+    Camera, HitRecord, Hittable, HittableList, RandState, Ray, RenderParams, Vec3,
+};
+
+fn ray(orig: Vec3, dir: Vec3) -> Ray {
+    Ray {
+        orig,
+        dir,
+        time: 0.0,
+    }
+}
+
+fn close(a: Vec3, b: Vec3) -> bool {
+    (a - b).length() < 1e-9
+}
+
+#[test]
+fn triangle_hit() {
+    let triangle = Triangle::new(
+        Vec3::new(0.0, 0.0, 0.0),
+        Vec3::new(1.0, 0.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        LambertianDiffuse::new(Vec3::repeat(0.5)),
+    );
+    let rec = &mut HitRecord::default();
+
+    // From both sides, with the normal facing the ray. The vertices are counter clockwise seen
+    // from +z, so that's the front
+    let front = ray(Vec3::new(0.25, 0.5, 1.0), Vec3::new(0.0, 0.0, -1.0));
+    let back = ray(Vec3::new(0.25, 0.5, -1.0), Vec3::new(0.0, 0.0, 1.0));
+    assert!(triangle.hit(&front, 0.001, f64::INFINITY, rec));
+    assert!(rec.front_face());
+    assert!(close(rec.normal(), Vec3::new(0.0, 0.0, 1.0)));
+    // The barycentric coordinates of v1 and v2
+    assert_eq!(rec.uv(), (0.25, 0.5));
+    assert!(triangle.hit(&back, 0.001, f64::INFINITY, rec));
+    assert!(!rec.front_face());
+    assert!(close(rec.normal(), Vec3::new(0.0, 0.0, -1.0)));
+    // Outside the edges, too far away, and parallel
+    let outside = ray(Vec3::new(0.75, 0.75, 1.0), Vec3::new(0.0, 0.0, -1.0));
+    let parallel = ray(Vec3::new(-1.0, 0.25, 0.0), Vec3::new(1.0, 0.0, 0.0));
+    assert!(!triangle.hit(&outside, 0.001, f64::INFINITY, rec));
+    assert!(!triangle.hit(&front, 0.001, 0.5, rec));
+    assert!(!triangle.hit(&parallel, 0.001, f64::INFINITY, rec));
+}
+
+#[test]
+fn mesh_hit() {
+    // Unit square in the xy plane made of two triangles
+    let mesh = TriangleMesh::new(
+        vec![
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(1.0, 1.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        ],
+        vec![],
+        vec![[0.0, 0.0], [2.0, 0.0], [2.0, 2.0], [0.0, 2.0]],
+        vec![[0, 1, 2], [0, 2, 3]],
+        LambertianDiffuse::new(Vec3::repeat(0.5)),
+    )
+    .unwrap();
+    let mut bbox = Aabb::empty();
+    assert!(mesh.bounding_box(&mut bbox));
+    assert!(bbox.min.x() <= 0.0 && bbox.max.x() >= 1.0 && bbox.max.y() >= 1.0);
+
+    let rec = &mut HitRecord::default();
+    for &(x, y) in &[(0.1, 0.1), (0.9, 0.1), (0.1, 0.9), (0.9, 0.9)] {
+        let r = ray(Vec3::new(x, y, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(mesh.hit(&r, 0.001, f64::INFINITY, rec));
+        // Interpolated from the uvs of the vertices, in both triangles
+        let (u, v) = rec.uv();
+        assert!((u - 2.0 * x).abs() < 1e-9 && (v - 2.0 * y).abs() < 1e-9);
+        // Flat shaded without normals
+        assert!(rec.front_face());
+        assert!(close(rec.normal(), Vec3::new(0.0, 0.0, 1.0)));
+    }
+    let r = ray(Vec3::new(1.1, 0.5, 1.0), Vec3::new(0.0, 0.0, -1.0));
+    assert!(!mesh.hit(&r, 0.001, f64::INFINITY, rec));
+}
+
+#[test]
+fn mesh_smooth_normals() {
+    // The same square, with normals leaning out to the left and right edges like a cylinder
+    let (left, right) = (Vec3::new(-1.0, 0.0, 1.0), Vec3::new(1.0, 0.0, 1.0));
+    let mesh = TriangleMesh::new(
+        vec![
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(1.0, 1.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        ],
+        vec![left, right, right, left],
+        vec![],
+        vec![[0, 1, 2], [0, 2, 3]],
+        LambertianDiffuse::new(Vec3::repeat(0.5)),
+    )
+    .unwrap();
+    let rec = &mut HitRecord::default();
+    let down = Vec3::new(0.0, 0.0, -1.0);
+
+    assert!(mesh.hit(&ray(Vec3::new(0.5, 0.3, 1.0), down), 0.001, 10.0, rec));
+    assert!(close(rec.normal(), Vec3::new(0.0, 0.0, 1.0)));
+    // A quarter of the way from the left edge, in both triangles
+    let leaning = Vec3::new(-1.0, 0.0, 2.0).unit_vector();
+    for &y in &[0.1, 0.9] {
+        assert!(mesh.hit(&ray(Vec3::new(0.25, y, 1.0), down), 0.001, 10.0, rec));
+        assert!(rec.front_face());
+        assert!(close(rec.normal(), leaning));
+    }
+    // From behind, the shading normal is flipped with the geometric one
+    assert!(mesh.hit(&ray(Vec3::new(0.25, 0.9, -1.0), -down), 0.001, 10.0, rec));
+    assert!(!rec.front_face());
+    assert!(close(rec.normal(), -leaning));
+}
+
+#[test]
+fn mesh_errors() {
+    let positions = vec![
+        Vec3::new(0.0, 0.0, 0.0),
+        Vec3::new(1.0, 0.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+    ];
+    let material = LambertianDiffuse::new(Vec3::repeat(0.5));
+    let mesh = |normals: Vec<Vec3>, uvs: Vec<[f64; 2]>, indices: Vec<[usize; 3]>| {
+        TriangleMesh::new(positions.clone(), normals, uvs, indices, material.clone())
+    };
+    assert!(mesh(vec![], vec![], vec![[0, 1, 2]]).is_ok());
+    assert_eq!(
+        mesh(vec![], vec![], vec![[0, 1, 3]]).err().unwrap(),
+        "vertex index 3 is out of range for 3 positions"
+    );
+    assert_eq!(
+        mesh(vec![Vec3::new(0.0, 0.0, 1.0)], vec![], vec![[0, 1, 2]])
+            .err()
+            .unwrap(),
+        "1 normals for 3 positions"
+    );
+    assert_eq!(
+        mesh(vec![], vec![[0.0, 0.0]; 4], vec![[0, 1, 2]])
+            .err()
+            .unwrap(),
+        "4 uvs for 3 positions"
+    );
+}
+
+#[test]
+fn render_triangle_scene() {
+    let rand = &mut RandState::from_seed(3);
+    let render_params = RenderParams {
+        image_width: 48,
+        image_height: 32,
+        samples_per_px: 4,
+        max_depth: 10,
+        ..Default::default()
+    };
+    let camera = Camera::new(
+        Vec3::new(13.0, 2.0, 3.0),
+        Vec3::new(0.0, 0.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        20.0,
+        3.0 / 2.0,
+        0.0,
+        1.0,
+    );
+    let output = render(
+        camera,
+        scenes::triangle_scene(),
+        &HittableList::new(),
+        render_params,
+        rand,
+    );
+    assert_eq!(output.len(), 48 * 32 * 3);
+}
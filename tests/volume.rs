@@ -0,0 +1,285 @@
+use rtweekend::{
+    bvh::Aabb,
+    material::Isotropic,
+    planar::cuboid,
+    scene_file::{parse_scene, write_scene},
+    transform::share,
+    volume::{ConstantMedium, DensityGrid, GridMedium},
+    HitRecord, Hittable, RandState, Ray, Vec3,
+};
+use std::path::Path;
+use std::sync::Arc;
+
+#[test]
+fn isotropic() {
+    let rand = &mut RandState::from_seed(4);
+    let material = Isotropic::new(Vec3::new(0.2, 0.4, 0.6));
+    let incoming = Ray {
+        orig: Vec3::new(0.0, 0.0, -5.0),
+        dir: Vec3::new(0.0, 0.0, 1.0),
+        time: 0.5,
+    };
+    let rec = HitRecord::default();
+    let mut sum = Vec3::zero();
+    for _ in 0..10000 {
+        let mut attenuation = Vec3::zero();
+        let mut scattered = Ray {
+            orig: Vec3::zero(),
+            dir: Vec3::zero(),
+            time: 0.0,
+        };
+        assert!(material.scatter(rand, &incoming, &rec, &mut attenuation, &mut scattered));
+        assert_eq!(attenuation, Vec3::new(0.2, 0.4, 0.6));
+        assert_eq!(scattered.time, 0.5);
+        assert!((scattered.dir.length() - 1.0).abs() < 1e-9);
+        sum += scattered.dir;
+    }
+    // No preferred direction, in particular not the incoming one
+    assert!((sum / 10000.0).length() < 0.05, "{:?}", sum / 10000.0);
+}
+
+#[test]
+fn constant_medium() {
+    let rand = &mut RandState::from_seed(2);
+    let boundary = share(cuboid(
+        Vec3::repeat(-1.0),
+        Vec3::repeat(1.0),
+        Isotropic::new(Vec3::repeat(1.0)),
+    ));
+    let density = 0.5;
+    let medium = ConstantMedium::new(boundary, density, Isotropic::new(Vec3::repeat(1.0)));
+
+    // The rays travel 2 units inside, starting 4 units away
+    let rays = 20000;
+    let (mut hits, mut first_half) = (0, 0);
+    let rec = &mut HitRecord::default();
+    for _ in 0..rays {
+        let ray = Ray {
+            orig: Vec3::new(0.0, 0.0, -5.0) + Vec3::random_range(rand, -0.5, 0.5),
+            dir: Vec3::new(0.0, 0.0, 1.0),
+            time: 0.0,
+        };
+        assert!(!medium.hit(&ray, 0.001, 3.5, rec));
+        if medium.hit(&ray, 0.001, f64::INFINITY, rec) {
+            hits += 1;
+            // The same ray always scatters in the same place
+            assert!(medium.hit(&ray, 0.001, f64::INFINITY, rec));
+        }
+        first_half += medium.hit(&ray, 0.001, ray.orig.z().abs(), rec) as u32;
+    }
+    let fraction = |hits: u32| hits as f64 / rays as f64;
+    let expected = 1.0 - (-density * 2.0f64).exp();
+    assert!(
+        (fraction(hits) - expected).abs() < 0.02,
+        "{}",
+        fraction(hits)
+    );
+    let expected = 1.0 - (-density * 1.0f64).exp();
+    assert!(
+        (fraction(first_half) - expected).abs() < 0.02,
+        "{}",
+        fraction(first_half)
+    );
+
+    // Missing the boundary misses the medium
+    let outside = Ray {
+        orig: Vec3::new(0.0, 2.0, -5.0),
+        dir: Vec3::new(0.0, 0.0, 1.0),
+        time: 0.0,
+    };
+    assert!(!medium.hit(&outside, 0.001, f64::INFINITY, rec));
+    // Rays that start inside only go through the rest of it
+    let inside = (0..rays)
+        .filter(|_| {
+            let ray = Ray {
+                orig: Vec3::new(0.0, 0.0, 0.5) + Vec3::random_range(rand, -0.1, 0.1),
+                dir: Vec3::new(0.0, 0.0, 1.0),
+                time: 0.0,
+            };
+            medium.hit(&ray, 0.001, f64::INFINITY, rec)
+        })
+        .count();
+    let expected = 1.0 - (-density * 0.5f64).exp();
+    assert!((fraction(inside as u32) - expected).abs() < 0.02);
+}
+
+#[test]
+fn scene_file_media() {
+    let source = "render width 10 height 10
+material fog isotropic 0.9 0.9 0.9
+material white lambertian 0.8 0.8 0.8
+object ball sphere 0 1 0 1 white
+medium ball 0.2 fog
+instance ball translate 3 0 0
+";
+    let scene = parse_scene("media.scene", source, Path::new("")).unwrap();
+    let written = write_scene(&scene.camera, &scene.params, &scene.world).unwrap();
+    assert!(written.contains("isotropic"));
+    assert!(written.contains("medium o0 0.2"));
+    // The boundary is shared with the instance, so it's only written once
+    assert_eq!(written.matches("sphere").count(), 1);
+    let parsed = parse_scene("written.scene", &written, Path::new("")).unwrap();
+    assert_eq!(
+        write_scene(&parsed.camera, &parsed.params, &parsed.world).unwrap(),
+        written
+    );
+
+    let error = |statement: &str| {
+        let source = format!(
+            "render width 10 height 10\nmaterial fog isotropic 1 1 1\nobject ball sphere 0 1 0 1 fog\n{}\n",
+            statement
+        );
+        parse_scene("test.scene", &source, Path::new(""))
+            .err()
+            .expect("should fail")
+            .message
+    };
+    assert_eq!(error("medium nope 1 fog"), "unknown object 'nope'");
+    for density in &["0", "-0.5"] {
+        assert_eq!(
+            error(&format!("medium ball {} fog", density)),
+            "the density must be greater than 0"
+        );
+    }
+}
+
+#[test]
+fn density_grid() {
+    // Two voxels along x, and one along y and z
+    let grid = DensityGrid::new([2, 1, 1], vec![0.0, 1.0]);
+    assert_eq!(grid.max_density(), 1.0);
+    let at = |x| grid.density(Vec3::new(x, 0.3, 0.8));
+    // Voxel centers are at 0.25 and 0.75, and it's constant past them
+    assert_eq!(at(0.1), 0.0);
+    assert_eq!(at(0.25), 0.0);
+    assert!((at(0.5) - 0.5).abs() < 1e-9);
+    assert!((at(0.625) - 0.75).abs() < 1e-9);
+    assert_eq!(at(0.9), 1.0);
+    assert_eq!(at(1.1), 0.0);
+    assert_eq!(grid.density(Vec3::new(0.9, -0.1, 0.5)), 0.0);
+
+    let noise = DensityGrid::noise(16, 3.0, 1);
+    assert_eq!(noise.size(), [16; 3]);
+    assert!(noise.max_density() > 0.0 && noise.max_density() <= 1.0);
+    // It fades out before the corners
+    assert_eq!(noise.density(Vec3::repeat(0.01)), 0.0);
+}
+
+#[test]
+fn grid_medium() {
+    let rand = &mut RandState::from_seed(8);
+    // Half of the highest density where the rays go, so half of the steps are null collisions
+    let grid = Arc::new(DensityGrid::new([2, 1, 1], vec![0.5, 1.0]));
+    let bounds = Aabb::new(Vec3::new(-1.0, -1.0, -1.0), Vec3::new(1.0, 1.0, 1.0));
+    let medium = GridMedium::new(grid, bounds, 1.0, Isotropic::new(Vec3::repeat(1.0)));
+    assert!((medium.density_at(Vec3::new(-0.5, 0.2, 0.3)) - 0.5).abs() < 1e-9);
+
+    let rays = 20000;
+    let mut hits = 0;
+    let rec = &mut HitRecord::default();
+    for _ in 0..rays {
+        let ray = Ray {
+            orig: Vec3::new(-0.5, 0.0, -5.0) + Vec3::random_range(rand, -0.01, 0.01),
+            dir: Vec3::new(0.0, 0.0, 2.0),
+            time: 0.0,
+        };
+        // Never before the box
+        assert!(!medium.hit(&ray, 0.001, 1.99, rec));
+        hits += medium.hit(&ray, 0.001, f64::INFINITY, rec) as u32;
+    }
+    // The same as a constant medium with the density along the rays
+    let expected = 1.0 - (-0.5 * 2.0f64).exp();
+    let fraction = hits as f64 / rays as f64;
+    assert!(
+        (fraction - expected).abs() < 0.02,
+        "{} != {}",
+        fraction,
+        expected
+    );
+}
+
+#[test]
+fn grid_files() {
+    let dir = std::env::temp_dir().join("rtweekend_grid_test");
+    std::fs::create_dir_all(&dir).unwrap();
+    let mut vol = b"VOL\x03".to_vec();
+    for x in [1i32, 2, 1, 1, 1].iter() {
+        vol.extend_from_slice(&x.to_le_bytes());
+    }
+    for x in [0.0f32, 0.0, 0.0, 1.0, 1.0, 1.0, 0.25, 2.0].iter() {
+        vol.extend_from_slice(&x.to_le_bytes());
+    }
+    std::fs::write(dir.join("grid.vol"), &vol).unwrap();
+    let grid = DensityGrid::load_vol(dir.join("grid.vol")).unwrap();
+    assert_eq!(grid.size(), [2, 1, 1]);
+    assert_eq!(grid.max_density(), 2.0);
+    assert_eq!(grid.density(Vec3::new(0.1, 0.5, 0.5)), 0.25);
+
+    std::fs::write(dir.join("short.vol"), &vol[..vol.len() - 1]).unwrap();
+    assert!(DensityGrid::load_vol(dir.join("short.vol")).is_err());
+    std::fs::write(dir.join("bad.vol"), b"not a volume").unwrap();
+    assert!(DensityGrid::load_vol(dir.join("bad.vol")).is_err());
+    let mut huge = b"VOL\x03".to_vec();
+    for x in [1i32, i32::MAX, i32::MAX, i32::MAX, 1].iter() {
+        huge.extend_from_slice(&x.to_le_bytes());
+    }
+    huge.resize(48, 0);
+    std::fs::write(dir.join("huge.vol"), &huge).unwrap();
+    assert_eq!(
+        DensityGrid::load_vol(dir.join("huge.vol")).err().unwrap(),
+        "invalid .vol size"
+    );
+
+    std::fs::write(dir.join("grid.raw"), [0u8, 51, 255, 0, 0, 0]).unwrap();
+    let grid = DensityGrid::load_raw(dir.join("grid.raw"), [3, 2, 1]).unwrap();
+    assert_eq!(grid.max_density(), 1.0);
+    assert!((grid.density(Vec3::new(0.5, 0.1, 0.5)) - 0.2).abs() < 1e-6);
+    assert!(DensityGrid::load_raw(dir.join("grid.raw"), [2, 2, 2]).is_err());
+    assert_eq!(
+        DensityGrid::load_raw(dir.join("grid.raw"), [usize::MAX, 2, 1])
+            .err()
+            .unwrap(),
+        "invalid grid size"
+    );
+
+    let source = "render width 10 height 10
+material fog isotropic 0.9 0.9 0.9
+grid cloud noise 8 2 3
+grid file vol grid.vol
+grid raw raw grid.raw 3 2 1
+grid_medium cloud -1 0 -1 1 2 1 5 fog
+grid_medium cloud 2 0 -1 4 2 1 5 fog
+grid_medium file -1 0 2 1 2 4 1 fog
+grid_medium raw -1 0 5 1 2 7 1 fog
+";
+    let scene = parse_scene("grids.scene", source, &dir).unwrap();
+    let written = write_scene(&scene.camera, &scene.params, &scene.world).unwrap();
+    // The grid is only written once
+    assert_eq!(written.matches("noise 8 2 3").count(), 1);
+    assert_eq!(written.matches("grid_medium").count(), 4);
+    let parsed = parse_scene("written.scene", &written, Path::new("")).unwrap();
+    assert_eq!(
+        write_scene(&parsed.camera, &parsed.params, &parsed.world).unwrap(),
+        written
+    );
+
+    let error = |source: &str| {
+        parse_scene("test.scene", source, &dir)
+            .err()
+            .expect("should fail")
+            .message
+    };
+    let base = "render width 10 height 10\nmaterial fog isotropic 1 1 1\n";
+    assert_eq!(
+        error(&format!("{}grid_medium nope 0 0 0 1 1 1 1 fog\n", base)),
+        "unknown grid 'nope'"
+    );
+    assert_eq!(
+        error(&format!(
+            "{}grid g noise 4 1\ngrid_medium g 0 0 0 1 -1 1 1 fog\n",
+            base
+        )),
+        "the first corner of a grid_medium has to be the lowest"
+    );
+    assert!(error(&format!("{}grid g raw grid.raw 4 4 4\n", base)).starts_with("could not load"));
+}